@@ -0,0 +1,3 @@
+// The exported rlox_* functions are defined in the main crate's ffi module;
+// linking it in is all this crate does.
+pub use crafting_interpreters::ffi::*;
@@ -0,0 +1,33 @@
+/* C API for the `ffi` feature, built as librlox by the capi crate
+ * (cargo build -p rlox-capi). Written by hand: keep it in step with
+ * src/ffi.rs, which the ffi_header_matches test checks.
+ */
+
+#ifndef RLOX_H
+#define RLOX_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct RloxInterpreter RloxInterpreter;
+
+RloxInterpreter *rlox_new(void);
+
+void rlox_free(RloxInterpreter *lox);
+
+/* Returns 0 on success, 65 for scan/parse errors, 70 for runtime errors and 64 for bad arguments. */
+int rlox_eval(RloxInterpreter *lox, const char *source);
+
+/* Strings returned below are owned by the interpreter and valid until the next rlox_eval or rlox_free. */
+const char *rlox_last_result(const RloxInterpreter *lox);
+
+const char *rlox_last_error(const RloxInterpreter *lox);
+
+const char *rlox_last_output(const RloxInterpreter *lox);
+
+#ifdef __cplusplus
+} /* extern "C" */
+#endif
+
+#endif /* RLOX_H */
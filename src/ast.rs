@@ -0,0 +1,196 @@
+use crate::expr::Expr;
+use crate::node::NodeId;
+use crate::stmt::Stmt;
+use crate::token::Token;
+
+pub fn ident(name: &str) -> Token {
+    Token::Identifier {
+        lexeme: String::from(name),
+        literal: String::from(name),
+        line: 0,
+    }
+}
+
+pub fn num(n: f32) -> Expr {
+    Expr::literal_num(n)
+}
+
+pub fn string(s: &str) -> Expr {
+    Expr::literal_string(s)
+}
+
+pub fn boolean(b: bool) -> Expr {
+    Expr::literal_bool(b)
+}
+
+pub fn nil() -> Expr {
+    Expr::literal_nil()
+}
+
+pub fn variable(name: &str) -> Expr {
+    Expr::Variable {
+        id: NodeId::next(),
+        name: ident(name),
+    }
+}
+
+pub fn assign(name: &str, value: Expr) -> Expr {
+    Expr::Assign {
+        id: NodeId::next(),
+        name: ident(name),
+        value: Box::new(value),
+    }
+}
+
+pub fn binary(left: Expr, operator: Token, right: Expr) -> Expr {
+    Expr::Binary {
+        id: NodeId::next(),
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+pub fn unary(operator: Token, right: Expr) -> Expr {
+    Expr::Unary {
+        id: NodeId::next(),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+pub fn logical(left: Expr, operator: Token, right: Expr) -> Expr {
+    Expr::Logical {
+        id: NodeId::next(),
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+pub fn grouping(expression: Expr) -> Expr {
+    Expr::Grouping {
+        id: NodeId::next(),
+        expression: Box::new(expression),
+    }
+}
+
+pub fn call(callee: Expr, arguments: Vec<Expr>) -> Expr {
+    Expr::Call {
+        id: NodeId::next(),
+        callee: Box::new(callee),
+        paren: Token::RightParen { line: 0 },
+        arguments,
+    }
+}
+
+pub fn index(object: Expr, index: Expr) -> Expr {
+    Expr::Index {
+        id: NodeId::next(),
+        object: Box::new(object),
+        bracket: Token::RightBracket { line: 0 },
+        index: Box::new(index),
+    }
+}
+
+pub fn expression(expr: Expr) -> Stmt {
+    Stmt::from(expr)
+}
+
+pub fn print(expr: Expr) -> Stmt {
+    Stmt::Print {
+        id: NodeId::next(),
+        keyword: Token::Print { line: 0 },
+        expr: Box::new(expr),
+    }
+}
+
+pub fn var(name: &str, initializer: Option<Expr>) -> Stmt {
+    Stmt::Variable {
+        id: NodeId::next(),
+        name: ident(name),
+        initializer: initializer.map(Box::new),
+        doc: None,
+    }
+}
+
+pub fn block(stmts: Vec<Stmt>) -> Stmt {
+    Stmt::Block {
+        id: NodeId::next(),
+        stmts,
+    }
+}
+
+pub fn function(name: &str, params: &[&str], body: Vec<Stmt>) -> Stmt {
+    Stmt::Function {
+        id: NodeId::next(),
+        name: ident(name),
+        params: params.iter().map(|p| ident(p)).collect(),
+        body,
+        doc: None,
+    }
+}
+
+pub fn ret(value: Option<Expr>) -> Stmt {
+    Stmt::Return {
+        id: NodeId::next(),
+        keyword: Token::Return { line: 0 },
+        value: value.map(Box::new),
+    }
+}
+
+pub fn while_loop(condition: Expr, body: Stmt) -> Stmt {
+    Stmt::While {
+        id: NodeId::next(),
+        condition: Box::new(condition),
+        body: Box::new(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::InterpreterResult;
+    #[test]
+    fn ast_builders() -> InterpreterResult<()> {
+        let program = vec![
+            function(
+                "add",
+                &["a", "b"],
+                vec![ret(Some(binary(
+                    variable("a"),
+                    Token::Plus { line: 0 },
+                    variable("b"),
+                )))],
+            ),
+            var("n", Some(num(0.0))),
+            while_loop(
+                binary(variable("n"), Token::Less { line: 0 }, num(3.0)),
+                block(vec![
+                    expression(assign(
+                        "n",
+                        call(variable("add"), vec![variable("n"), num(1.0)]),
+                    )),
+                    print(grouping(unary(Token::Minus { line: 0 }, variable("n")))),
+                ]),
+            ),
+        ];
+        let printed = program
+            .iter()
+            .map(Stmt::print)
+            .collect::<InterpreterResult<Vec<_>>>()?;
+        assert_eq!(
+            printed,
+            vec![
+                "(fun add (a b) (return (+ a b)))",
+                "(var n 0)",
+                "(while (< n 3) (block (; (= n (call add n 1))) (print (grouping (- n)))))",
+            ]
+        );
+        let interpreter = crate::interpreter::Interpreter::default();
+        interpreter.capture_output(true);
+        interpreter.interpret_all(&program)?;
+        assert_eq!(interpreter.take_output(), "-1\n-2\n-3\n");
+        Ok(())
+    }
+}
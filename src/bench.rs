@@ -0,0 +1,166 @@
+use crate::errors::InterpreterResult;
+use crate::Runner;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+pub(crate) const DEFAULT_ITERS: usize = 10;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+pub(crate) fn allocations() -> (usize, usize) {
+    (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+#[derive(Debug)]
+pub(crate) struct Sample {
+    pub(crate) elapsed: Duration,
+    pub(crate) allocations: usize,
+    pub(crate) bytes: usize,
+    pub(crate) output: usize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    samples: Vec<Sample>,
+}
+
+impl Report {
+    pub(crate) fn min(&self) -> Duration {
+        self.samples
+            .iter()
+            .map(|s| s.elapsed)
+            .min()
+            .unwrap_or_default()
+    }
+    pub(crate) fn max(&self) -> Duration {
+        self.samples
+            .iter()
+            .map(|s| s.elapsed)
+            .max()
+            .unwrap_or_default()
+    }
+    pub(crate) fn mean(&self) -> Duration {
+        if self.samples.is_empty() {
+            Duration::default()
+        } else {
+            self.samples.iter().map(|s| s.elapsed).sum::<Duration>() / self.samples.len() as u32
+        }
+    }
+    fn mean_of(&self, f: impl Fn(&Sample) -> usize) -> usize {
+        if self.samples.is_empty() {
+            0
+        } else {
+            self.samples.iter().map(f).sum::<usize>() / self.samples.len()
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "iterations: {}", self.samples.len())?;
+        writeln!(
+            f,
+            "time: min {:?}, mean {:?}, max {:?}",
+            self.min(),
+            self.mean(),
+            self.max()
+        )?;
+        writeln!(
+            f,
+            "allocations: mean {} ({} bytes) per iteration",
+            self.mean_of(|s| s.allocations),
+            self.mean_of(|s| s.bytes)
+        )?;
+        write!(
+            f,
+            "output: {} bytes per iteration, discarded",
+            self.mean_of(|s| s.output)
+        )
+    }
+}
+
+// The program's output is captured rather than printed, so it stays out of
+// both the report and the timings.
+pub(crate) fn bench(source: &str, iters: usize) -> InterpreterResult<Report> {
+    let mut report = Report::default();
+    for _ in 0..iters {
+        let runner = Runner::new();
+        runner.interpreter.capture_output(true);
+        let (allocs_before, bytes_before) = allocations();
+        let start = Instant::now();
+        runner.run(String::from(source))?;
+        let elapsed = start.elapsed();
+        let (allocs_after, bytes_after) = allocations();
+        report.samples.push(Sample {
+            elapsed,
+            allocations: allocs_after - allocs_before,
+            bytes: bytes_after - bytes_before,
+            output: runner.interpreter.take_output().len(),
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn sample(ms: u64, allocations: usize) -> Sample {
+        Sample {
+            elapsed: Duration::from_millis(ms),
+            allocations,
+            bytes: allocations * 8,
+            output: 0,
+        }
+    }
+    #[test]
+    fn bench_report_stats() {
+        let report = Report {
+            samples: vec![sample(3, 10), sample(1, 20), sample(2, 30)],
+        };
+        assert_eq!(report.min(), Duration::from_millis(1));
+        assert_eq!(report.max(), Duration::from_millis(3));
+        assert_eq!(report.mean(), Duration::from_millis(2));
+        assert_eq!(report.mean_of(|s| s.allocations), 20);
+        assert_eq!(report.mean_of(|s| s.bytes), 160);
+    }
+    #[test]
+    fn bench_report_empty() {
+        let report = Report::default();
+        assert_eq!(report.mean(), Duration::default());
+        assert_eq!(report.mean_of(|s| s.allocations), 0);
+    }
+    #[test]
+    fn bench_runs_iterations() -> InterpreterResult<()> {
+        let report = bench("var a = max(1, 2); print a;", 3)?;
+        assert_eq!(report.samples.len(), 3);
+        assert_eq!(report.mean_of(|s| s.output), 2);
+        assert!(report
+            .to_string()
+            .ends_with("output: 2 bytes per iteration, discarded"));
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use crate::errors::{InterpreterError, InterpreterResult};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Capability {
+    Fs,
+    Env,
+    Net,
+    Process,
+    Ffi,
+    Threads,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Capabilities {
+    pub allow_fs: bool,
+    pub allow_env: bool,
+    pub allow_net: bool,
+    pub allow_process: bool,
+    pub allow_ffi: bool,
+    pub allow_threads: bool,
+}
+
+impl Capabilities {
+    pub fn all() -> Self {
+        Self {
+            allow_fs: true,
+            allow_env: true,
+            allow_net: true,
+            allow_process: true,
+            allow_ffi: true,
+            allow_threads: true,
+        }
+    }
+    // Nothing that reaches outside the interpreter's own process: threads,
+    // but no files, environment, network, subprocesses or foreign code.
+    pub fn safe() -> Self {
+        Self {
+            allow_threads: true,
+            ..Self::none()
+        }
+    }
+    pub fn none() -> Self {
+        Self {
+            allow_fs: false,
+            allow_env: false,
+            allow_net: false,
+            allow_process: false,
+            allow_ffi: false,
+            allow_threads: false,
+        }
+    }
+    pub fn with(self, capability: Capability) -> Self {
+        Self {
+            allow_fs: self.allow_fs || capability == Capability::Fs,
+            allow_env: self.allow_env || capability == Capability::Env,
+            allow_net: self.allow_net || capability == Capability::Net,
+            allow_process: self.allow_process || capability == Capability::Process,
+            allow_ffi: self.allow_ffi || capability == Capability::Ffi,
+            allow_threads: self.allow_threads || capability == Capability::Threads,
+        }
+    }
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Fs => self.allow_fs,
+            Capability::Env => self.allow_env,
+            Capability::Net => self.allow_net,
+            Capability::Process => self.allow_process,
+            Capability::Ffi => self.allow_ffi,
+            Capability::Threads => self.allow_threads,
+        }
+    }
+    pub(crate) fn require(&self, capability: Capability, line: usize) -> InterpreterResult<()> {
+        if self.allows(capability) {
+            Ok(())
+        } else {
+            Err(InterpreterError::CapabilityDenied {
+                capability: capability.name(),
+                line,
+            })
+        }
+    }
+}
+
+// A script gets nothing beyond its own process unless the embedder or the
+// command line grants it, so running one you didn't write is safe by default.
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::safe()
+    }
+}
+
+impl Capability {
+    // The names --allow takes.
+    pub(crate) const FLAGS: [(&'static str, Capability); 6] = [
+        ("fs", Capability::Fs),
+        ("env", Capability::Env),
+        ("net", Capability::Net),
+        ("process", Capability::Process),
+        ("ffi", Capability::Ffi),
+        ("threads", Capability::Threads),
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Capability::Fs => "filesystem",
+            Capability::Env => "environment",
+            Capability::Net => "network",
+            Capability::Process => "process",
+            Capability::Ffi => "foreign function",
+            Capability::Threads => "thread",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn capabilities_require() {
+        assert!(Capabilities::all().require(Capability::Ffi, 1).is_ok());
+        assert!(Capabilities::none()
+            .with(Capability::Fs)
+            .require(Capability::Fs, 1)
+            .is_ok());
+        assert_eq!(
+            Capability::FLAGS
+                .iter()
+                .fold(Capabilities::none(), |caps, (_, capability)| caps
+                    .with(*capability)),
+            Capabilities::all()
+        );
+        let caps = Capabilities {
+            allow_env: true,
+            ..Capabilities::none()
+        };
+        assert!(caps.require(Capability::Env, 1).is_ok());
+        assert!(matches!(
+            caps.require(Capability::Fs, 3),
+            Err(InterpreterError::CapabilityDenied {
+                capability: "filesystem",
+                line: 3
+            })
+        ));
+        assert!(!caps.allows(Capability::Net));
+        assert!(!caps.allows(Capability::Process));
+        assert!(!caps.allows(Capability::Ffi));
+        assert!(!caps.allows(Capability::Threads));
+    }
+    #[test]
+    fn capabilities_default_is_safe() {
+        assert_eq!(Capabilities::default(), Capabilities::safe());
+        let caps = Capabilities::default();
+        assert!(caps.allows(Capability::Threads));
+        for capability in [
+            Capability::Fs,
+            Capability::Env,
+            Capability::Net,
+            Capability::Process,
+            Capability::Ffi,
+        ] {
+            assert!(!caps.allows(capability));
+        }
+        let mut lox = crate::lox::Lox::new();
+        assert!(matches!(
+            lox.eval_str("readFile(\"/etc/hostname\");"),
+            Err(InterpreterError::CapabilityDenied {
+                capability: "filesystem",
+                line: 1
+            })
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::function::{Callable, LoxFunction};
+use crate::value::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+pub struct LoxClass {
+    pub(crate) name: String,
+    pub(crate) methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl LoxClass {
+    pub(crate) fn new(name: String, methods: HashMap<String, Rc<LoxFunction>>) -> Self {
+        Self { name, methods }
+    }
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+    pub(crate) fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        self.methods.get(name).cloned()
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+pub struct LoxInstance {
+    pub(crate) class: Rc<LoxClass>,
+    pub(crate) fields: HashMap<String, Value>,
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl LoxInstance {
+    pub(crate) fn new(class: Rc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: HashMap::default(),
+        }
+    }
+    pub(crate) fn get(&self, name: &str) -> Option<Value> {
+        self.fields.get(name).cloned().or_else(|| {
+            self.class
+                .find_method(name)
+                .map(|method| Value::Callable(Callable::Lox(method)))
+        })
+    }
+    pub(crate) fn set(&mut self, name: &str, value: Value) {
+        self.fields.insert(String::from(name), value);
+    }
+}
+
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::Token;
+    use std::cell::RefCell;
+    #[test]
+    fn class_instance_fields_and_methods() {
+        let method = Rc::new(LoxFunction {
+            name: Token::Identifier {
+                literal: String::from("eat"),
+                lexeme: String::from("eat"),
+                line: 1,
+            },
+            params: Vec::default(),
+            body: Vec::default(),
+            closure: None,
+            locals: Default::default(),
+            this: None,
+        });
+        let class = Rc::new(LoxClass::new(
+            String::from("Bagel"),
+            HashMap::from([(String::from("eat"), method)]),
+        ));
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(&class))));
+        let value = Value::Instance(Rc::clone(&instance));
+        assert_eq!(Value::Class(Rc::clone(&class)).to_string(), "Bagel");
+        assert_eq!(value.to_string(), "Bagel instance");
+        assert_eq!(value.type_name(), "instance");
+        assert!(instance.borrow().get("topping").is_none());
+        instance
+            .borrow_mut()
+            .set("topping", Value::r#String("seeds".into()));
+        assert_eq!(
+            instance.borrow().get("topping"),
+            Some(Value::r#String("seeds".into()))
+        );
+        assert!(matches!(
+            instance.borrow().get("eat"),
+            Some(Value::Callable(Callable::Lox(_)))
+        ));
+        assert_eq!(value, Value::Instance(instance));
+        assert_ne!(
+            value,
+            Value::Instance(Rc::new(RefCell::new(LoxInstance::new(class))))
+        );
+    }
+}
@@ -0,0 +1,746 @@
+use crate::bench::DEFAULT_ITERS;
+use crate::capabilities::{Capabilities, Capability};
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::limits::Limits;
+use std::path::Path;
+
+const DEFAULT_LCOV: &str = "lcov.info";
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Mode {
+    Prompt {
+        options: RunOptions,
+    },
+    File {
+        fname: String,
+        options: RunOptions,
+    },
+    Interactive {
+        fname: String,
+        options: RunOptions,
+    },
+    Eval {
+        source: String,
+        options: RunOptions,
+    },
+    Tokens {
+        fname: String,
+    },
+    Ast {
+        fname: String,
+    },
+    Check {
+        fname: String,
+        strict: Option<bool>,
+        prelude: Option<bool>,
+    },
+    Debug {
+        fname: String,
+    },
+    Coverage {
+        fname: String,
+        out: String,
+    },
+    Test {
+        path: String,
+    },
+    Doc {
+        fname: String,
+    },
+    Bench {
+        fname: String,
+        iters: usize,
+    },
+    Compile {
+        fname: String,
+        out: String,
+    },
+    Format {
+        fname: String,
+        output: FormatOutput,
+    },
+}
+
+// Switches are None unless given on the command line, so that lox.toml can
+// fill them in; see Config::apply.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct RunOptions {
+    pub(crate) strict: Option<bool>,
+    pub(crate) stats: Option<bool>,
+    pub(crate) latin1: Option<bool>,
+    pub(crate) prelude: Option<bool>,
+    pub(crate) limits: Limits,
+    pub(crate) capabilities: Capabilities,
+    pub(crate) plugins: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum FormatOutput {
+    InPlace,
+    Stdout,
+    Check,
+}
+
+pub(crate) fn parse_args<I>(args: I) -> InterpreterResult<Mode>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter().peekable();
+    let mut mode = None;
+    let mut iters = None;
+    let mut out = None;
+    let mut options = RunOptions::default();
+    match args.peek().map(String::as_str) {
+        Some("bench") => {
+            args.next();
+            mode = Some(Mode::Bench {
+                fname: value(&mut args)?,
+                iters: DEFAULT_ITERS,
+            });
+        }
+        Some("compile") => {
+            args.next();
+            let fname = value(&mut args)?;
+            mode = Some(Mode::Compile {
+                out: Path::new(&fname)
+                    .with_extension("loxc")
+                    .to_string_lossy()
+                    .into_owned(),
+                fname,
+            });
+        }
+        Some("doc") => {
+            args.next();
+            mode = Some(Mode::Doc {
+                fname: value(&mut args)?,
+            });
+        }
+        Some("test") => {
+            args.next();
+            mode = Some(Mode::Test {
+                path: value(&mut args)?,
+            });
+        }
+        Some("fmt") => {
+            args.next();
+            return format_args(args);
+        }
+        _ => {}
+    }
+    while let Some(arg) = args.next() {
+        let next = match arg.as_str() {
+            "--iters" => {
+                let n = value(&mut args)?;
+                iters = Some(n.parse().map_err(|_| InterpreterError::Usage)?);
+                continue;
+            }
+            "-o" | "--output" => {
+                out = Some(value(&mut args)?);
+                continue;
+            }
+            "--strict" | "--no-strict" => {
+                options.strict = Some(arg == "--strict");
+                continue;
+            }
+            "--stats" | "--no-stats" => {
+                options.stats = Some(arg == "--stats");
+                continue;
+            }
+            "--latin1" | "--no-latin1" => {
+                options.latin1 = Some(arg == "--latin1");
+                continue;
+            }
+            "--prelude" | "--no-prelude" => {
+                options.prelude = Some(arg == "--prelude");
+                continue;
+            }
+            "--allow" => {
+                for name in value(&mut args)?.split(',') {
+                    options.capabilities = match name {
+                        "all" => Capabilities::all(),
+                        _ => Capability::FLAGS
+                            .iter()
+                            .find(|(flag, _)| *flag == name)
+                            .map(|(_, capability)| options.capabilities.with(*capability))
+                            .ok_or(InterpreterError::Usage)?,
+                    };
+                }
+                continue;
+            }
+            #[cfg(feature = "dlopen")]
+            "--plugin" => {
+                options.plugins.push(value(&mut args)?);
+                continue;
+            }
+            "-i" | "--interactive" => Mode::Interactive {
+                fname: value(&mut args)?,
+                options: RunOptions::default(),
+            },
+            "-e" | "--eval" => Mode::Eval {
+                source: value(&mut args)?,
+                options: RunOptions::default(),
+            },
+            "--tokens" => Mode::Tokens {
+                fname: value(&mut args)?,
+            },
+            "--ast" => Mode::Ast {
+                fname: value(&mut args)?,
+            },
+            "--check" => Mode::Check {
+                fname: value(&mut args)?,
+                strict: None,
+                prelude: None,
+            },
+            "--debug" | "--step" => Mode::Debug {
+                fname: value(&mut args)?,
+            },
+            "--coverage" => Mode::Coverage {
+                fname: value(&mut args)?,
+                out: String::from(DEFAULT_LCOV),
+            },
+            flag if flag.starts_with('-') => return Err(InterpreterError::Usage),
+            _ => Mode::File {
+                fname: arg,
+                options: RunOptions::default(),
+            },
+        };
+        if mode.replace(next).is_some() {
+            return Err(InterpreterError::Usage);
+        }
+    }
+    let mode = match (mode, iters, out) {
+        (Some(Mode::Bench { fname, .. }), Some(iters), None) => Mode::Bench { fname, iters },
+        (Some(Mode::Compile { fname, .. }), None, Some(out)) => Mode::Compile { fname, out },
+        (Some(Mode::Coverage { fname, .. }), None, Some(out)) => Mode::Coverage { fname, out },
+        (mode, None, None) => mode.unwrap_or(Mode::Prompt {
+            options: RunOptions::default(),
+        }),
+        _ => return Err(InterpreterError::Usage),
+    };
+    match mode {
+        Mode::Prompt { .. } => Ok(Mode::Prompt { options }),
+        Mode::File { fname, .. } => Ok(Mode::File { fname, options }),
+        Mode::Interactive { fname, .. } => Ok(Mode::Interactive { fname, options }),
+        Mode::Eval { source, .. } => Ok(Mode::Eval { source, options }),
+        Mode::Check { fname, .. }
+            if options.stats.is_none()
+                && options.latin1.is_none()
+                && options.plugins.is_empty()
+                && options.capabilities == Capabilities::default() =>
+        {
+            Ok(Mode::Check {
+                fname,
+                strict: options.strict,
+                prelude: options.prelude,
+            })
+        }
+        _ if options != RunOptions::default() => Err(InterpreterError::Usage),
+        mode => Ok(mode),
+    }
+}
+
+fn format_args(args: impl Iterator<Item = String>) -> InterpreterResult<Mode> {
+    let mut fname = None;
+    let mut output = FormatOutput::InPlace;
+    for arg in args {
+        match arg.as_str() {
+            "--check" if output == FormatOutput::InPlace => output = FormatOutput::Check,
+            "--stdout" if output == FormatOutput::InPlace => output = FormatOutput::Stdout,
+            flag if flag.starts_with('-') => return Err(InterpreterError::Usage),
+            _ if fname.is_none() => fname = Some(arg),
+            _ => return Err(InterpreterError::Usage),
+        }
+    }
+    Ok(Mode::Format {
+        fname: fname.ok_or(InterpreterError::Usage)?,
+        output,
+    })
+}
+
+fn value(args: &mut impl Iterator<Item = String>) -> InterpreterResult<String> {
+    args.next().ok_or(InterpreterError::Usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| String::from(*a)).collect()
+    }
+    #[test]
+    fn cli_prompt() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&[]))?,
+            Mode::Prompt {
+                options: RunOptions::default()
+            }
+        );
+        Ok(())
+    }
+    #[test]
+    fn cli_file() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["foo.lox"]))?,
+            Mode::File {
+                fname: String::from("foo.lox"),
+                options: RunOptions::default()
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--tokens", "foo.lox"]))?,
+            Mode::Tokens {
+                fname: String::from("foo.lox")
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--ast", "foo.lox"]))?,
+            Mode::Ast {
+                fname: String::from("foo.lox")
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--check", "foo.lox"]))?,
+            Mode::Check {
+                fname: String::from("foo.lox"),
+                strict: None,
+                prelude: None
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--debug", "foo.lox"]))?,
+            Mode::Debug {
+                fname: String::from("foo.lox")
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--step", "foo.lox"]))?,
+            Mode::Debug {
+                fname: String::from("foo.lox")
+            }
+        );
+        Ok(())
+    }
+    #[test]
+    fn cli_eval() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["-e", "print 1 + 2;"]))?,
+            Mode::Eval {
+                source: String::from("print 1 + 2;"),
+                options: RunOptions::default()
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--eval", "print 1;"]))?,
+            Mode::Eval {
+                source: String::from("print 1;"),
+                options: RunOptions::default()
+            }
+        );
+        Ok(())
+    }
+    #[test]
+    fn cli_interactive() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["-i", "foo.lox", "--strict"]))?,
+            Mode::Interactive {
+                fname: String::from("foo.lox"),
+                options: RunOptions {
+                    strict: Some(true),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["-i"])),
+            Err(InterpreterError::Usage)
+        ));
+        assert!(matches!(
+            parse_args(args(&["-i", "foo.lox", "bar.lox"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_strict() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["--strict"]))?,
+            Mode::Prompt {
+                options: RunOptions {
+                    strict: Some(true),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--strict", "foo.lox"]))?,
+            Mode::File {
+                fname: String::from("foo.lox"),
+                options: RunOptions {
+                    strict: Some(true),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["-e", "print 1;", "--strict"]))?,
+            Mode::Eval {
+                source: String::from("print 1;"),
+                options: RunOptions {
+                    strict: Some(true),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--check", "foo.lox", "--strict"]))?,
+            Mode::Check {
+                fname: String::from("foo.lox"),
+                strict: Some(true),
+                prelude: None
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["--strict", "--tokens", "foo.lox"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_stats() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["--stats", "foo.lox", "--strict"]))?,
+            Mode::File {
+                fname: String::from("foo.lox"),
+                options: RunOptions {
+                    strict: Some(true),
+                    stats: Some(true),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["-e", "print 1;", "--stats"]))?,
+            Mode::Eval {
+                source: String::from("print 1;"),
+                options: RunOptions {
+                    stats: Some(true),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["--check", "foo.lox", "--stats"])),
+            Err(InterpreterError::Usage)
+        ));
+        assert!(matches!(
+            parse_args(args(&["bench", "foo.lox", "--stats"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_latin1() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["--latin1", "foo.lox"]))?,
+            Mode::File {
+                fname: String::from("foo.lox"),
+                options: RunOptions {
+                    latin1: Some(true),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["--latin1", "--tokens", "foo.lox"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_no_prelude() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["--no-prelude"]))?,
+            Mode::Prompt {
+                options: RunOptions {
+                    prelude: Some(false),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--strict", "--check", "foo.lox", "--no-prelude"]))?,
+            Mode::Check {
+                fname: String::from("foo.lox"),
+                strict: Some(true),
+                prelude: Some(false)
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["--no-prelude", "--ast", "foo.lox"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_negated_flags() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&[
+                "--no-strict",
+                "--no-stats",
+                "--no-latin1",
+                "--prelude",
+                "foo.lox"
+            ]))?,
+            Mode::File {
+                fname: String::from("foo.lox"),
+                options: RunOptions {
+                    strict: Some(false),
+                    stats: Some(false),
+                    latin1: Some(false),
+                    prelude: Some(true),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--strict", "--no-strict", "--check", "foo.lox"]))?,
+            Mode::Check {
+                fname: String::from("foo.lox"),
+                strict: Some(false),
+                prelude: None
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["--no-strict", "--ast", "foo.lox"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_usage_lists_every_flag() {
+        let usage = InterpreterError::Usage.to_string();
+        let usage = format!(
+            "{} {}",
+            usage.replace("--[no-]", "--"),
+            usage.replace("--[no-]", "--no-")
+        );
+        let source = include_str!("cli.rs");
+        let source = &source[..source.find("#[cfg(test)]").unwrap()];
+        for arm in source.lines().filter_map(|line| line.split_once("=>")) {
+            // An arm's aliases, like -e and --eval, only need one mention.
+            let flags: Vec<&str> = arm
+                .0
+                .split('"')
+                .skip(1)
+                .step_by(2)
+                .filter(|flag| flag.starts_with('-'))
+                .collect();
+            assert!(
+                flags.is_empty() || flags.iter().any(|flag| usage.contains(flag)),
+                "{:?} missing from usage",
+                flags
+            );
+        }
+    }
+    #[test]
+    fn cli_allow() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["--allow", "fs,env", "foo.lox"]))?,
+            Mode::File {
+                fname: String::from("foo.lox"),
+                options: RunOptions {
+                    capabilities: Capabilities::default()
+                        .with(Capability::Fs)
+                        .with(Capability::Env),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["-e", "print 1;", "--allow", "all"]))?,
+            Mode::Eval {
+                source: String::from("print 1;"),
+                options: RunOptions {
+                    capabilities: Capabilities::all(),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        for bad in [
+            &["--allow", "disk", "foo.lox"][..],
+            &["--allow"],
+            &["--allow", "fs", "--check", "foo.lox"],
+        ] {
+            assert!(matches!(
+                parse_args(args(bad)),
+                Err(InterpreterError::Usage)
+            ));
+        }
+        Ok(())
+    }
+    #[cfg(feature = "dlopen")]
+    #[test]
+    fn cli_plugin() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["--plugin", "a.so", "foo.lox", "--plugin", "b.so"]))?,
+            Mode::File {
+                fname: String::from("foo.lox"),
+                options: RunOptions {
+                    plugins: vec![String::from("a.so"), String::from("b.so")],
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["--check", "foo.lox", "--plugin", "a.so"])),
+            Err(InterpreterError::Usage)
+        ));
+        assert!(matches!(
+            parse_args(args(&["foo.lox", "--plugin"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_bench() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["bench", "foo.lox"]))?,
+            Mode::Bench {
+                fname: String::from("foo.lox"),
+                iters: DEFAULT_ITERS
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["bench", "foo.lox", "--iters", "3"]))?,
+            Mode::Bench {
+                fname: String::from("foo.lox"),
+                iters: 3
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["foo.lox", "--iters", "3"])),
+            Err(InterpreterError::Usage)
+        ));
+        assert!(matches!(
+            parse_args(args(&["bench", "foo.lox", "--iters", "lots"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_compile() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["compile", "foo.lox"]))?,
+            Mode::Compile {
+                fname: String::from("foo.lox"),
+                out: String::from("foo.loxc")
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["compile", "foo.lox", "-o", "bar.loxc"]))?,
+            Mode::Compile {
+                fname: String::from("foo.lox"),
+                out: String::from("bar.loxc")
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["foo.lox", "-o", "bar.loxc"])),
+            Err(InterpreterError::Usage)
+        ));
+        assert!(matches!(
+            parse_args(args(&["compile", "foo.lox", "--iters", "3"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_coverage() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["--coverage", "foo.lox"]))?,
+            Mode::Coverage {
+                fname: String::from("foo.lox"),
+                out: String::from("lcov.info")
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--coverage", "foo.lox", "-o", "foo.info"]))?,
+            Mode::Coverage {
+                fname: String::from("foo.lox"),
+                out: String::from("foo.info")
+            }
+        );
+        Ok(())
+    }
+    #[test]
+    fn cli_test() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["test", "tests/"]))?,
+            Mode::Test {
+                path: String::from("tests/")
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["test"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_doc() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["doc", "lib.lox"]))?,
+            Mode::Doc {
+                fname: String::from("lib.lox")
+            }
+        );
+        Ok(())
+    }
+    #[test]
+    fn cli_format() -> InterpreterResult<()> {
+        assert_eq!(
+            parse_args(args(&["fmt", "foo.lox"]))?,
+            Mode::Format {
+                fname: String::from("foo.lox"),
+                output: FormatOutput::InPlace
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["fmt", "--check", "foo.lox"]))?,
+            Mode::Format {
+                fname: String::from("foo.lox"),
+                output: FormatOutput::Check
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["fmt", "foo.lox", "--stdout"]))?,
+            Mode::Format {
+                fname: String::from("foo.lox"),
+                output: FormatOutput::Stdout
+            }
+        );
+        assert!(matches!(
+            parse_args(args(&["fmt", "foo.lox", "--check", "--stdout"])),
+            Err(InterpreterError::Usage)
+        ));
+        assert!(matches!(
+            parse_args(args(&["fmt"])),
+            Err(InterpreterError::Usage)
+        ));
+        Ok(())
+    }
+    #[test]
+    fn cli_usage() {
+        assert!(matches!(
+            parse_args(args(&["-e"])),
+            Err(InterpreterError::Usage)
+        ));
+        assert!(matches!(
+            parse_args(args(&["foo.lox", "bar.lox"])),
+            Err(InterpreterError::Usage)
+        ));
+        assert!(matches!(
+            parse_args(args(&["--frobnicate"])),
+            Err(InterpreterError::Usage)
+        ));
+    }
+}
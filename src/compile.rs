@@ -0,0 +1,87 @@
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::stmt::Stmt;
+
+// A compiled program is MAGIC and VERSION followed by the statements as
+// MessagePack. Resolved depths aren't stored: they are keyed by NodeIds, which
+// each process hands out afresh as it loads the program, and resolving is one
+// pass over the tree that in strict mode has to see the running interpreter's
+// globals anyway.
+const MAGIC: &[u8] = b"LOXC";
+const VERSION: u8 = b'4';
+
+pub(crate) fn is_compiled(source: &[u8]) -> bool {
+    source.starts_with(MAGIC)
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn compile(stmts: &[Stmt]) -> InterpreterResult<Vec<u8>> {
+    let mut out = [MAGIC, &[VERSION]].concat();
+    rmp_serde::encode::write(&mut out, stmts)
+        .map_err(|e| error(format!("Couldn't compile program: {}", e)))?;
+    Ok(out)
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn load(source: &[u8]) -> InterpreterResult<Vec<Stmt>> {
+    rmp_serde::from_slice(payload(source)?)
+        .map_err(|e| error(format!("Corrupt compiled program: {}", e)))
+}
+
+#[cfg(not(feature = "serde"))]
+pub(crate) fn compile(_stmts: &[Stmt]) -> InterpreterResult<Vec<u8>> {
+    Err(InterpreterError::FeatureDisabled { feature: "serde" })
+}
+
+#[cfg(not(feature = "serde"))]
+pub(crate) fn load(source: &[u8]) -> InterpreterResult<Vec<Stmt>> {
+    payload(source)?;
+    Err(InterpreterError::FeatureDisabled { feature: "serde" })
+}
+
+fn payload(source: &[u8]) -> InterpreterResult<&[u8]> {
+    source
+        .strip_prefix(MAGIC)
+        .and_then(|s| s.strip_prefix(&[VERSION]))
+        .ok_or_else(|| {
+            error(format!(
+                "Unsupported compiled program (expected {}{})",
+                String::from_utf8_lossy(MAGIC),
+                char::from(VERSION)
+            ))
+        })
+}
+
+fn error(message: String) -> InterpreterError {
+    InterpreterError::Interpreter { line: 0, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn compile_payload() {
+        assert!(is_compiled(b"LOXC4\x90"));
+        assert!(!is_compiled(b"print 1;"));
+        assert_eq!(payload(b"LOXC4\x90").ok(), Some(&b"\x90"[..]));
+        assert!(payload(b"LOXC3\n[]").is_err());
+        assert!(payload(b"LOXC9\x90").is_err());
+        assert!(payload(b"LOXC").is_err());
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn compile_round_trip() -> InterpreterResult<()> {
+        use crate::parser;
+        use crate::scanner::scan;
+        let source = "var a = 1; var s = \"text\"; var n = nil; var b = true; var big = 12345678901234567890n;
+             fun f(x) { return x + a; } while (false) { print a; }";
+        let (tokens, _) = scan(String::from(source));
+        let (stmts, errs) = parser::parse(tokens);
+        assert!(errs.is_empty());
+        let compiled = compile(&stmts)?;
+        assert!(is_compiled(&compiled));
+        assert_eq!(load(&compiled)?, stmts);
+        assert!(compiled.len() < serde_json::to_vec(&stmts)?.len() / 2);
+        assert!(load(&compiled[..compiled.len() - 1]).is_err());
+        Ok(())
+    }
+}
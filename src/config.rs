@@ -0,0 +1,221 @@
+use crate::cli::RunOptions;
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::limits::Limits;
+use crate::suggest;
+use std::env;
+use std::path::{Path, PathBuf};
+
+pub(crate) const CONFIG_FILE: &str = "lox.toml";
+
+const KEYS: [&str; 5] = ["strict", "prelude", "stats", "latin1", "limits"];
+const LIMIT_KEYS: [&str; 6] = [
+    "expressions",
+    "call_depth",
+    "string_bytes",
+    "nesting",
+    "literal_bytes",
+    "tokens",
+];
+
+// Defaults for the run modes, from lox.toml. The user's file is read first
+// and the nearest project file, found by walking up from the working
+// directory, overrides it key by key. Flags win over either file, whichever
+// way they point: --strict and --no-strict, --prelude and --no-prelude, and so
+// on.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Config {
+    strict: Option<bool>,
+    prelude: Option<bool>,
+    stats: Option<bool>,
+    latin1: Option<bool>,
+    limits: Limits,
+}
+
+impl Config {
+    pub(crate) fn load() -> InterpreterResult<Self> {
+        let mut config = Self::default();
+        let cwd = env::current_dir()?;
+        let project = cwd
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|path| path.is_file());
+        for path in user_path().into_iter().chain(project) {
+            config = Self::read(&path)?.or(config);
+        }
+        Ok(config)
+    }
+    fn read(path: &Path) -> InterpreterResult<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => Self::parse(&path.display().to_string(), &source),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(InterpreterError::from(err)),
+        }
+    }
+    pub(crate) fn parse(fname: &str, source: &str) -> InterpreterResult<Self> {
+        let error = |message: String| InterpreterError::Config {
+            fname: String::from(fname),
+            message,
+        };
+        let table: toml::Table = source.parse().map_err(|e| error(format!("{}", e)))?;
+        let mut config = Self::default();
+        for (key, value) in table.iter() {
+            let flag = || {
+                value
+                    .as_bool()
+                    .ok_or_else(|| error(format!("{} should be true or false", key)))
+            };
+            match key.as_str() {
+                "strict" => config.strict = Some(flag()?),
+                "prelude" => config.prelude = Some(flag()?),
+                "stats" => config.stats = Some(flag()?),
+                "latin1" => config.latin1 = Some(flag()?),
+                "limits" => {
+                    let limits = value
+                        .as_table()
+                        .ok_or_else(|| error(String::from("limits should be a table")))?;
+                    for (key, value) in limits.iter() {
+                        let max = value
+                            .as_integer()
+                            .and_then(|n| usize::try_from(n).ok())
+                            .ok_or_else(|| {
+                                error(format!("limits.{} should be a whole number", key))
+                            })?;
+                        let limit = match key.as_str() {
+                            "expressions" => &mut config.limits.max_expressions,
+                            "call_depth" => &mut config.limits.max_call_depth,
+                            "string_bytes" => &mut config.limits.max_string_bytes,
+                            "nesting" => &mut config.limits.max_nesting,
+                            "literal_bytes" => &mut config.limits.max_literal_bytes,
+                            "tokens" => &mut config.limits.max_tokens,
+                            _ => {
+                                return Err(error(unknown(
+                                    &format!("limits.{}", key),
+                                    key,
+                                    LIMIT_KEYS,
+                                )))
+                            }
+                        };
+                        *limit = Some(max);
+                    }
+                }
+                _ => return Err(error(unknown(key, key, KEYS))),
+            }
+        }
+        Ok(config)
+    }
+    // Keys set here win over those set in `other`.
+    fn or(self, other: Self) -> Self {
+        let (limits, fallback) = (self.limits, other.limits);
+        Self {
+            strict: self.strict.or(other.strict),
+            prelude: self.prelude.or(other.prelude),
+            stats: self.stats.or(other.stats),
+            latin1: self.latin1.or(other.latin1),
+            limits: Limits {
+                max_expressions: limits.max_expressions.or(fallback.max_expressions),
+                max_call_depth: limits.max_call_depth.or(fallback.max_call_depth),
+                max_string_bytes: limits.max_string_bytes.or(fallback.max_string_bytes),
+                max_nesting: limits.max_nesting.or(fallback.max_nesting),
+                max_literal_bytes: limits.max_literal_bytes.or(fallback.max_literal_bytes),
+                max_tokens: limits.max_tokens.or(fallback.max_tokens),
+            },
+        }
+    }
+    pub(crate) fn apply(&self, options: RunOptions) -> RunOptions {
+        RunOptions {
+            strict: options.strict.or(self.strict),
+            stats: options.stats.or(self.stats),
+            latin1: options.latin1.or(self.latin1),
+            prelude: options.prelude.or(self.prelude),
+            limits: self.limits,
+            ..options
+        }
+    }
+}
+
+// $XDG_CONFIG_HOME/lox/lox.toml, falling back to ~/.config.
+fn user_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("lox").join(CONFIG_FILE))
+}
+
+fn unknown<const N: usize>(path: &str, key: &str, keys: [&str; N]) -> String {
+    match suggest::closest(key, keys) {
+        Some(similar) => format!("unknown key {}; did you mean '{}'?", path, similar),
+        None => format!("unknown key {}", path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn config_parse() -> InterpreterResult<()> {
+        let config = Config::parse(
+            CONFIG_FILE,
+            "strict = true\nprelude = false\n\n[limits]\ncall_depth = 64\ntokens = 1000\n",
+        )?;
+        assert_eq!(config.strict, Some(true));
+        assert_eq!(config.prelude, Some(false));
+        assert_eq!(config.stats, None);
+        assert_eq!(config.limits.max_call_depth, Some(64));
+        assert_eq!(config.limits.max_tokens, Some(1000));
+        assert_eq!(config.limits.max_expressions, None);
+        let message = |source: &str| match Config::parse(CONFIG_FILE, source) {
+            Err(InterpreterError::Config { message, .. }) => message,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            message("strcit = true"),
+            "unknown key strcit; did you mean 'strict'?"
+        );
+        assert_eq!(message("[limits]\ndepth = 3"), "unknown key limits.depth");
+        assert_eq!(message("strict = 1"), "strict should be true or false");
+        assert_eq!(
+            message("[limits]\nnesting = -1"),
+            "limits.nesting should be a whole number"
+        );
+        assert!(!message("strict = ").is_empty());
+        Ok(())
+    }
+    #[test]
+    fn config_precedence() -> InterpreterResult<()> {
+        let user = Config::parse(
+            "user",
+            "strict = true\nstats = true\n[limits]\nnesting = 8\n",
+        )?;
+        let project = Config::parse("project", "stats = false\n[limits]\ntokens = 9\n")?;
+        let config = project.or(user);
+        let options = config.apply(RunOptions::default());
+        assert_eq!(options.strict, Some(true));
+        assert_eq!(options.stats, Some(false));
+        assert_eq!(options.prelude, None);
+        assert_eq!(options.limits.max_nesting, Some(8));
+        assert_eq!(options.limits.max_tokens, Some(9));
+        let options = config.apply(RunOptions {
+            strict: Some(false),
+            stats: Some(true),
+            prelude: Some(false),
+            ..RunOptions::default()
+        });
+        assert_eq!(options.strict, Some(false));
+        assert_eq!(options.stats, Some(true));
+        assert_eq!(options.prelude, Some(false));
+        let config = Config::parse(
+            "project",
+            "prelude = false
+latin1 = true
+",
+        )?;
+        let options = config.apply(RunOptions {
+            prelude: Some(true),
+            latin1: Some(false),
+            ..RunOptions::default()
+        });
+        assert_eq!(options.prelude, Some(true));
+        assert_eq!(options.latin1, Some(false));
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use crate::errors::InterpreterResult;
+use crate::hooks::{HookContext, InterpreterHooks};
+use crate::node::NodeId;
+use crate::stmt::Stmt;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::rc::Rc;
+
+#[derive(Clone, Default)]
+pub(crate) struct Coverage {
+    stmts: Rc<RefCell<HashMap<NodeId, Hits>>>,
+}
+
+struct Hits {
+    line: usize,
+    count: usize,
+}
+
+impl Coverage {
+    pub(crate) fn new(stmts: &[Stmt]) -> Self {
+        let coverage = Self::default();
+        coverage.add_lines(stmts);
+        coverage
+    }
+    fn add_lines(&self, stmts: &[Stmt]) {
+        for stmt in stmts.iter() {
+            match stmt {
+                Stmt::Block { stmts, .. } => self.add_lines(stmts),
+                Stmt::Function { body, .. } => self.add_line(stmt).add_lines(body),
+                Stmt::While { body, .. } => self
+                    .add_line(stmt)
+                    .add_lines(std::slice::from_ref(body.as_ref())),
+                _ => {
+                    self.add_line(stmt);
+                }
+            }
+        }
+    }
+    fn add_line(&self, stmt: &Stmt) -> &Self {
+        if let Some(line) = stmt.line() {
+            self.stmts
+                .borrow_mut()
+                .insert(stmt.id(), Hits { line, count: 0 });
+        }
+        self
+    }
+    fn hits(&self) -> BTreeMap<usize, usize> {
+        let mut hits = BTreeMap::default();
+        for Hits { line, count } in self.stmts.borrow().values() {
+            *hits.entry(*line).or_insert(0) += count;
+        }
+        hits
+    }
+    pub(crate) fn summary(&self, fname: &str) -> String {
+        let hits = self.hits();
+        let covered = hits.values().filter(|n| **n > 0).count();
+        let percent = if hits.is_empty() {
+            100.0
+        } else {
+            covered as f64 * 100.0 / hits.len() as f64
+        };
+        let mut s = format!(
+            "Coverage for {}: {}/{} lines ({:.1}%)",
+            fname,
+            covered,
+            hits.len(),
+            percent
+        );
+        let missed: Vec<String> = hits
+            .iter()
+            .filter(|(_, n)| **n == 0)
+            .map(|(line, _)| line.to_string())
+            .collect();
+        if !missed.is_empty() {
+            s.push_str(&format!("\nNot executed: {}", missed.join(", ")));
+        }
+        s
+    }
+    pub(crate) fn lcov(&self, fname: &str) -> InterpreterResult<String> {
+        let hits = self.hits();
+        let mut s = format!("TN:\nSF:{}\n", fname);
+        for (line, n) in hits.iter() {
+            writeln!(s, "DA:{},{}", line, n)?;
+        }
+        writeln!(s, "LF:{}", hits.len())?;
+        writeln!(s, "LH:{}", hits.values().filter(|n| **n > 0).count())?;
+        s.push_str("end_of_record\n");
+        Ok(s)
+    }
+}
+
+impl InterpreterHooks for Coverage {
+    fn on_statement_enter(&mut self, _ctx: &HookContext, stmt: &Stmt) -> InterpreterResult<()> {
+        if let Some(hits) = self.stmts.borrow_mut().get_mut(&stmt.id()) {
+            hits.count += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser;
+    use crate::scanner::scan_tokens;
+    #[test]
+    fn coverage_report() -> InterpreterResult<()> {
+        let source = "var a = 0;\nfun f() {\n  return 1;\n}\nwhile (a < 2) {\n  a = a + 1;\n}\n";
+        let (stmts, _) = parser::parse(scan_tokens(String::from(source))?);
+        let coverage = Coverage::new(&stmts);
+        let mut interpreter = Interpreter::default();
+        interpreter.add_hook(Box::new(coverage.clone()));
+        interpreter.interpret_all(&stmts)?;
+        assert_eq!(
+            coverage.summary("t.lox"),
+            "Coverage for t.lox: 4/5 lines (80.0%)\nNot executed: 3"
+        );
+        assert_eq!(
+            coverage.lcov("t.lox")?,
+            "TN:\nSF:t.lox\nDA:1,1\nDA:2,1\nDA:3,0\nDA:5,1\nDA:6,2\nLF:5\nLH:4\nend_of_record\n"
+        );
+        Ok(())
+    }
+}
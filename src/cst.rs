@@ -0,0 +1,136 @@
+use crate::doc;
+use crate::errors::InterpreterResult;
+use crate::parser;
+use crate::scanner::TokenStream;
+use crate::stmt::Stmt;
+use crate::token::Token;
+use std::ops::Range;
+
+// A lossless view of a source file: every token, trivia included, with the
+// exact bytes it came from, grouped under the top-level statement it belongs
+// to. Comments and blank lines before a statement belong to it, as does a
+// comment at the end of its last line. Concatenating the spans gives back the
+// source unchanged.
+pub(crate) struct Cst<'a> {
+    source: &'a str,
+    nodes: Vec<Node>,
+    trailing: Vec<CstToken>,
+}
+
+pub(crate) struct Node {
+    pub(crate) stmt: Stmt,
+    pub(crate) tokens: Vec<CstToken>,
+}
+
+impl Node {
+    pub(crate) fn span(&self) -> Range<usize> {
+        let start = self.tokens.first().map_or(0, |t| t.span.start);
+        let end = self.tokens.last().map_or(start, |t| t.span.end);
+        start..end
+    }
+}
+
+pub(crate) struct CstToken {
+    pub(crate) token: Token,
+    pub(crate) span: Range<usize>,
+}
+
+impl<'a> Cst<'a> {
+    pub(crate) fn parse(source: &'a str) -> InterpreterResult<Self> {
+        Self::parse_at(source, 1)
+    }
+    pub(crate) fn parse_at(source: &'a str, line: usize) -> InterpreterResult<Self> {
+        let mut stream = TokenStream::with_trivia(source).at_line(line);
+        let mut tokens = Vec::default();
+        loop {
+            let start = stream.offset();
+            match stream.next() {
+                Some(token) => tokens.push(CstToken {
+                    token: token?,
+                    span: start..stream.offset(),
+                }),
+                None => break,
+            }
+        }
+        let plain: Vec<Token> = tokens.iter().map(|t| t.token.clone()).collect();
+        let significant: Vec<usize> = (0..plain.len())
+            .filter(|&i| !plain[i].is_trivia())
+            .collect();
+        let cleaned: Vec<Token> = significant.iter().map(|&i| plain[i].clone()).collect();
+        let (mut stmts, ranges): (Vec<Stmt>, Vec<Range<usize>>) =
+            parser::parse_ranges(&cleaned)?.into_iter().unzip();
+        doc::attach(&mut stmts, &mut doc::comments(&plain));
+        let mut rest = tokens.into_iter();
+        let mut taken = 0;
+        let nodes = stmts
+            .into_iter()
+            .zip(ranges)
+            .map(|(stmt, range)| {
+                let last = significant[range.end - 1];
+                let end = trailing_comment(&plain, last + 1).unwrap_or(last + 1);
+                let tokens = rest.by_ref().take(end - taken).collect();
+                taken = end;
+                Node { stmt, tokens }
+            })
+            .collect();
+        Ok(Self {
+            source,
+            nodes,
+            trailing: rest.collect(),
+        })
+    }
+    pub(crate) fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+    pub(crate) fn into_nodes(self) -> Vec<Node> {
+        self.nodes
+    }
+    pub(crate) fn tokens(&self) -> impl Iterator<Item = &CstToken> {
+        self.nodes
+            .iter()
+            .flat_map(|node| node.tokens.iter())
+            .chain(self.trailing.iter())
+    }
+    pub(crate) fn text(&self, token: &CstToken) -> &'a str {
+        &self.source[token.span.clone()]
+    }
+}
+
+// The index just past a comment that shares the line the statement ends on.
+fn trailing_comment(tokens: &[Token], from: usize) -> Option<usize> {
+    for (i, token) in tokens.iter().enumerate().skip(from) {
+        match token {
+            Token::Whitespace { text, .. } if !text.contains(['\n', '\r']) => {}
+            Token::Comment { .. } => return Some(i + 1),
+            _ => return None,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn cst_round_trip() -> InterpreterResult<()> {
+        let source = "// header\n\n/// Adds.\nfun add(a,b){\n  return a+b; // sum\n}\nvar  s = \"caf\u{e9}\"; // trailing\n\nprint add(1, 2);\n// the end\n";
+        let cst = Cst::parse(source)?;
+        let text = |node: &Node| -> String { node.tokens.iter().map(|t| cst.text(t)).collect() };
+        assert_eq!(
+            cst.tokens().map(|t| cst.text(t)).collect::<String>(),
+            source
+        );
+        let nodes = cst.nodes();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(
+            text(&nodes[0]),
+            "// header\n\n/// Adds.\nfun add(a,b){\n  return a+b; // sum\n}"
+        );
+        assert!(matches!(&nodes[0].stmt, Stmt::Function { doc: Some(d), .. } if d == "Adds."));
+        assert_eq!(text(&nodes[1]), "\nvar  s = \"caf\u{e9}\"; // trailing");
+        assert_eq!(text(&nodes[2]), "\n\nprint add(1, 2);");
+        assert!(Cst::parse("print ;").is_err());
+        assert!(Cst::parse("\"open").is_err());
+        Ok(())
+    }
+}
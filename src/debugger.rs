@@ -0,0 +1,179 @@
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::function::Callable;
+use crate::hooks::{HookContext, InterpreterHooks};
+use crate::stmt::Stmt;
+use crate::value::Value;
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+const HELP: &str = "Commands: next (n), continue (c), break <line> (b), delete <line> (d), \
+                    print <name> (p), vars, breakpoints, quit (q)";
+
+pub(crate) struct Debugger {
+    breakpoints: BTreeSet<usize>,
+    stepping: bool,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+}
+
+impl Debugger {
+    pub(crate) fn new(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        Self {
+            breakpoints: BTreeSet::default(),
+            stepping: true,
+            input,
+            output,
+        }
+    }
+    fn pause(&mut self, ctx: &HookContext, stmt: &Stmt, line: usize) -> InterpreterResult<()> {
+        writeln!(self.output, "[line {}] {}", line, stmt.print()?)?;
+        loop {
+            write!(self.output, "(debug) ")?;
+            self.output.flush()?;
+            let mut command = String::default();
+            if self.input.read_line(&mut command)? == 0 {
+                self.stepping = false;
+                self.breakpoints.clear();
+                return Ok(());
+            }
+            let mut words = command.split_whitespace();
+            match (words.next(), words.next()) {
+                (None, _) | (Some("n" | "next"), None) => {
+                    self.stepping = true;
+                    return Ok(());
+                }
+                (Some("c" | "continue"), None) => {
+                    self.stepping = false;
+                    return Ok(());
+                }
+                (Some("b" | "break"), Some(n)) => match n.parse() {
+                    Ok(n) => {
+                        self.breakpoints.insert(n);
+                        writeln!(self.output, "Breakpoint at line {}", n)?;
+                    }
+                    Err(_) => writeln!(self.output, "Not a line number: {}", n)?,
+                },
+                (Some("d" | "delete"), Some(n)) => match n.parse() {
+                    Ok(n) if self.breakpoints.remove(&n) => {
+                        writeln!(self.output, "Deleted breakpoint at line {}", n)?
+                    }
+                    _ => writeln!(self.output, "No breakpoint at line {}", n)?,
+                },
+                (Some("breakpoints"), None) => {
+                    for line in self.breakpoints.iter() {
+                        writeln!(self.output, "line {}", line)?;
+                    }
+                }
+                (Some("p" | "print"), Some(name)) => match ctx.lookup(name) {
+                    Some(value) => writeln!(self.output, "{} = {}", name, value)?,
+                    None => writeln!(self.output, "Undefined variable {}", name)?,
+                },
+                (Some("vars"), None) => self.vars(ctx)?,
+                (Some("q" | "quit"), None) => return Err(InterpreterError::Exit { code: 0 }),
+                _ => writeln!(self.output, "{}", HELP)?,
+            }
+        }
+    }
+    fn vars(&mut self, ctx: &HookContext) -> InterpreterResult<()> {
+        let scopes = ctx.scopes();
+        let globals = scopes.len() - 1;
+        for (depth, scope) in scopes.into_iter().enumerate() {
+            let label = if depth == globals {
+                String::from("globals")
+            } else {
+                format!("scope {}", depth)
+            };
+            writeln!(self.output, "{}:", label)?;
+            for (name, value) in scope {
+                if !matches!(value, Value::Callable(Callable::Native(_))) {
+                    writeln!(self.output, "  {} = {}", name, value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InterpreterHooks for Debugger {
+    fn on_statement_enter(&mut self, ctx: &HookContext, stmt: &Stmt) -> InterpreterResult<()> {
+        if matches!(stmt, Stmt::Block { .. }) {
+            return Ok(());
+        }
+        match stmt.line() {
+            Some(line) if self.stepping || self.breakpoints.contains(&line) => {
+                self.pause(ctx, stmt, line)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser;
+    use crate::scanner::scan_tokens;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn debug(source: &str, commands: &str) -> (String, String, InterpreterResult<()>) {
+        let out = Shared::default();
+        let debugger = Debugger::new(
+            Box::new(Cursor::new(String::from(commands))),
+            Box::new(out.clone()),
+        );
+        let mut interpreter = Interpreter::default();
+        interpreter.add_hook(Box::new(debugger));
+        interpreter.capture_output(true);
+        let (stmts, _) = parser::parse(scan_tokens(String::from(source)).unwrap());
+        let res = interpreter.interpret_all(&stmts).map(|_| ());
+        let log = String::from_utf8(out.0.take()).unwrap();
+        (log, interpreter.take_output(), res)
+    }
+
+    #[test]
+    fn debugger_breakpoints() -> InterpreterResult<()> {
+        let source = "var a = 1;\nfun f(x) {\n  var y = x + a;\n  return y;\n}\nprint f(2);\n";
+        let (log, printed, res) = debug(source, "break 3\ncontinue\np x\nvars\nc\n");
+        res?;
+        assert_eq!(printed, "3\n");
+        assert_eq!(
+            log,
+            "[line 1] (var a 1)\n\
+             (debug) Breakpoint at line 3\n\
+             (debug) [line 3] (var y (+ x a))\n\
+             (debug) x = 2\n\
+             (debug) scope 0:\n  x = 2\n\
+             globals:\n  a = 1\n  f = <fn f>\n\
+             (debug) "
+        );
+        Ok(())
+    }
+    #[test]
+    fn debugger_step_and_quit() {
+        let (log, printed, res) = debug("print 1;\nprint 2;\nprint 3;\n", "\nfoo\nq\n");
+        assert!(matches!(res, Err(InterpreterError::Exit { code: 0 })));
+        assert_eq!(printed, "1\n");
+        assert_eq!(
+            log,
+            format!(
+                "[line 1] (print 1)\n(debug) [line 2] (print 2)\n(debug) {}\n(debug) ",
+                HELP
+            )
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::stmt::Stmt;
+use crate::token::Token;
+use std::collections::HashMap;
+
+const DOC_PREFIX: &str = "///";
+
+pub(crate) fn comments(tokens: &[Token]) -> HashMap<usize, String> {
+    let mut docs = HashMap::default();
+    let mut pending: Vec<&str> = Vec::default();
+    let mut last_line = 0;
+    for token in tokens.iter() {
+        match token {
+            Token::Whitespace { .. } => {}
+            Token::Comment { text, line }
+                if text.starts_with(DOC_PREFIX) && !text.starts_with("////") =>
+            {
+                if *line != last_line + 1 {
+                    pending.clear();
+                }
+                let text = &text[DOC_PREFIX.len()..];
+                pending.push(text.strip_prefix(' ').unwrap_or(text).trim_end());
+                last_line = *line;
+            }
+            token => {
+                let declaration = match token {
+                    Token::Var { line } | Token::Fun { line } | Token::Class { line } => {
+                        Some(*line)
+                    }
+                    _ => None,
+                };
+                if let Some(line) = declaration.filter(|l| *l == last_line + 1) {
+                    if !pending.is_empty() {
+                        docs.insert(line, pending.join("\n"));
+                    }
+                }
+                pending.clear();
+            }
+        }
+    }
+    docs
+}
+
+pub(crate) fn attach(stmts: &mut [Stmt], docs: &mut HashMap<usize, String>) {
+    if docs.is_empty() {
+        return;
+    }
+    for stmt in stmts.iter_mut() {
+        match stmt {
+            Stmt::Variable { name, doc, .. } => {
+                *doc = name.get_line().and_then(|l| docs.remove(&l))
+            }
+            Stmt::Function {
+                name, body, doc, ..
+            } => {
+                *doc = name.get_line().and_then(|l| docs.remove(&l));
+                attach(body, docs);
+            }
+            Stmt::Block { stmts, .. } => attach(stmts, docs),
+            Stmt::While { body, .. } => attach(std::slice::from_mut(body.as_mut()), docs),
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn markdown(title: &str, stmts: &[Stmt]) -> String {
+    let mut s = format!("# {}\n", title);
+    for stmt in stmts.iter() {
+        let (heading, doc) = match stmt {
+            Stmt::Function {
+                name, params, doc, ..
+            } => {
+                let params: Vec<String> = params.iter().map(Token::to_string).collect();
+                (format!("fun {}({})", name, params.join(", ")), doc)
+            }
+            Stmt::Variable { name, doc, .. } => (format!("var {}", name), doc),
+            _ => continue,
+        };
+        if heading
+            .split_whitespace()
+            .nth(1)
+            .is_none_or(|n| n.starts_with('_'))
+        {
+            continue;
+        }
+        s.push_str(&format!("\n## {}\n", heading));
+        if let Some(doc) = doc {
+            s.push_str(&format!("\n{}\n", doc));
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::InterpreterResult;
+    use crate::parser;
+    use crate::scanner::scan_with_trivia;
+    #[test]
+    fn doc_comments() -> InterpreterResult<()> {
+        let source = "/// Adds two numbers.\n///\n/// Returns a number.\nfun add(a, b) {\n  /// Inner.\n  var c = a + b;\n  return c;\n}\n// not a doc\nvar x = 1;\n/// Detached.\n\nvar y = 2;\n/// The limit.\nvar limit = 3;\n/// Private.\nvar _hidden = 4;\n";
+        let (stmts, errs) = parser::parse(scan_with_trivia(String::from(source)).0);
+        assert!(errs.is_empty());
+        match &stmts[0] {
+            Stmt::Function { doc, body, .. } => {
+                assert_eq!(
+                    doc.as_deref(),
+                    Some("Adds two numbers.\n\nReturns a number.")
+                );
+                assert!(matches!(&body[0], Stmt::Variable { doc: Some(d), .. } if d == "Inner."));
+            }
+            stmt => panic!("expected a function, got {:?}", stmt),
+        }
+        assert!(matches!(&stmts[1], Stmt::Variable { doc: None, .. }));
+        assert!(matches!(&stmts[2], Stmt::Variable { doc: None, .. }));
+        assert_eq!(
+            markdown("lib.lox", &stmts),
+            "# lib.lox\n\n## fun add(a, b)\n\nAdds two numbers.\n\nReturns a number.\n\n## var x\n\n## var y\n\n## var limit\n\nThe limit.\n"
+        );
+        Ok(())
+    }
+}
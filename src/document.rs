@@ -0,0 +1,256 @@
+use crate::cst::{Cst, Node};
+use crate::source::Diagnostic;
+use crate::stmt::Stmt;
+use std::ops::Range;
+
+// An editable source buffer that keeps its top-level statements parsed. An
+// edit reparses the statements it touches plus the one after, which may have
+// gained a doc comment, and shifts the rest. Edits that add or remove lines
+// reparse everything after them too, since every later token's line changes.
+#[derive(Debug, Default)]
+pub struct Document {
+    source: String,
+    parsed: Vec<Parsed>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug)]
+struct Parsed {
+    stmt: Stmt,
+    span: Range<usize>,
+}
+
+impl Document {
+    pub fn new<T>(source: T) -> Self
+    where
+        T: Into<String>,
+    {
+        let mut document = Self {
+            source: source.into(),
+            ..Self::default()
+        };
+        document.reparse_all();
+        document
+    }
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+    pub fn stmts(&self) -> impl Iterator<Item = &Stmt> {
+        self.parsed.iter().map(|p| &p.stmt)
+    }
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+    // Replaces the bytes in `range` with `text`; panics like
+    // String::replace_range if the range isn't on character boundaries.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) {
+        let same_lines = lines(&self.source[range.clone()]) == lines(text);
+        self.source.replace_range(range.clone(), text);
+        if !self.diagnostics.is_empty() || self.parsed.is_empty() {
+            return self.reparse_all();
+        }
+        let len = self.parsed.len();
+        let mut first = self
+            .parsed
+            .iter()
+            .position(|p| p.span.end >= range.start)
+            .unwrap_or(len);
+        // A doc comment at the end of the previous statement's line documents
+        // the next declaration, so the window can't start right after it.
+        while first > 0 && first < len && after_comment(&self.source, self.parsed[first].span.start)
+        {
+            first -= 1;
+        }
+        let end = match self.parsed.iter().rposition(|p| p.span.start <= range.end) {
+            Some(last) if same_lines => (last + 2).clamp(first, len),
+            _ => len,
+        };
+        let delta = text.len() as isize - range.len() as isize;
+        let shift = |n: usize| n.checked_add_signed(delta).unwrap_or(0);
+        let start_byte = match self.parsed.get(first) {
+            Some(p) => p.span.start,
+            None => self.parsed.last().map_or(0, |p| p.span.end),
+        };
+        let end_byte = match self.parsed.get(end) {
+            Some(p) => shift(p.span.start),
+            None => self.source.len(),
+        };
+        let line = 1 + lines(&self.source[..start_byte]);
+        let nodes = match Cst::parse_at(&self.source[start_byte..end_byte], line) {
+            Ok(cst) => cst.into_nodes(),
+            Err(_) => return self.reparse_all(),
+        };
+        let reparsed = nodes.into_iter().map(|node| Parsed::new(node, start_byte));
+        let count = reparsed.len();
+        self.parsed.splice(first..end, reparsed);
+        for p in self.parsed[first + count..].iter_mut() {
+            p.span = shift(p.span.start)..shift(p.span.end);
+        }
+        self.close_gaps();
+    }
+    fn reparse_all(&mut self) {
+        match Cst::parse(&self.source) {
+            Ok(cst) => {
+                self.parsed = cst
+                    .into_nodes()
+                    .into_iter()
+                    .map(|node| Parsed::new(node, 0))
+                    .collect();
+                self.diagnostics.clear();
+            }
+            Err(err) => {
+                self.parsed.clear();
+                self.diagnostics = vec![Diagnostic::from(err)];
+            }
+        }
+        self.close_gaps();
+    }
+    // Trivia after a statement, such as what's left at the end of a reparsed
+    // region, belongs to whatever follows it.
+    fn close_gaps(&mut self) {
+        let mut start = 0;
+        for p in self.parsed.iter_mut() {
+            p.span.start = start;
+            start = p.span.end;
+        }
+    }
+}
+
+impl Parsed {
+    fn new(node: Node, offset: usize) -> Self {
+        let span = node.span();
+        Self {
+            stmt: node.stmt,
+            span: span.start + offset..span.end + offset,
+        }
+    }
+}
+
+// Whether the line `at` is on has a comment before it. Conservative, since
+// "//" in a string counts too.
+fn after_comment(source: &str, at: usize) -> bool {
+    let line_start = source[..at].rfind(['\n', '\r']).map_or(0, |i| i + 1);
+    source[line_start..at].contains("//")
+}
+
+// Counts lines the way the scanner does, with \r\n as one break.
+fn lines(s: &str) -> usize {
+    s.matches('\n').count() + s.matches('\r').count() - s.matches("\r\n").count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn ids(document: &Document) -> Vec<crate::node::NodeId> {
+        document.stmts().map(Stmt::id).collect()
+    }
+    fn check(document: &Document) {
+        let cst = Cst::parse(document.source()).unwrap();
+        let expected: Vec<&Stmt> = cst.nodes().iter().map(|n| &n.stmt).collect();
+        assert_eq!(document.stmts().collect::<Vec<_>>(), expected);
+    }
+    #[test]
+    fn document_reuses_untouched_statements() {
+        let mut document =
+            Document::new("var a = 1;\nvar b = 2;\nvar c = 3;\nvar d = 4;\nprint a + d;\n");
+        let before = ids(&document);
+        document.edit(19..20, "20");
+        assert_eq!(
+            document.source(),
+            "var a = 1;\nvar b = 20;\nvar c = 3;\nvar d = 4;\nprint a + d;\n"
+        );
+        check(&document);
+        let after = ids(&document);
+        assert_eq!(after[0], before[0]);
+        assert_ne!(after[1], before[1]);
+        assert_eq!(after[3..], before[3..]);
+
+        let before = after;
+        document.edit(0..0, "/// First.\n");
+        check(&document);
+        assert!(matches!(
+            document.stmts().next(),
+            Some(Stmt::Variable { doc: Some(d), .. }) if d == "First."
+        ));
+        assert_ne!(ids(&document)[4], before[4]);
+
+        let end = document.source().len();
+        document.edit(end..end, "print b;");
+        check(&document);
+        assert_eq!(document.stmts().count(), 6);
+        assert!(document.diagnostics().is_empty());
+    }
+    #[test]
+    fn document_keeps_docs_after_trailing_comments() {
+        let mut document = Document::new("var a = 1;\nfun f(x) {\n  return x + a;\n}\nvar c = 3;/// doc\nvar b = 2;\nprinxf(b);\nwhile (a < 3) a = a;");
+        document.edit(68..73, "\n");
+        check(&document);
+        assert!(document
+            .stmts()
+            .any(|s| matches!(s, Stmt::Variable { doc: Some(d), .. } if d == "doc")));
+    }
+    // Random edits from a small alphabet of fragments must leave the document
+    // the same as parsing its text from scratch.
+    #[test]
+    fn document_matches_fresh_parse_after_random_edits() {
+        const FRAGMENTS: [&str; 14] = [
+            "",
+            "\n",
+            " ",
+            ";",
+            "{",
+            "}",
+            "(",
+            ")",
+            "var x = 1;",
+            "/// doc\n",
+            "// note",
+            "print a;",
+            "fun g() {",
+            "a + 1",
+        ];
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+        for _ in 0..200 {
+            let mut document = Document::new("var a = 1;\nfun f(x) {\n  return x + a;\n}\nvar c = 3; /// doc\nvar b = 2;\nprint f(b);\nwhile (a < 3) a = a + 1;\n");
+            for _ in 0..20 {
+                let len = document.source().len();
+                let start = next(len + 1);
+                let end = (start + next(12)).min(len);
+                document.edit(start..end, FRAGMENTS[next(FRAGMENTS.len())]);
+                let fresh = Document::new(document.source());
+                assert_eq!(
+                    document.stmts().collect::<Vec<_>>(),
+                    fresh.stmts().collect::<Vec<_>>(),
+                    "{:?}",
+                    document.source()
+                );
+                assert_eq!(document.diagnostics(), fresh.diagnostics());
+            }
+        }
+    }
+    #[test]
+    fn document_recovers_from_broken_edits() {
+        let mut document = Document::new("var a = 1;\nprint a;\n");
+        document.edit(8..9, "");
+        assert_eq!(document.stmts().count(), 0);
+        assert_eq!(document.diagnostics()[0].line, Some(1));
+        document.edit(8..8, "(2");
+        assert_eq!(document.diagnostics().len(), 1);
+        document.edit(10..10, ")");
+        assert_eq!(document.source(), "var a = (2);\nprint a;\n");
+        assert!(document.diagnostics().is_empty());
+        check(&document);
+        document.edit(0..12, "{\n");
+        assert_eq!(document.diagnostics().len(), 1);
+        let end = document.source().len();
+        document.edit(end..end, "}\n");
+        check(&document);
+        assert_eq!(document.stmts().count(), 1);
+    }
+}
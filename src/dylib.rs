@@ -0,0 +1,225 @@
+use crate::capabilities::Capability;
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use libloading::Library;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const MAX_FFI_ARGS: usize = 4;
+
+thread_local! {
+    static LIBRARIES: RefCell<Vec<Rc<Library>>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Type {
+    F64,
+    I64,
+    Void,
+}
+
+pub(crate) fn ffi_load(
+    interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Ffi, line)?;
+    let path = String::try_from(&args[0]).map_err(|e| e.add_line_to_type_error(line))?;
+    // Loading runs the library's initializers, which is as trusted as the path.
+    let library = unsafe { Library::new(&path) }.map_err(|e| InterpreterError::RuntimeError {
+        line,
+        message: format!("Couldn't load {}: {}", path, e),
+    })?;
+    LIBRARIES.with(|libraries| {
+        let mut libraries = libraries.borrow_mut();
+        libraries.push(Rc::new(library));
+        Ok(Value::Number((libraries.len() - 1) as f32))
+    })
+}
+
+// Every parameter has to share one type, so a handful of monomorphic function
+// pointer types cover the signatures without needing libffi.
+pub(crate) fn ffi_call(
+    interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Ffi, line)?;
+    let library = library_arg(&args[0], line)?;
+    let name = String::try_from(&args[1]).map_err(|e| e.add_line_to_type_error(line))?;
+    let params = String::try_from(&args[2]).map_err(|e| e.add_line_to_type_error(line))?;
+    let params = params
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| match parse_type(p, line)? {
+            Type::Void => Err(InterpreterError::RuntimeError {
+                line,
+                message: String::from("Parameters can't be void"),
+            }),
+            t => Ok(t),
+        })
+        .collect::<InterpreterResult<Vec<Type>>>()?;
+    let ret = String::try_from(&args[3]).map_err(|e| e.add_line_to_type_error(line))?;
+    let ret = parse_type(&ret, line)?;
+    let values = &args[4..];
+    if values.len() != params.len() {
+        return Err(InterpreterError::RuntimeError {
+            line,
+            message: format!(
+                "{} takes {} arguments but got {}",
+                name,
+                params.len(),
+                values.len()
+            ),
+        });
+    }
+    let param = match params.first() {
+        None => Type::I64,
+        Some(first) if params.len() <= MAX_FFI_ARGS && params.iter().all(|p| p == first) => *first,
+        Some(_) => {
+            return Err(InterpreterError::RuntimeError {
+                line,
+                message: format!(
+                    "Foreign calls take up to {} parameters of a single type",
+                    MAX_FFI_ARGS
+                ),
+            })
+        }
+    };
+    let numbers = values
+        .iter()
+        .map(|v| f64::try_from(v).map_err(|e| e.add_line_to_type_error(line)))
+        .collect::<InterpreterResult<Vec<f64>>>()?;
+    let result = match (param, ret) {
+        (Type::F64, Type::F64) => call::<f64, f64>(&library, &name, &numbers).map(Value::try_from),
+        (Type::F64, Type::I64) => call::<f64, i64>(&library, &name, &numbers).map(from_i64),
+        (Type::F64, Type::Void) => {
+            call::<f64, ()>(&library, &name, &numbers).map(|_| Ok(Value::Nil))
+        }
+        (Type::I64, Type::F64) => {
+            call::<i64, f64>(&library, &name, &ints(&numbers, line)?).map(Value::try_from)
+        }
+        (Type::I64, Type::I64) => {
+            call::<i64, i64>(&library, &name, &ints(&numbers, line)?).map(from_i64)
+        }
+        (Type::I64, Type::Void) => {
+            call::<i64, ()>(&library, &name, &ints(&numbers, line)?).map(|_| Ok(Value::Nil))
+        }
+        (Type::Void, _) => unreachable!(),
+    };
+    result.map_err(|e| InterpreterError::RuntimeError {
+        line,
+        message: format!("Couldn't find {}: {}", name, e),
+    })?
+}
+
+fn call<A: Copy, R>(library: &Library, name: &str, args: &[A]) -> Result<R, libloading::Error> {
+    // The caller vouches for the signature; a wrong one is undefined behaviour,
+    // the same as declaring it wrong in C.
+    unsafe {
+        Ok(match *args {
+            [] => library.get::<unsafe extern "C" fn() -> R>(name.as_bytes())?(),
+            [a] => library.get::<unsafe extern "C" fn(A) -> R>(name.as_bytes())?(a),
+            [a, b] => library.get::<unsafe extern "C" fn(A, A) -> R>(name.as_bytes())?(a, b),
+            [a, b, c] => {
+                library.get::<unsafe extern "C" fn(A, A, A) -> R>(name.as_bytes())?(a, b, c)
+            }
+            [a, b, c, d] => {
+                library.get::<unsafe extern "C" fn(A, A, A, A) -> R>(name.as_bytes())?(a, b, c, d)
+            }
+            _ => unreachable!(),
+        })
+    }
+}
+
+fn parse_type(name: &str, line: usize) -> InterpreterResult<Type> {
+    match name.trim() {
+        "f64" | "double" => Ok(Type::F64),
+        "i64" | "long" => Ok(Type::I64),
+        "void" => Ok(Type::Void),
+        other => Err(InterpreterError::RuntimeError {
+            line,
+            message: format!("Unknown foreign type {}", other),
+        }),
+    }
+}
+
+fn ints(numbers: &[f64], line: usize) -> InterpreterResult<Vec<i64>> {
+    numbers
+        .iter()
+        .map(|&n| {
+            if n.fract() == 0.0 {
+                Ok(n as i64)
+            } else {
+                Err(
+                    InterpreterError::type_error(String::from("whole number"), n.to_string())
+                        .add_line_to_type_error(line),
+                )
+            }
+        })
+        .collect()
+}
+
+fn from_i64(n: i64) -> InterpreterResult<Value> {
+    Ok(Value::Number(n as f32))
+}
+
+fn library_arg(handle: &Value, line: usize) -> InterpreterResult<Rc<Library>> {
+    let unknown = || InterpreterError::RuntimeError {
+        line,
+        message: format!("Unknown library {}", handle),
+    };
+    let id = match handle {
+        Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => *n as usize,
+        Value::Number(_) => return Err(unknown()),
+        value => {
+            return Err(InterpreterError::type_error(
+                String::from("number"),
+                String::from(value.type_name()),
+            )
+            .add_line_to_type_error(line))
+        }
+    };
+    LIBRARIES.with(|libraries| libraries.borrow().get(id).cloned().ok_or_else(unknown))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::capabilities::Capabilities;
+    use crate::lox::Lox;
+    #[test]
+    fn dylib_calls_libm() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.set_capabilities(Capabilities::all());
+        lox.eval_str("var m = ffiLoad(\"libm.so.6\");")?;
+        assert_eq!(
+            lox.eval_str("ffiCall(m, \"pow\", \"f64, f64\", \"f64\", 2, 10);")?,
+            Value::Number(1024.0)
+        );
+        assert_eq!(
+            lox.eval_str("ffiCall(m, \"cos\", \"f64\", \"f64\", 0);")?,
+            Value::Number(1.0)
+        );
+        assert!(matches!(
+            lox.eval_str("ffiCall(m, \"nope\", \"\", \"void\");"),
+            Err(InterpreterError::RuntimeError { line: 1, .. })
+        ));
+        assert!(matches!(
+            lox.eval_str("ffiCall(m, \"ldexp\", \"f64, i64\", \"f64\", 1, 2);"),
+            Err(InterpreterError::RuntimeError { line: 1, .. })
+        ));
+        assert!(matches!(
+            lox.eval_str("ffiCall(m, \"cos\", \"f64\", \"f64\");"),
+            Err(InterpreterError::RuntimeError { line: 1, .. })
+        ));
+        lox.set_capabilities(Capabilities::none());
+        assert!(matches!(
+            lox.eval_str("ffiLoad(\"libm.so.6\");"),
+            Err(InterpreterError::CapabilityDenied { line: 1, .. })
+        ));
+        Ok(())
+    }
+}
@@ -4,19 +4,54 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Globals {
+    values: HashMap<String, Value>,
+}
+
+impl Globals {
+    pub(crate) fn values(&self) -> &HashMap<String, Value> {
+        &self.values
+    }
+    pub(crate) fn define(&mut self, name: String, value: Value) {
+        self.values.insert(name, value);
+    }
+    pub(crate) fn get(&self, name: &str) -> InterpreterResult<Value> {
+        self.values
+            .get(name)
+            .cloned()
+            .ok_or_else(|| InterpreterError::undefined_variable_error(String::from(name)))
+    }
+    pub(crate) fn assign(&mut self, name: &str, value: Value) -> InterpreterResult<Value> {
+        match self.values.get_mut(name) {
+            Some(v) => {
+                *v = value.clone();
+                Ok(value)
+            }
+            None => Err(InterpreterError::undefined_variable_error(name.into())),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub(crate) struct Environment {
     enclosing: Option<Rc<RefCell<Environment>>>,
     values: HashMap<String, Value>,
 }
 
 impl Environment {
-    pub(crate) fn new(enclosing: Rc<RefCell<Environment>>) -> Self {
+    pub(crate) fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Self {
         Self {
-            enclosing: Some(enclosing),
+            enclosing,
             values: HashMap::default(),
         }
     }
+    pub(crate) fn enclosing(&self) -> Option<Rc<RefCell<Environment>>> {
+        self.enclosing.clone()
+    }
+    pub(crate) fn values(&self) -> &HashMap<String, Value> {
+        &self.values
+    }
     pub(crate) fn define(&mut self, name: String, value: Value) {
         self.values.insert(name, value);
     }
@@ -31,15 +66,32 @@ impl Environment {
             },
         }
     }
-    pub(crate) fn assign(&mut self, name: &str, value: Value) -> InterpreterResult<Value> {
-        if self.values.contains_key(name) {
-            self.define(name.to_string(), value.clone());
-            Ok(value)
-        } else {
-            match self.enclosing.as_ref() {
-                Some(e) => e.borrow_mut().assign(name, value),
-                None => Err(InterpreterError::undefined_variable_error(name.into())),
+    pub(crate) fn get_at(&self, depth: usize, name: &str) -> InterpreterResult<Value> {
+        match (depth, &self.enclosing) {
+            (0, _) => self
+                .values
+                .get(name)
+                .cloned()
+                .ok_or_else(|| InterpreterError::undefined_variable_error(String::from(name))),
+            (_, Some(e)) => e.borrow().get_at(depth - 1, name),
+            (_, None) => Err(InterpreterError::undefined_variable_error(String::from(
+                name,
+            ))),
+        }
+    }
+    pub(crate) fn assign_at(
+        &mut self,
+        depth: usize,
+        name: &str,
+        value: Value,
+    ) -> InterpreterResult<Value> {
+        match (depth, &self.enclosing) {
+            (0, _) if self.values.contains_key(name) => {
+                self.define(String::from(name), value.clone());
+                Ok(value)
             }
+            (0, _) | (_, None) => Err(InterpreterError::undefined_variable_error(name.into())),
+            (_, Some(e)) => e.borrow_mut().assign_at(depth - 1, name, value),
         }
     }
 }
@@ -20,9 +20,18 @@ pub enum InterpreterError {
         #[from]
         source: ReadlineError,
     },
+    #[cfg(feature = "serde")]
+    #[error("JSON error: {source}")]
+    Json {
+        #[from]
+        source: serde_json::Error,
+    },
     #[error("[{line}] Error: {message}")]
     Interpreter { line: usize, message: String },
-    #[error("Usage: rlox [script]")]
+    // Kept apart from other scan errors so the REPL can wait for more input.
+    #[error("[{line}] Error: Unterminated string starting at column {column}")]
+    UnterminatedString { line: usize, column: usize },
+    #[error("Usage: rlox [[--[no-]strict] [--[no-]stats] [--[no-]latin1] [--[no-]prelude] [--allow caps] [--plugin path] script | [--[no-]strict] [--[no-]stats] [--[no-]latin1] [--[no-]prelude] [--allow caps] [--plugin path] -i script | [--[no-]strict] [--[no-]stats] [--[no-]prelude] [--allow caps] [--plugin path] -e source | --tokens script | --ast script | [--[no-]strict] [--[no-]prelude] --check script | --debug script | --step script | --coverage script [-o lcov] | bench script [--iters n] | compile script [-o out] | fmt script [--check | --stdout] | test path | doc script]")]
     Usage,
     #[error("Error parsing code on line {line}")]
     Parse { line: usize },
@@ -34,13 +43,82 @@ pub enum InterpreterError {
     },
     #[error("Syntax error on line {line}: {message}")]
     SyntaxError { line: usize, message: String },
+    #[error("{fname} isn't valid UTF-8 at byte {offset} (line {line}); rerun with --latin1 to read it as Latin-1")]
+    Encoding {
+        fname: String,
+        offset: usize,
+        line: usize,
+    },
+    #[error("{fname}: {message}")]
+    Config { fname: String, message: String },
+    #[error("Runtime error on line {line}: {message}")]
+    RuntimeError { line: usize, message: String },
     #[error("Undefined variable {name}{}", show_line(.line))]
     UndefinedVariable { line: Option<usize>, name: String },
+    #[error("Runtime error on line {line}: {capability} access is not allowed")]
+    CapabilityDenied {
+        capability: &'static str,
+        line: usize,
+    },
+    #[error("Exceeded {limit} limit of {max}")]
+    LimitExceeded { limit: &'static str, max: usize },
+    #[error("Interrupted")]
+    Interrupted,
+    #[error("Can't return from top-level code on line {line}")]
+    Return { line: usize },
+    #[error("Exited with code {code}")]
+    Exit { code: i32 },
+    #[error("This build doesn't include the {feature} feature")]
+    FeatureDisabled { feature: &'static str },
     #[error("An unknown error has occurred")]
     Unknown,
 }
 
 impl InterpreterError {
+    pub fn is_static(&self) -> bool {
+        matches!(
+            self,
+            Self::Interpreter { .. }
+                | Self::UnterminatedString { .. }
+                | Self::Parse { .. }
+                | Self::SyntaxError { .. }
+        )
+    }
+    pub fn is_runtime(&self) -> bool {
+        matches!(
+            self,
+            Self::RuntimeError { .. }
+                | Self::Type { .. }
+                | Self::UndefinedVariable { .. }
+                | Self::Return { .. }
+                | Self::LimitExceeded { .. }
+                | Self::CapabilityDenied { .. }
+        )
+    }
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Self::Interpreter { line, .. }
+            | Self::UnterminatedString { line, .. }
+            | Self::Parse { line }
+            | Self::SyntaxError { line, .. }
+            | Self::RuntimeError { line, .. }
+            | Self::CapabilityDenied { line, .. }
+            | Self::Return { line } => Some(*line),
+            Self::Type { line, .. } | Self::UndefinedVariable { line, .. } => *line,
+            _ => None,
+        }
+    }
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Usage => 64,
+            Self::Encoding { .. } => 65,
+            Self::Config { .. } => 78,
+            Self::Interrupted => 130,
+            Self::Exit { code } => *code,
+            e if e.is_static() => 65,
+            _ => 70,
+        }
+    }
     pub(crate) fn add_line_to_type_error(self, new_line: usize) -> Self {
         match self {
             Self::Type {
@@ -81,3 +159,45 @@ fn show_line(line: &Option<usize>) -> String {
 }
 
 pub type InterpreterResult<T> = Result<T, InterpreterError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn errors_exit_code() {
+        assert_eq!(InterpreterError::Usage.exit_code(), 64);
+        assert_eq!(InterpreterError::Parse { line: 1 }.exit_code(), 65);
+        assert_eq!(
+            InterpreterError::Interpreter {
+                line: 1,
+                message: String::from("Unknown token @")
+            }
+            .exit_code(),
+            65
+        );
+        assert_eq!(
+            InterpreterError::RuntimeError {
+                line: 1,
+                message: String::from("oops")
+            }
+            .exit_code(),
+            70
+        );
+        assert_eq!(
+            InterpreterError::undefined_variable_error(String::from("foo")).exit_code(),
+            70
+        );
+        assert_eq!(
+            InterpreterError::Encoding {
+                fname: String::from("a.lox"),
+                offset: 3,
+                line: 1
+            }
+            .exit_code(),
+            65
+        );
+        assert_eq!(InterpreterError::Interrupted.exit_code(), 130);
+        assert_eq!(InterpreterError::Exit { code: 3 }.exit_code(), 3);
+        assert_eq!(InterpreterError::Unknown.exit_code(), 70);
+    }
+}
@@ -1,63 +1,283 @@
 use crate::errors::{InterpreterError, InterpreterResult};
 use crate::expr_printer::ExprPrinter;
+use crate::node::NodeId;
+use crate::string::LoxString;
 use crate::token::Token;
 pub use crate::value::Value;
+use num_bigint::BigInt;
 use std::cmp::PartialEq;
 use std::convert::TryFrom;
+use std::rc::Rc;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     Assign {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
         name: Token,
         value: Box<Expr>,
     },
     Binary {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
         left: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
     },
+    Call {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
+        callee: Box<Expr>,
+        paren: Token,
+        arguments: Vec<Expr>,
+    },
     Grouping {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
         expression: Box<Expr>,
     },
+    Index {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
     Literal {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
         value: Value,
     },
+    Logical {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
+        left: Box<Expr>,
+        operator: Token,
+        right: Box<Expr>,
+    },
     Unary {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
         operator: Token,
         right: Box<Expr>,
     },
     Variable {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
         name: Token,
     },
 }
 
+pub trait ExprVisitor<T> {
+    fn visit_assign(&mut self, id: NodeId, name: &Token, value: &Expr) -> T;
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> T;
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> T;
+    fn visit_grouping(&mut self, expression: &Expr) -> T;
+    fn visit_index(&mut self, object: &Expr, bracket: &Token, index: &Expr) -> T;
+    fn visit_literal(&mut self, value: &Value) -> T;
+    fn visit_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> T;
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) -> T;
+    fn visit_variable(&mut self, id: NodeId, name: &Token) -> T;
+}
+
 impl Expr {
+    pub fn accept<T, V>(&self, visitor: &mut V) -> T
+    where
+        V: ExprVisitor<T> + ?Sized,
+    {
+        match self {
+            Self::Assign { id, name, value } => visitor.visit_assign(*id, name, value),
+            Self::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => visitor.visit_binary(left, operator, right),
+            Self::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => visitor.visit_call(callee, paren, arguments),
+            Self::Grouping { expression, .. } => visitor.visit_grouping(expression),
+            Self::Index {
+                object,
+                bracket,
+                index,
+                ..
+            } => visitor.visit_index(object, bracket, index),
+            Self::Literal { value, .. } => visitor.visit_literal(value),
+            Self::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => visitor.visit_logical(left, operator, right),
+            Self::Unary {
+                operator, right, ..
+            } => visitor.visit_unary(operator, right),
+            Self::Variable { id, name } => visitor.visit_variable(*id, name),
+        }
+    }
+    pub fn id(&self) -> NodeId {
+        match self {
+            Self::Assign { id, .. }
+            | Self::Binary { id, .. }
+            | Self::Call { id, .. }
+            | Self::Grouping { id, .. }
+            | Self::Index { id, .. }
+            | Self::Literal { id, .. }
+            | Self::Logical { id, .. }
+            | Self::Unary { id, .. }
+            | Self::Variable { id, .. } => *id,
+        }
+    }
+    pub(crate) fn line(&self) -> Option<usize> {
+        match self {
+            Self::Assign { name, .. } | Self::Variable { name, .. } => name.get_line(),
+            Self::Binary { left, operator, .. } | Self::Logical { left, operator, .. } => {
+                left.line().or(operator.get_line())
+            }
+            Self::Call { callee, paren, .. } => callee.line().or(paren.get_line()),
+            Self::Grouping { expression, .. } => expression.line(),
+            Self::Index {
+                object, bracket, ..
+            } => object.line().or(bracket.get_line()),
+            Self::Unary { operator, .. } => operator.get_line(),
+            Self::Literal { .. } => None,
+        }
+    }
     pub fn literal_num(n: f32) -> Self {
         Self::Literal {
+            id: NodeId::next(),
             value: Value::Number(n),
         }
     }
     pub fn literal_string<T>(s: T) -> Self
     where
-        T: Into<String>,
+        T: Into<LoxString>,
     {
         Self::Literal {
+            id: NodeId::next(),
             value: Value::r#String(s.into()),
         }
     }
+    pub fn literal_bigint(n: BigInt) -> Self {
+        Self::Literal {
+            id: NodeId::next(),
+            value: Value::BigInt(Rc::new(n)),
+        }
+    }
+    pub fn literal_bytes(b: &[u8]) -> Self {
+        Self::Literal {
+            id: NodeId::next(),
+            value: Value::Bytes(Rc::from(b)),
+        }
+    }
     pub fn literal_bool(b: bool) -> Self {
         Self::Literal {
+            id: NodeId::next(),
             value: Value::Bool(b),
         }
     }
     pub fn literal_nil() -> Self {
-        Self::Literal { value: Value::Nil }
+        Self::Literal {
+            id: NodeId::next(),
+            value: Value::Nil,
+        }
     }
     pub fn print(&self) -> InterpreterResult<String> {
         ExprPrinter::default().build(self)?.print()
     }
 }
 
+impl PartialEq for Expr {
+    // Ids identify a node, they aren't part of its structure.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Assign { name, value, .. },
+                Self::Assign {
+                    name: n, value: v, ..
+                },
+            ) => name == n && value == v,
+            (
+                Self::Binary {
+                    left,
+                    operator,
+                    right,
+                    ..
+                },
+                Self::Binary {
+                    left: l,
+                    operator: o,
+                    right: r,
+                    ..
+                },
+            ) => left == l && operator == o && right == r,
+            (
+                Self::Call {
+                    callee,
+                    paren,
+                    arguments,
+                    ..
+                },
+                Self::Call {
+                    callee: c,
+                    paren: p,
+                    arguments: a,
+                    ..
+                },
+            ) => callee == c && paren == p && arguments == a,
+            (Self::Grouping { expression, .. }, Self::Grouping { expression: e, .. }) => {
+                expression == e
+            }
+            (
+                Self::Index {
+                    object,
+                    bracket,
+                    index,
+                    ..
+                },
+                Self::Index {
+                    object: o,
+                    bracket: b,
+                    index: i,
+                    ..
+                },
+            ) => object == o && bracket == b && index == i,
+            (Self::Literal { value, .. }, Self::Literal { value: v, .. }) => value == v,
+            (
+                Self::Logical {
+                    left,
+                    operator,
+                    right,
+                    ..
+                },
+                Self::Logical {
+                    left: l,
+                    operator: o,
+                    right: r,
+                    ..
+                },
+            ) => left == l && operator == o && right == r,
+            (
+                Self::Unary {
+                    operator, right, ..
+                },
+                Self::Unary {
+                    operator: o,
+                    right: r,
+                    ..
+                },
+            ) => operator == o && right == r,
+            (Self::Variable { name, .. }, Self::Variable { name: n, .. }) => name == n,
+            _ => false,
+        }
+    }
+}
+
 impl TryFrom<String> for Expr {
     type Error = InterpreterError;
     fn try_from(value: String) -> Result<Self, Self::Error> {
@@ -78,17 +298,34 @@ impl TryFrom<&Expr> for String {
         match value {
             Expr::Literal {
                 value: Value::r#String(s),
-            } => Ok(s.clone()),
+                ..
+            } => Ok(s.to_string()),
             Expr::Literal {
                 value: Value::Number(_),
+                ..
             } => type_error("string", "number"),
-            Expr::Literal { value: Value::Nil } => type_error("string", "nil"),
+            Expr::Literal {
+                value: Value::Nil, ..
+            } => type_error("string", "nil"),
             Expr::Literal {
                 value: Value::Bool(_),
+                ..
             } => type_error("string", "boolean"),
             Expr::Assign { .. } => type_error("string", "assignment expression"),
+            Expr::Literal {
+                value:
+                    value @ (Value::BigInt(_)
+                    | Value::Bytes(_)
+                    | Value::Callable(_)
+                    | Value::Class(_)
+                    | Value::Instance(_)),
+                ..
+            } => type_error("string", value.type_name()),
             Expr::Binary { .. } => type_error("string", "binary expression"),
+            Expr::Call { .. } => type_error("string", "call expression"),
             Expr::Grouping { .. } => type_error("string", "grouping expression"),
+            Expr::Index { .. } => type_error("string", "index expression"),
+            Expr::Logical { .. } => type_error("string", "logical expression"),
             Expr::Unary { .. } => type_error("string", "unary expression"),
             Expr::Variable { .. } => type_error("string", "variable"),
         }
@@ -101,17 +338,34 @@ impl TryFrom<&Expr> for f32 {
         match value {
             Expr::Literal {
                 value: Value::Number(n),
+                ..
             } => Ok(*n),
             Expr::Literal {
                 value: Value::r#String(_),
+                ..
             } => type_error("number", "string"),
-            Expr::Literal { value: Value::Nil } => type_error("number", "nil"),
+            Expr::Literal {
+                value: Value::Nil, ..
+            } => type_error("number", "nil"),
             Expr::Literal {
                 value: Value::Bool(_),
+                ..
             } => type_error("number", "boolean"),
             Expr::Assign { .. } => type_error("number", "assignment expression"),
+            Expr::Literal {
+                value:
+                    value @ (Value::BigInt(_)
+                    | Value::Bytes(_)
+                    | Value::Callable(_)
+                    | Value::Class(_)
+                    | Value::Instance(_)),
+                ..
+            } => type_error("number", value.type_name()),
             Expr::Binary { .. } => type_error("number", "binary expression"),
+            Expr::Call { .. } => type_error("number", "call expression"),
             Expr::Grouping { .. } => type_error("number", "grouping expression"),
+            Expr::Index { .. } => type_error("number", "index expression"),
+            Expr::Logical { .. } => type_error("number", "logical expression"),
             Expr::Unary { .. } => type_error("nubmer", "unary expression"),
             Expr::Variable { .. } => type_error("number", "variable"),
         }
@@ -123,17 +377,34 @@ impl TryFrom<&Expr> for bool {
         match value {
             Expr::Literal {
                 value: Value::Bool(b),
+                ..
             } => Ok(*b),
             Expr::Literal {
                 value: Value::r#String(_),
+                ..
             } => type_error("boolean", "string"),
-            Expr::Literal { value: Value::Nil } => type_error("boolean", "nil"),
+            Expr::Literal {
+                value: Value::Nil, ..
+            } => type_error("boolean", "nil"),
             Expr::Literal {
                 value: Value::Number(_),
+                ..
             } => type_error("boolean", "number"),
             Expr::Assign { .. } => type_error("boolean", "assignment expression"),
+            Expr::Literal {
+                value:
+                    value @ (Value::BigInt(_)
+                    | Value::Bytes(_)
+                    | Value::Callable(_)
+                    | Value::Class(_)
+                    | Value::Instance(_)),
+                ..
+            } => type_error("boolean", value.type_name()),
             Expr::Binary { .. } => type_error("boolean", "binary expression"),
+            Expr::Call { .. } => type_error("boolean", "call expression"),
             Expr::Grouping { .. } => type_error("boolean", "grouping expression"),
+            Expr::Index { .. } => type_error("boolean", "index expression"),
+            Expr::Logical { .. } => type_error("boolean", "logical expression"),
             Expr::Unary { .. } => type_error("boolean", "unary expression"),
             Expr::Variable { .. } => type_error("boolean", "variable"),
         }
@@ -150,6 +421,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast;
     #[test]
     fn expr_print_literal() -> InterpreterResult<()> {
         let e = Expr::literal_string("hello");
@@ -162,29 +434,36 @@ mod tests {
     }
     #[test]
     fn expr_grouping() -> InterpreterResult<()> {
-        let e = Expr::Grouping {
-            expression: Box::new(Expr::literal_nil()),
-        };
+        let e = ast::grouping(Expr::literal_nil());
         assert_eq!(e.print()?, String::from("(grouping nil)"));
         Ok(())
     }
     #[test]
     fn expr_binary() -> InterpreterResult<()> {
-        let e = Expr::Binary {
-            left: Box::new(Expr::literal_num(1.0)),
-            operator: Token::Plus { line: 0 },
-            right: Box::new(Expr::literal_num(2.0)),
-        };
+        let e = ast::binary(
+            Expr::literal_num(1.0),
+            Token::Plus { line: 0 },
+            Expr::literal_num(2.0),
+        );
         assert_eq!(e.print()?, String::from("(+ 1 2)"));
         Ok(())
     }
     #[test]
+    fn expr_assign() -> InterpreterResult<()> {
+        let e = ast::assign("foo", ast::num(1.0));
+        assert_eq!(e.print()?, String::from("(= foo 1)"));
+        Ok(())
+    }
+    #[test]
     fn expr_unary() -> InterpreterResult<()> {
-        let e = Expr::Unary {
-            operator: Token::Minus { line: 0 },
-            right: Box::new(Expr::literal_num(1.0)),
-        };
+        let e = ast::unary(Token::Minus { line: 0 }, Expr::literal_num(1.0));
         assert_eq!(e.print()?, String::from("(- 1)"));
         Ok(())
     }
+    #[test]
+    fn expr_call() -> InterpreterResult<()> {
+        let e = ast::call(ast::variable("add"), vec![ast::num(1.0), ast::num(2.0)]);
+        assert_eq!(e.print()?, String::from("(call add 1 2)"));
+        Ok(())
+    }
 }
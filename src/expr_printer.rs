@@ -1,5 +1,6 @@
 use crate::errors::InterpreterResult;
-use crate::expr::Expr;
+use crate::expr::{Expr, ExprVisitor};
+use crate::node::NodeId;
 use crate::token::Token;
 use crate::value::Value;
 use std::fmt::Write;
@@ -10,56 +11,73 @@ pub struct ExprPrinter {
 }
 
 impl ExprPrinter {
-    pub fn build(self, expr: &Expr) -> InterpreterResult<Self> {
-        match expr {
-            Expr::Literal { value } => self.build_literal(value),
-            Expr::Grouping { expression } => self.build_grouping(expression.as_ref()),
-            Expr::Binary {
-                left,
-                operator,
-                right,
-            } => self.build_binary(operator, left.as_ref(), right.as_ref()),
-            Expr::Unary { operator, right } => self.build_unary(operator, right.as_ref()),
-            Expr::Variable { name } => self.build_variable(name),
-            Expr::Assign { .. } => todo!(),
-        }
+    pub fn build(mut self, expr: &Expr) -> InterpreterResult<Self> {
+        expr.accept(&mut self)?;
+        Ok(self)
     }
     pub fn print(self) -> InterpreterResult<String> {
         Ok(self.s)
     }
-    fn build_literal(mut self, value: &Value) -> InterpreterResult<Self> {
-        write!(&mut self.s, "{}", value)?;
-        Ok(self)
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> InterpreterResult<()> {
+        write!(&mut self.s, "({}", name)?;
+        for expr in exprs.iter() {
+            self.s.write_str(" ")?;
+            expr.accept(self)?;
+        }
+        self.s.write_str(")")?;
+        Ok(())
     }
-    fn build_variable(mut self, name: &Token) -> InterpreterResult<Self> {
-        write!(&mut self.s, "{}", name)?;
-        Ok(self)
+}
+
+impl ExprVisitor<InterpreterResult<()>> for ExprPrinter {
+    fn visit_assign(&mut self, _id: NodeId, name: &Token, value: &Expr) -> InterpreterResult<()> {
+        self.parenthesize(&format!("= {}", name), &[value])
     }
-    fn build_grouping(self, expr: &Expr) -> InterpreterResult<Self> {
-        self.l_paren("grouping")?.build(expr)?.r_paren()
+    fn visit_binary(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> InterpreterResult<()> {
+        self.parenthesize(&operator.to_string(), &[left, right])
     }
-    fn build_binary(self, operator: &Token, left: &Expr, right: &Expr) -> InterpreterResult<Self> {
-        self.l_paren(&format!("{}", operator))?
-            .build(left)?
-            .space()?
-            .build(right)?
-            .r_paren()
+    fn visit_call(
+        &mut self,
+        callee: &Expr,
+        _paren: &Token,
+        arguments: &[Expr],
+    ) -> InterpreterResult<()> {
+        let exprs: Vec<&Expr> = std::iter::once(callee).chain(arguments.iter()).collect();
+        self.parenthesize("call", &exprs)
     }
-    fn build_unary(self, operator: &Token, right: &Expr) -> InterpreterResult<Self> {
-        self.l_paren(&format!("{}", operator))?
-            .build(right)?
-            .r_paren()
+    fn visit_grouping(&mut self, expression: &Expr) -> InterpreterResult<()> {
+        self.parenthesize("grouping", &[expression])
     }
-    fn l_paren(mut self, name: &str) -> InterpreterResult<Self> {
-        write!(&mut self.s, "({} ", name)?;
-        Ok(self)
+    fn visit_index(
+        &mut self,
+        object: &Expr,
+        _bracket: &Token,
+        index: &Expr,
+    ) -> InterpreterResult<()> {
+        self.parenthesize("index", &[object, index])
     }
-    fn r_paren(mut self) -> InterpreterResult<Self> {
-        self.s.write_str(")")?;
-        Ok(self)
+    fn visit_literal(&mut self, value: &Value) -> InterpreterResult<()> {
+        write!(&mut self.s, "{}", value)?;
+        Ok(())
     }
-    fn space(mut self) -> InterpreterResult<Self> {
-        self.s.write_str(" ")?;
-        Ok(self)
+    fn visit_logical(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> InterpreterResult<()> {
+        self.parenthesize(&operator.to_string(), &[left, right])
+    }
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) -> InterpreterResult<()> {
+        self.parenthesize(&operator.to_string(), &[right])
+    }
+    fn visit_variable(&mut self, _id: NodeId, name: &Token) -> InterpreterResult<()> {
+        write!(&mut self.s, "{}", name)?;
+        Ok(())
     }
 }
@@ -0,0 +1,187 @@
+use crate::errors::InterpreterError;
+use crate::lox::Lox;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+pub struct RloxInterpreter {
+    lox: Lox,
+    result: Option<CString>,
+    output: CString,
+    error: Option<CString>,
+}
+
+#[no_mangle]
+pub extern "C" fn rlox_new() -> *mut RloxInterpreter {
+    let mut lox = Lox::new();
+    lox.capture_output(true);
+    Box::into_raw(Box::new(RloxInterpreter {
+        lox,
+        result: None,
+        output: CString::default(),
+        error: None,
+    }))
+}
+
+/// # Safety
+/// `lox` must be null or a pointer returned by `rlox_new` that hasn't already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn rlox_free(lox: *mut RloxInterpreter) {
+    if !lox.is_null() {
+        drop(Box::from_raw(lox));
+    }
+}
+
+/// # Safety
+/// `lox` must be a live pointer returned by `rlox_new` and `source` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rlox_eval(lox: *mut RloxInterpreter, source: *const c_char) -> c_int {
+    let Some(lox) = lox.as_mut() else {
+        return InterpreterError::Usage.exit_code();
+    };
+    lox.result = None;
+    lox.error = None;
+    let res = if source.is_null() {
+        Err(InterpreterError::Usage)
+    } else {
+        match CStr::from_ptr(source).to_str() {
+            Ok(source) => panic::catch_unwind(AssertUnwindSafe(|| lox.lox.eval_str(source)))
+                .unwrap_or(Err(InterpreterError::Unknown)),
+            Err(_) => Err(InterpreterError::Interpreter {
+                line: 0,
+                message: "Source is not valid UTF-8".into(),
+            }),
+        }
+    };
+    lox.output = to_c_string(lox.lox.take_output());
+    match res {
+        Ok(val) => {
+            lox.result = Some(to_c_string(val.to_string()));
+            0
+        }
+        Err(err) => {
+            lox.error = Some(to_c_string(err.to_string()));
+            err.exit_code()
+        }
+    }
+}
+
+/// # Safety
+/// `lox` must be null or a live pointer returned by `rlox_new`. The returned string is owned by
+/// the interpreter and is only valid until the next call to `rlox_eval` or `rlox_free`.
+#[no_mangle]
+pub unsafe extern "C" fn rlox_last_result(lox: *const RloxInterpreter) -> *const c_char {
+    lox.as_ref()
+        .and_then(|lox| lox.result.as_deref())
+        .map_or(ptr::null(), CStr::as_ptr)
+}
+
+/// # Safety
+/// Same as `rlox_last_result`.
+#[no_mangle]
+pub unsafe extern "C" fn rlox_last_error(lox: *const RloxInterpreter) -> *const c_char {
+    lox.as_ref()
+        .and_then(|lox| lox.error.as_deref())
+        .map_or(ptr::null(), CStr::as_ptr)
+}
+
+/// # Safety
+/// Same as `rlox_last_result`.
+#[no_mangle]
+pub unsafe extern "C" fn rlox_last_output(lox: *const RloxInterpreter) -> *const c_char {
+    lox.as_ref().map_or(ptr::null(), |lox| lox.output.as_ptr())
+}
+
+fn to_c_string(s: String) -> CString {
+    CString::new(s.replace('\0', "")).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    unsafe fn read(s: *const c_char) -> Option<String> {
+        (!s.is_null()).then(|| CStr::from_ptr(s).to_string_lossy().into_owned())
+    }
+    #[test]
+    fn ffi_eval() {
+        unsafe {
+            let lox = rlox_new();
+            let src = CString::new("var a = 1; print a; a + 2;").unwrap();
+            assert_eq!(rlox_eval(lox, src.as_ptr()), 0);
+            assert_eq!(read(rlox_last_result(lox)), Some(String::from("3")));
+            assert_eq!(read(rlox_last_output(lox)), Some(String::from("1\n")));
+            assert_eq!(read(rlox_last_error(lox)), None);
+            rlox_free(lox);
+        }
+    }
+    #[test]
+    fn ffi_errors() {
+        unsafe {
+            let lox = rlox_new();
+            let src = CString::new("1 +;").unwrap();
+            assert_eq!(rlox_eval(lox, src.as_ptr()), 65);
+            assert!(read(rlox_last_error(lox)).is_some());
+            assert_eq!(read(rlox_last_result(lox)), None);
+            let src = CString::new("nope;").unwrap();
+            assert_eq!(rlox_eval(lox, src.as_ptr()), 70);
+            assert_eq!(rlox_eval(lox, ptr::null()), 64);
+            rlox_free(lox);
+            assert_eq!(rlox_eval(ptr::null_mut(), ptr::null()), 64);
+            assert!(rlox_last_error(ptr::null()).is_null());
+            rlox_free(ptr::null_mut());
+        }
+    }
+    // The header is written by hand, so check it declares exactly the
+    // functions exported here, with the same types.
+    #[test]
+    fn ffi_header_matches() {
+        fn c_type(rust: &str) -> String {
+            match rust.trim() {
+                "c_int" => String::from("int"),
+                "c_char" => String::from("char"),
+                t if t.starts_with("*mut ") => format!("{} *", c_type(&t[5..])),
+                t if t.starts_with("*const ") => format!("const {} *", c_type(&t[7..])),
+                t => String::from(t),
+            }
+        }
+        fn declare(ty: &str, name: &str) -> String {
+            if ty.ends_with('*') {
+                format!("{}{}", ty, name)
+            } else {
+                format!("{} {}", ty, name)
+            }
+        }
+        let mut exported: Vec<String> = include_str!("ffi.rs")
+            .lines()
+            .filter_map(|line| line.split_once("extern \"C\" fn "))
+            .map(|(_, sig)| {
+                let (name, rest) = sig.split_once('(').unwrap();
+                let (params, ret) = rest.split_once(')').unwrap();
+                let ret = ret.trim_end_matches('{').trim();
+                let ret = ret.strip_prefix("->").map_or(String::from("void"), c_type);
+                let params: Vec<String> = params
+                    .split(',')
+                    .filter(|p| !p.trim().is_empty())
+                    .map(|p| {
+                        let (name, ty) = p.split_once(':').unwrap();
+                        declare(&c_type(ty), name.trim())
+                    })
+                    .collect();
+                let params = if params.is_empty() {
+                    String::from("void")
+                } else {
+                    params.join(", ")
+                };
+                format!("{}({});", declare(&ret, name), params)
+            })
+            .collect();
+        let mut declared: Vec<String> = include_str!("../include/rlox.h")
+            .lines()
+            .filter(|line| line.contains("rlox_") && line.ends_with(");"))
+            .map(String::from)
+            .collect();
+        exported.sort();
+        declared.sort();
+        assert_eq!(exported, declared);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::cst::Cst;
+use crate::errors::InterpreterResult;
+use crate::token::Token;
+
+const INDENT: &str = "    ";
+
+pub fn format_source(source: &str) -> InterpreterResult<String> {
+    let cst = Cst::parse(source)?;
+    let mut formatter = Formatter::default();
+    for t in cst.tokens() {
+        match &t.token {
+            Token::Whitespace { .. } | Token::Eof { .. } => {}
+            token => formatter.token(token, cst.text(t)),
+        }
+    }
+    Ok(formatter.finish())
+}
+
+#[derive(Default)]
+struct Formatter<'a> {
+    out: String,
+    indent: usize,
+    parens: usize,
+    newline: bool,
+    unary: bool,
+    prev: Option<&'a Token>,
+    prev_line: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn token(&mut self, token: &'a Token, text: &str) {
+        let line = token.get_line().unwrap_or(self.prev_line);
+        match token {
+            Token::Comment { .. } if self.prev.is_some() && line == self.prev_line => {
+                self.out.push(' ');
+            }
+            Token::RightBrace { .. } => {
+                self.indent = self.indent.saturating_sub(1);
+                self.start_line(line, token);
+            }
+            Token::Else { .. } if matches!(self.prev, Some(Token::RightBrace { .. })) => {
+                self.out.push(' ');
+            }
+            _ if self.newline || self.prev.is_none() => self.start_line(line, token),
+            _ if self.spaced(token) => self.out.push(' '),
+            _ => {}
+        }
+        self.newline = false;
+        self.out.push_str(match token {
+            Token::Comment { .. } => text.trim_end(),
+            _ => text,
+        });
+        match token {
+            Token::LeftParen { .. } => self.parens += 1,
+            Token::RightParen { .. } => self.parens = self.parens.saturating_sub(1),
+            Token::LeftBrace { .. } => {
+                self.indent += 1;
+                self.newline = true;
+            }
+            Token::Semicolon { .. } if self.parens == 0 => self.newline = true,
+            Token::RightBrace { .. } | Token::Comment { .. } => self.newline = true,
+            _ => {}
+        }
+        self.unary = matches!(token, Token::Minus { .. } | Token::Bang { .. })
+            && self.prev.is_none_or(operand_expected);
+        self.prev = Some(token);
+        self.prev_line = line;
+    }
+    fn start_line(&mut self, line: usize, token: &Token) {
+        if let Some(prev) = self.prev {
+            self.out.push('\n');
+            if line > self.prev_line + 1
+                && !matches!(prev, Token::LeftBrace { .. })
+                && !matches!(token, Token::RightBrace { .. })
+            {
+                self.out.push('\n');
+            }
+        }
+        self.out.push_str(&INDENT.repeat(self.indent));
+    }
+    fn spaced(&self, token: &Token) -> bool {
+        let prev = match self.prev {
+            Some(prev) => prev,
+            None => return false,
+        };
+        if self.unary
+            || matches!(
+                prev,
+                Token::LeftParen { .. } | Token::LeftBracket { .. } | Token::Dot { .. }
+            )
+        {
+            return false;
+        }
+        match token {
+            Token::RightParen { .. }
+            | Token::RightBracket { .. }
+            | Token::Semicolon { .. }
+            | Token::Comma { .. }
+            | Token::Dot { .. } => false,
+            Token::LeftParen { .. } => !matches!(
+                prev,
+                Token::Identifier { .. }
+                    | Token::RightParen { .. }
+                    | Token::This { .. }
+                    | Token::Super { .. }
+            ),
+            Token::LeftBracket { .. } => operand_expected(prev),
+            _ => true,
+        }
+    }
+    fn finish(mut self) -> String {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+}
+
+fn operand_expected(prev: &Token) -> bool {
+    !matches!(
+        prev,
+        Token::Identifier { .. }
+            | Token::r#String { .. }
+            | Token::Number { .. }
+            | Token::BigInt { .. }
+            | Token::Bytes { .. }
+            | Token::RightParen { .. }
+            | Token::RightBracket { .. }
+            | Token::True { .. }
+            | Token::False { .. }
+            | Token::Nil { .. }
+            | Token::This { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn formatter_spacing() -> InterpreterResult<()> {
+        assert_eq!(
+            format_source("var  x=-1+ 2*(3-!y);print f( x,\"a b\" );")?,
+            "var x = -1 + 2 * (3 - !y);\nprint f(x, \"a b\");\n"
+        );
+        assert_eq!(
+            format_source("print b\"ab\\x00\" [ i+1 ]-1;")?,
+            "print b\"ab\\x00\"[i + 1] - 1;\n"
+        );
+        Ok(())
+    }
+    #[test]
+    fn formatter_layout() -> InterpreterResult<()> {
+        let source =
+            "fun f(a,b){ // add\nvar c=a+b;\n\n\n  return c;}\n// done\nwhile(x<3){x=x+1;}";
+        let expected = "fun f(a, b) { // add\n    var c = a + b;\n\n    return c;\n}\n// done\nwhile (x < 3) {\n    x = x + 1;\n}\n";
+        assert_eq!(format_source(source)?, expected);
+        assert_eq!(format_source(expected)?, expected);
+        Ok(())
+    }
+    #[test]
+    fn formatter_rejects_invalid_source() {
+        assert!(format_source("var x = ;").is_err());
+        assert!(format_source("\"unterminated").is_err());
+    }
+}
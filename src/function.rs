@@ -0,0 +1,171 @@
+use crate::class::LoxInstance;
+use crate::environment::Environment;
+use crate::errors::InterpreterResult;
+use crate::interpreter::Interpreter;
+use crate::plugin::PluginCall;
+use crate::resolver::Locals;
+use crate::stmt::Stmt;
+use crate::token::Token;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+pub struct LoxFunction {
+    pub(crate) name: Token,
+    pub(crate) params: Vec<Token>,
+    pub(crate) body: Vec<Stmt>,
+    pub(crate) closure: Option<Rc<RefCell<Environment>>>,
+    pub(crate) locals: Locals,
+    pub(crate) this: Option<Value>,
+}
+
+impl LoxFunction {
+    pub fn name(&self) -> String {
+        self.name.to_string()
+    }
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+    pub fn signature(&self) -> String {
+        let params: Vec<String> = self.params.iter().map(Token::to_string).collect();
+        format!("{}({})", self.name, params.join(", "))
+    }
+    pub(crate) fn line(&self) -> usize {
+        self.name.get_line().unwrap_or(0)
+    }
+    // A copy whose calls see instance as `this`.
+    pub(crate) fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> Self {
+        Self {
+            name: self.name.clone(),
+            params: self.params.clone(),
+            body: self.body.clone(),
+            closure: self.closure.clone(),
+            locals: Rc::clone(&self.locals),
+            this: Some(Value::Instance(instance)),
+        }
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name)
+    }
+}
+
+pub(crate) type NativeFn = fn(&Interpreter, Vec<Value>, usize) -> InterpreterResult<Value>;
+
+pub(crate) enum NativeCall {
+    Builtin(NativeFn),
+    #[cfg_attr(not(feature = "dlopen"), allow(dead_code))]
+    Plugin(PluginCall),
+}
+
+pub struct NativeFunction {
+    pub(crate) name: &'static str,
+    pub(crate) params: &'static str,
+    pub(crate) arity: RangeInclusive<usize>,
+    pub(crate) call: NativeCall,
+    pub(crate) doc: &'static str,
+}
+
+impl NativeFunction {
+    pub fn name(&self) -> String {
+        String::from(self.name)
+    }
+    pub fn arity(&self) -> usize {
+        *self.arity.start()
+    }
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, self.params)
+    }
+    pub fn doc(&self) -> &str {
+        self.doc
+    }
+    pub(crate) fn accepts(&self, args: usize) -> bool {
+        self.arity.contains(&args)
+    }
+    pub(crate) fn expected(&self) -> String {
+        if self.arity.start() == self.arity.end() {
+            self.arity.start().to_string()
+        } else {
+            format!("{} to {}", self.arity.start(), self.arity.end())
+        }
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Callable {
+    Lox(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+}
+
+impl Callable {
+    pub fn name(&self) -> String {
+        match self {
+            Self::Lox(f) => f.name(),
+            Self::Native(f) => f.name(),
+        }
+    }
+    pub fn arity(&self) -> usize {
+        match self {
+            Self::Lox(f) => f.arity(),
+            Self::Native(f) => f.arity(),
+        }
+    }
+    pub fn signature(&self) -> String {
+        match self {
+            Self::Lox(f) => f.signature(),
+            Self::Native(f) => f.signature(),
+        }
+    }
+    pub(crate) fn call(
+        &self,
+        interpreter: &Interpreter,
+        args: Vec<Value>,
+        line: usize,
+    ) -> InterpreterResult<Value> {
+        match self {
+            Self::Lox(f) => interpreter.call_function(f, args, line),
+            Self::Native(f) => interpreter.call_native(f, args, line),
+        }
+    }
+}
+
+impl PartialEq for Callable {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Lox(f), Self::Lox(o)) => Rc::ptr_eq(f, o),
+            (Self::Native(f), Self::Native(o)) => Rc::ptr_eq(f, o),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Callable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lox(fun) => write!(f, "{}", fun),
+            Self::Native(fun) => write!(f, "{}", fun),
+        }
+    }
+}
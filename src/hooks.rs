@@ -0,0 +1,106 @@
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::function::Callable;
+use crate::interpreter::Interpreter;
+use crate::stmt::Stmt;
+use crate::value::Value;
+
+pub trait InterpreterHooks {
+    fn on_statement_enter(&mut self, _ctx: &HookContext, _stmt: &Stmt) -> InterpreterResult<()> {
+        Ok(())
+    }
+    fn on_call(
+        &mut self,
+        _ctx: &HookContext,
+        _callee: &Callable,
+        _args: &[Value],
+        _line: usize,
+    ) -> InterpreterResult<()> {
+        Ok(())
+    }
+    fn on_return(&mut self, _ctx: &HookContext, _callee: &Callable, _value: &Value) {}
+    fn on_error(&mut self, _ctx: &HookContext, _error: &InterpreterError) {}
+}
+
+pub struct HookContext<'a> {
+    interpreter: &'a Interpreter,
+}
+
+impl<'a> HookContext<'a> {
+    pub(crate) fn new(interpreter: &'a Interpreter) -> Self {
+        Self { interpreter }
+    }
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        self.interpreter.lookup(name)
+    }
+    pub fn scopes(&self) -> Vec<Vec<(String, Value)>> {
+        self.interpreter.scopes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::Lox;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+
+    impl InterpreterHooks for Recorder {
+        fn on_statement_enter(&mut self, _ctx: &HookContext, stmt: &Stmt) -> InterpreterResult<()> {
+            self.0
+                .borrow_mut()
+                .push(format!("line {}", stmt.line().unwrap_or(0)));
+            Ok(())
+        }
+        fn on_call(
+            &mut self,
+            ctx: &HookContext,
+            callee: &Callable,
+            args: &[Value],
+            _line: usize,
+        ) -> InterpreterResult<()> {
+            let total = ctx.lookup("total").unwrap_or(Value::Nil);
+            self.0.borrow_mut().push(format!(
+                "call {}({}) total={}",
+                callee.name(),
+                args.len(),
+                total
+            ));
+            Ok(())
+        }
+        fn on_return(&mut self, _ctx: &HookContext, callee: &Callable, value: &Value) {
+            self.0
+                .borrow_mut()
+                .push(format!("return {} {}", callee.name(), value));
+        }
+        fn on_error(&mut self, _ctx: &HookContext, error: &InterpreterError) {
+            self.0.borrow_mut().push(format!("error {}", error));
+        }
+    }
+
+    #[test]
+    fn hooks_events() -> InterpreterResult<()> {
+        let recorder = Recorder::default();
+        let mut lox = Lox::new();
+        lox.add_hook(Box::new(recorder.clone()));
+        lox.eval_str("var total = 1;\nfun add(n) {\n  return total + n;\n}\nadd(2);\n")?;
+        assert!(lox.eval_str("add();").is_err());
+        assert_eq!(
+            *recorder.0.borrow(),
+            vec![
+                "line 1",
+                "line 2",
+                "line 5",
+                "call add(1) total=1",
+                "line 3",
+                "return add 3",
+                "line 1",
+                "call add(0) total=1",
+                "error Runtime error on line 1: Expected 1 arguments but got 0",
+            ]
+        );
+        Ok(())
+    }
+}
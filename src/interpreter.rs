@@ -1,190 +1,829 @@
-use crate::environment::Environment;
+use crate::bench;
+use crate::cancel::CancelToken;
+use crate::capabilities::{Capabilities, Capability};
+use crate::environment::{Environment, Globals};
 use crate::errors::{InterpreterError, InterpreterResult};
-use crate::expr::Expr;
-use crate::stmt::Stmt;
+use crate::expr::{Expr, ExprVisitor};
+use crate::function::{Callable, LoxFunction, NativeCall, NativeFunction};
+use crate::hooks::{HookContext, InterpreterHooks};
+use crate::limits::{Limits, Usage};
+use crate::natives;
+use crate::node::NodeId;
+use crate::resolver::{self, Locals};
+use crate::stats::{Counters, Stats};
+use crate::stmt::{Stmt, StmtVisitor};
+use crate::string::LoxString;
 use crate::token::Token;
-use crate::value::Value;
-use std::cell::RefCell;
+use crate::value::{whole_number, Value};
+use num_bigint::BigInt;
+use num_traits::Zero;
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::io::{self, BufRead};
 use std::rc::Rc;
+use std::sync::Arc;
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub(crate) struct Interpreter {
-    env: Rc<RefCell<Environment>>,
+    globals: RefCell<Globals>,
+    env: RefCell<Option<Rc<RefCell<Environment>>>>,
+    locals: RefCell<Locals>,
+    returned: RefCell<Option<Value>>,
+    output: RefCell<Option<String>>,
+    cancel: CancelToken,
+    limits: Limits,
+    usage: Usage,
+    capabilities: Capabilities,
+    input: RefCell<Input>,
+    hooks: RefCell<Hooks>,
+    strict: bool,
+    stats: Counters,
+    prelude: RefCell<Option<Arc<str>>>,
+}
+
+struct EnvGuard<'a> {
+    interpreter: &'a Interpreter,
+    previous: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Drop for EnvGuard<'_> {
+    fn drop(&mut self) {
+        _ = self.interpreter.env.replace(self.previous.take());
+    }
+}
+
+struct LocalsGuard<'a> {
+    interpreter: &'a Interpreter,
+    previous: Locals,
+}
+
+impl Drop for LocalsGuard<'_> {
+    fn drop(&mut self) {
+        _ = self
+            .interpreter
+            .locals
+            .replace(std::mem::take(&mut self.previous));
+    }
+}
+
+#[derive(Default)]
+struct Input(Option<Box<dyn BufRead>>);
+
+#[derive(Default)]
+struct Hooks(Vec<Box<dyn InterpreterHooks>>);
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hooks({})", self.0.len())
+    }
+}
+
+impl fmt::Debug for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() {
+            "Input(custom)"
+        } else {
+            "Input(stdin)"
+        })
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self {
+            globals: RefCell::new(natives::globals()),
+            env: RefCell::default(),
+            locals: RefCell::default(),
+            returned: RefCell::default(),
+            output: RefCell::default(),
+            cancel: CancelToken::default(),
+            limits: Limits::default(),
+            usage: Usage::default(),
+            capabilities: Capabilities::default(),
+            input: RefCell::default(),
+            hooks: RefCell::default(),
+            strict: false,
+            stats: Counters::default(),
+            prelude: RefCell::default(),
+        }
+    }
 }
 
 impl Interpreter {
     pub(crate) fn interpret(&self, stmt: &Stmt) -> InterpreterResult<Value> {
-        match stmt {
-            Stmt::Expr { expr } => self.interpret_expr(expr),
-            Stmt::Print { expr } => {
-                let val = self.interpret_expr(expr)?;
-                Self::print(val)
-            }
-            Stmt::Variable {
-                name: Token::Identifier { literal, .. },
-                initializer,
-            } => {
-                let val = match initializer {
-                    Some(initializer) => self.interpret_expr(initializer)?,
-                    None => Value::Nil,
-                };
-                self.env.borrow_mut().define(String::from(literal), val);
-                Ok(Value::Nil)
-            }
-            Stmt::Block { stmts } => {
-                let new = Environment::new(Rc::clone(&self.env));
-                let previous = self.env.replace(new);
-                for stmt in stmts.iter() {
-                    match self.interpret(stmt) {
-                        Ok(_) => continue,
-                        Err(e) => {
-                            _ = self.env.replace(previous);
-                            return Err(e);
-                        }
-                    };
-                }
-                _ = self.env.replace(previous);
-                Ok(Value::Nil)
-            }
-            _ => Err(InterpreterError::SyntaxError {
-                line: 0,
-                message: "Invalid variable".into(),
-            }),
+        self.check_cancelled()?;
+        self.run_hooks(|hook, ctx| hook.on_statement_enter(ctx, stmt))?;
+        self.stats.update(|stats| stats.statements += 1);
+        stmt.accept(&mut &*self)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "interpret", level = "debug", skip_all, fields(stmts = stmts.len()))
+    )]
+    pub(crate) fn interpret_all(&self, stmts: &[Stmt]) -> InterpreterResult<Option<Value>> {
+        let locals = self
+            .resolve(stmts)
+            .map_err(|errs| errs.into_iter().next().unwrap_or(InterpreterError::Unknown))?;
+        self.execute(stmts, locals)
+    }
+
+    // Runs statements with the table `resolve` returned for them.
+    pub(crate) fn execute(
+        &self,
+        stmts: &[Stmt],
+        locals: Locals,
+    ) -> InterpreterResult<Option<Value>> {
+        let _guard = LocalsGuard {
+            interpreter: self,
+            previous: self.locals.replace(locals),
+        };
+        self.usage.reset();
+        let (allocations, bytes) = bench::allocations();
+        let res = self.interpret_stmts(stmts);
+        let (allocations_after, bytes_after) = bench::allocations();
+        self.stats.update(|stats| {
+            stats.allocations += allocations_after.saturating_sub(allocations);
+            stats.allocated_bytes += bytes_after.saturating_sub(bytes);
+        });
+        res
+    }
+
+    fn interpret_stmts(&self, stmts: &[Stmt]) -> InterpreterResult<Option<Value>> {
+        let mut last = None;
+        for stmt in stmts.iter() {
+            let val = self.interpret(stmt).inspect_err(|err| {
+                _ = self.run_hooks(|hook, ctx| {
+                    hook.on_error(ctx, err);
+                    Ok(())
+                })
+            })?;
+            last = matches!(stmt, Stmt::Expr { .. }).then_some(val);
         }
+        Ok(last)
     }
 
-    fn print(val: Value) -> InterpreterResult<Value> {
-        println!("{}", val);
-        Ok(Value::Nil)
+    pub(crate) fn stats(&self) -> Stats {
+        self.stats.get()
     }
 
-    fn interpret_expr(&self, expr: &Expr) -> InterpreterResult<Value> {
-        match expr {
-            Expr::Assign { name, value } => self.interpret_assign(name, value),
-            Expr::Literal { value } => Ok(value.clone()),
-            Expr::Grouping { expression } => self.interpret_grouping(expression.as_ref()),
-            Expr::Binary {
-                left,
-                operator,
-                right,
-            } => self.interpret_binary(operator, left.as_ref(), right.as_ref()),
-            Expr::Unary { operator, right } => self.interpret_unary(operator, right.as_ref()),
-            Expr::Variable {
-                name: Token::Identifier { literal, line, .. },
-            } => self.get_variable(literal, line),
-            _ => Err(InterpreterError::SyntaxError {
-                line: 0,
-                message: "Invalid variable".into(),
-            }),
+    pub(crate) fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
+    pub(crate) fn lookup(&self, name: &str) -> Option<Value> {
+        self.current_env()
+            .and_then(|env| env.borrow().get(name).ok())
+            .or_else(|| self.globals().get(name).ok())
+    }
+
+    pub(crate) fn define(&self, name: &str, value: Value) {
+        match self.current_env() {
+            Some(env) => env.borrow_mut().define(String::from(name), value),
+            None => self.globals.borrow_mut().define(String::from(name), value),
         }
     }
-    fn get_variable(&self, literal: &str, line: &usize) -> InterpreterResult<Value> {
-        self.env
-            .borrow()
-            .get(literal)
-            .map_err(|e| e.add_line_to_undefined_error(*line))
+
+    pub(crate) fn snapshot(&self) -> Globals {
+        self.globals().clone()
     }
 
-    fn interpret_assign(&self, name: &Token, value: &Expr) -> InterpreterResult<Value> {
-        match name {
-            Token::Identifier { literal, line, .. } => {
-                let v = self.interpret_expr(value)?;
-                self.env
-                    .borrow_mut()
-                    .assign(literal, v)
-                    .map_err(|e| e.add_line_to_undefined_error(*line))
-            }
-            t => Err(InterpreterError::SyntaxError {
-                line: t.get_line().unwrap_or(0),
-                message: "Invalid assignment".into(),
-            }),
+    pub(crate) fn restore(&self, globals: Globals) {
+        _ = self.globals.replace(globals);
+    }
+
+    pub(crate) fn reset(&self) {
+        self.restore(natives::globals());
+        self.set_prelude(None);
+    }
+
+    // The source of the loaded prelude, so that spawned threads can load it too.
+    pub(crate) fn prelude(&self) -> Option<Arc<str>> {
+        self.prelude.borrow().clone()
+    }
+
+    pub(crate) fn set_prelude(&self, source: Option<Arc<str>>) {
+        _ = self.prelude.replace(source);
+    }
+
+    pub(crate) fn add_hook(&mut self, hook: Box<dyn InterpreterHooks>) {
+        self.hooks.get_mut().0.push(hook);
+    }
+
+    fn run_hooks<F>(&self, mut f: F) -> InterpreterResult<()>
+    where
+        F: FnMut(&mut dyn InterpreterHooks, &HookContext) -> InterpreterResult<()>,
+    {
+        let mut hooks = std::mem::take(&mut self.hooks.borrow_mut().0);
+        let ctx = HookContext::new(self);
+        let res = hooks.iter_mut().try_for_each(|hook| f(hook.as_mut(), &ctx));
+        self.hooks.borrow_mut().0 = hooks;
+        res
+    }
+
+    fn call(&self, callable: &Callable, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+        self.run_hooks(|hook, ctx| hook.on_call(ctx, callable, &args, line))?;
+        #[cfg(feature = "tracing")]
+        let _span = {
+            tracing::trace!(function = %callable.name(), line, args = args.len(), "call");
+            tracing::trace_span!("call", function = %callable.name(), line).entered()
+        };
+        let value = callable.call(self, args, line)?;
+        #[cfg(feature = "tracing")]
+        tracing::trace!(%value, "return");
+        self.run_hooks(|hook, ctx| {
+            hook.on_return(ctx, callable, &value);
+            Ok(())
+        })?;
+        Ok(value)
+    }
+
+    pub(crate) fn scopes(&self) -> Vec<Vec<(String, Value)>> {
+        let mut scopes = Vec::default();
+        let mut env = self.current_env();
+        while let Some(current) = env {
+            let current = current.borrow();
+            scopes.push(sorted(current.values()));
+            env = current.enclosing();
         }
+        scopes.push(sorted(self.globals().values()));
+        scopes
     }
-    fn interpret_grouping(&self, expr: &Expr) -> InterpreterResult<Value> {
-        self.interpret_expr(expr)
+
+    pub(crate) fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.input = RefCell::new(Input(Some(input)));
+    }
+
+    pub(crate) fn read_line(&self) -> InterpreterResult<Option<String>> {
+        let mut line = String::default();
+        let read = match self.input.borrow_mut().0.as_mut() {
+            Some(input) => input.read_line(&mut line)?,
+            None => io::stdin().lock().read_line(&mut line)?,
+        };
+        Ok((read > 0).then_some(line))
     }
 
-    fn interpret_binary(
+    pub(crate) fn call_function(
         &self,
-        operator: &Token,
-        left: &Expr,
-        right: &Expr,
+        function: &LoxFunction,
+        args: Vec<Value>,
+        line: usize,
     ) -> InterpreterResult<Value> {
-        let left = self.interpret_expr(left)?;
-        let right = self.interpret_expr(right)?;
+        if args.len() != function.arity() {
+            return Err(InterpreterError::RuntimeError {
+                line,
+                message: format!(
+                    "Expected {} arguments but got {}",
+                    function.arity(),
+                    args.len()
+                ),
+            });
+        }
+        self.usage.enter_call(&self.limits)?;
+        let depth = self.usage.call_depth();
+        self.stats.update(|stats| {
+            stats.calls += 1;
+            stats.peak_call_depth = stats.peak_call_depth.max(depth);
+        });
+        let mut env = Environment::new(function.closure.clone());
+        if let Some(this) = &function.this {
+            env.define(String::from("this"), this.clone());
+        }
+        for (param, arg) in function.params.iter().zip(args) {
+            env.define(param.to_string(), arg);
+        }
+        let _guard = LocalsGuard {
+            interpreter: self,
+            previous: self.locals.replace(Rc::clone(&function.locals)),
+        };
+        let res = match self.execute_block(&function.body, Rc::new(RefCell::new(env))) {
+            Ok(()) => Ok(Value::Nil),
+            Err(InterpreterError::Return { .. }) => Ok(self.returned.take().unwrap_or(Value::Nil)),
+            Err(e) => Err(e),
+        };
+        self.usage.exit_call();
+        res
+    }
+
+    pub(crate) fn call_native(
+        &self,
+        native: &NativeFunction,
+        args: Vec<Value>,
+        line: usize,
+    ) -> InterpreterResult<Value> {
+        if !native.accepts(args.len()) {
+            return Err(InterpreterError::RuntimeError {
+                line,
+                message: format!(
+                    "Expected {} arguments but got {}",
+                    native.expected(),
+                    args.len()
+                ),
+            });
+        }
+        self.stats.update(|stats| stats.calls += 1);
+        match &native.call {
+            NativeCall::Builtin(call) => call(self, args, line),
+            NativeCall::Plugin(plugin) => plugin.call(args, line),
+        }
+    }
+
+    pub(crate) fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub(crate) fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub(crate) fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub(crate) fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    pub(crate) fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    pub(crate) fn require(&self, capability: Capability, line: usize) -> InterpreterResult<()> {
+        self.capabilities.require(capability, line)
+    }
+
+    pub(crate) fn reset_usage(&self) {
+        self.usage.reset();
+    }
+
+    pub(crate) fn globals(&self) -> Ref<'_, Globals> {
+        self.globals.borrow()
+    }
+
+    fn current_env(&self) -> Option<Rc<RefCell<Environment>>> {
+        self.env.borrow().clone()
+    }
+
+    pub(crate) fn execute_block(
+        &self,
+        stmts: &[Stmt],
+        env: Rc<RefCell<Environment>>,
+    ) -> InterpreterResult<()> {
+        let _guard = EnvGuard {
+            interpreter: self,
+            previous: self.env.replace(Some(env)),
+        };
+        for stmt in stmts.iter() {
+            self.interpret(stmt)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    fn check_cancelled(&self) -> InterpreterResult<()> {
+        if self.cancel.take() {
+            Err(InterpreterError::Interrupted)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn capture_output(&self, capture: bool) {
+        _ = self.output.replace(capture.then(String::default));
+    }
+
+    pub(crate) fn take_output(&self) -> String {
+        self.output
+            .borrow_mut()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn print(&self, val: Value, line: usize) -> InterpreterResult<Value> {
+        let s = self.stringify(&val, line)?;
+        match self.output.borrow_mut().as_mut() {
+            Some(output) => writeln!(output, "{}", s)?,
+            None => println!("{}", s),
+        }
+        Ok(Value::Nil)
+    }
+
+    // An instance that defines eq(other) decides equality with anything; the
+    // left operand's method wins when both have one.
+    fn equals(&self, left: &Value, right: &Value, line: usize) -> InterpreterResult<bool> {
+        let method = |value: &Value| match value {
+            Value::Instance(instance) => instance
+                .borrow()
+                .class
+                .find_method("eq")
+                .map(|method| Rc::new(method.bind(Rc::clone(instance)))),
+            _ => None,
+        };
+        let (method, other) = match (method(left), method(right)) {
+            (Some(method), _) => (method, right),
+            (None, Some(method)) => (method, left),
+            (None, None) => return Ok(left == right),
+        };
+        let result = self.call(&Callable::Lox(method), vec![other.clone()], line)?;
+        cast_bool(&result, &line)
+    }
+
+    // Instances that define toString() print as whatever it returns.
+    pub(crate) fn stringify(&self, val: &Value, line: usize) -> InterpreterResult<String> {
+        let method = match val {
+            Value::Instance(instance) => instance
+                .borrow()
+                .class
+                .find_method("toString")
+                .map(|method| Rc::new(method.bind(Rc::clone(instance)))),
+            _ => None,
+        };
+        match method {
+            Some(method) => Ok(self
+                .call(&Callable::Lox(method), Vec::default(), line)?
+                .to_string()),
+            None => Ok(val.to_string()),
+        }
+    }
+
+    pub(crate) fn resolve(&self, stmts: &[Stmt]) -> Result<Locals, Vec<InterpreterError>> {
+        let (locals, errs) = if self.strict {
+            resolver::resolve_strict(stmts, self.globals().values().keys())
+        } else {
+            resolver::resolve(stmts)
+        };
+        if errs.is_empty() {
+            Ok(Rc::new(locals))
+        } else {
+            Err(errs)
+        }
+    }
+    fn depth(&self, id: NodeId) -> Option<usize> {
+        self.locals.borrow().get(&id).copied()
+    }
+    fn interpret_expr(&self, expr: &Expr) -> InterpreterResult<Value> {
+        self.usage.expression(&self.limits)?;
+        expr.accept(&mut &*self)
+    }
+    fn get_variable(
+        &self,
+        literal: &str,
+        line: &usize,
+        depth: Option<usize>,
+    ) -> InterpreterResult<Value> {
+        self.stats.update(|stats| stats.lookups += 1);
+        match (depth, self.current_env()) {
+            (Some(depth), Some(env)) => env.borrow().get_at(depth, literal),
+            _ => self.globals().get(literal),
+        }
+        .map_err(|e| e.add_line_to_undefined_error(*line))
+    }
+    fn repeat(&self, s: &LoxString, count: f32, line: &usize) -> InterpreterResult<Value> {
+        if count < 0.0 || count.fract() != 0.0 || !count.is_finite() {
+            return Err(InterpreterError::type_error(
+                "whole number".into(),
+                Value::Number(count).to_string(),
+            )
+            .add_line_to_type_error(*line));
+        }
+        let count = count as usize;
+        // Charged before allocating; an overflowing size saturates and so
+        // exceeds any cap.
+        self.usage
+            .string(s.len().saturating_mul(count), &self.limits)?;
+        Ok(Value::r#String(s.with_str(|s| s.repeat(count)).into()))
+    }
+    // Kept out of visit_binary so that recursing through the operands doesn't
+    // carry every operator's temporaries on the stack.
+    fn binary(&self, left: &Value, operator: &Token, right: &Value) -> InterpreterResult<Value> {
+        if let Some(value) = bigint_binary(left, operator, right)? {
+            return Ok(value);
+        }
         match operator {
             Token::Minus { line } => {
-                let left = cast_f32(&left, line)?;
-                let right = cast_f32(&right, line)?;
+                let left = cast_f32(left, line)?;
+                let right = cast_f32(right, line)?;
                 Ok(Value::Number(left - right))
             }
             Token::Slash { line } => {
-                let left = cast_f32(&left, line)?;
-                let right = cast_f32(&right, line)?;
+                let left = cast_f32(left, line)?;
+                let right = cast_f32(right, line)?;
                 Ok(Value::Number(left / right))
             }
-            Token::Star { line } => {
-                let left = cast_f32(&left, line)?;
-                let right = cast_f32(&right, line)?;
-                Ok(Value::Number(left * right))
-            }
-            Token::Plus { line } => {
-                let left_num = cast_f32(&left, line);
-                if left_num.is_ok() {
-                    let right_num = cast_f32(&right, line)?;
-                    left_num.map(|n| Value::Number(n + right_num))
-                } else {
-                    let left_str = cast_string(&left, line)?;
-                    let right_str = cast_string(&right, line)?;
-                    Ok(Value::r#String(format!("{}{}", left_str, right_str)))
+            Token::Star { line } => match (left, right) {
+                (Value::r#String(s), Value::Number(n)) | (Value::Number(n), Value::r#String(s)) => {
+                    self.repeat(s, *n, line)
                 }
-            }
+                _ => {
+                    let left = cast_f32(left, line)?;
+                    let right = cast_f32(right, line)?;
+                    Ok(Value::Number(left * right))
+                }
+            },
+            Token::Plus { line } => match (left, cast_f32(left, line)) {
+                (_, Ok(n)) => Ok(Value::Number(n + cast_f32(right, line)?)),
+                (Value::r#String(left_str), _) => {
+                    let right_str = cast_string(right, line)?;
+                    self.usage
+                        .string(left_str.len() + right_str.len(), &self.limits)?;
+                    Ok(Value::r#String(left_str.concat(&right_str)))
+                }
+                _ => Err(InterpreterError::type_error(
+                    "number or string".into(),
+                    left.type_name().into(),
+                )
+                .add_line_to_type_error(*line)),
+            },
             Token::Greater { line } => {
-                let left = cast_f32(&left, line)?;
-                let right = cast_f32(&right, line)?;
+                let left = cast_f32(left, line)?;
+                let right = cast_f32(right, line)?;
                 Ok(Value::Bool(left > right))
             }
             Token::Less { line } => {
-                let left = cast_f32(&left, line)?;
-                let right = cast_f32(&right, line)?;
+                let left = cast_f32(left, line)?;
+                let right = cast_f32(right, line)?;
                 Ok(Value::Bool(left < right))
             }
             Token::GreaterEqual { line } => {
-                let left = cast_f32(&left, line)?;
-                let right = cast_f32(&right, line)?;
+                let left = cast_f32(left, line)?;
+                let right = cast_f32(right, line)?;
                 Ok(Value::Bool(left >= right))
             }
             Token::LessEqual { line } => {
-                let left = cast_f32(&left, line)?;
-                let right = cast_f32(&right, line)?;
+                let left = cast_f32(left, line)?;
+                let right = cast_f32(right, line)?;
                 Ok(Value::Bool(left <= right))
             }
-            Token::EqualEqual { .. } => Ok(Value::Bool(left == right)),
-            Token::BangEqual { .. } => Ok(Value::Bool(left != right)),
-            t => Err(InterpreterError::SyntaxError {
+            Token::EqualEqual { line } => Ok(Value::Bool(self.equals(left, right, *line)?)),
+            Token::BangEqual { line } => Ok(Value::Bool(!self.equals(left, right, *line)?)),
+            t => Err(InterpreterError::RuntimeError {
                 line: t.get_line().unwrap_or(0),
                 message: "Invalid binary expression".into(),
             }),
         }
     }
+}
 
-    fn interpret_unary(&self, operator: &Token, right: &Expr) -> InterpreterResult<Value> {
+impl StmtVisitor<InterpreterResult<Value>> for &Interpreter {
+    fn visit_block(&mut self, stmts: &[Stmt]) -> InterpreterResult<Value> {
+        let env = Environment::new(self.current_env());
+        self.execute_block(stmts, Rc::new(RefCell::new(env)))?;
+        Ok(Value::Nil)
+    }
+    fn visit_variable(
+        &mut self,
+        name: &Token,
+        initializer: Option<&Expr>,
+    ) -> InterpreterResult<Value> {
+        let val = match initializer {
+            Some(initializer) => self.interpret_expr(initializer)?,
+            None => Value::Nil,
+        };
+        self.define(&name.to_string(), val);
+        Ok(Value::Nil)
+    }
+    fn visit_print(&mut self, keyword: &Token, expr: &Expr) -> InterpreterResult<Value> {
+        let val = self.interpret_expr(expr)?;
+        self.print(val, keyword.get_line().unwrap_or(0))
+    }
+    fn visit_expr(&mut self, expr: &Expr) -> InterpreterResult<Value> {
+        self.interpret_expr(expr)
+    }
+    fn visit_function(
+        &mut self,
+        name: &Token,
+        params: &[Token],
+        body: &[Stmt],
+    ) -> InterpreterResult<Value> {
+        let function = LoxFunction {
+            name: name.clone(),
+            params: params.to_vec(),
+            body: body.to_vec(),
+            closure: self.current_env(),
+            locals: Rc::clone(&self.locals.borrow()),
+            this: None,
+        };
+        self.define(
+            &name.to_string(),
+            Value::Callable(Callable::Lox(Rc::new(function))),
+        );
+        Ok(Value::Nil)
+    }
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) -> InterpreterResult<Value> {
+        let value = match value {
+            Some(value) => self.interpret_expr(value)?,
+            None => Value::Nil,
+        };
+        _ = self.returned.replace(Some(value));
+        Err(InterpreterError::Return {
+            line: keyword.get_line().unwrap_or(0),
+        })
+    }
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> InterpreterResult<Value> {
+        while bool::try_from(&self.interpret_expr(condition)?)? {
+            self.check_cancelled()?;
+            self.interpret(body)?;
+        }
+        Ok(Value::Nil)
+    }
+}
+
+impl ExprVisitor<InterpreterResult<Value>> for &Interpreter {
+    fn visit_assign(&mut self, id: NodeId, name: &Token, value: &Expr) -> InterpreterResult<Value> {
+        match name {
+            Token::Identifier { literal, line, .. } => {
+                let v = self.interpret_expr(value)?;
+                self.stats.update(|stats| stats.lookups += 1);
+                match (self.depth(id), self.current_env()) {
+                    (Some(depth), Some(env)) => env.borrow_mut().assign_at(depth, literal, v),
+                    _ => self.globals.borrow_mut().assign(literal, v),
+                }
+                .map_err(|e| e.add_line_to_undefined_error(*line))
+            }
+            t => Err(InterpreterError::RuntimeError {
+                line: t.get_line().unwrap_or(0),
+                message: "Invalid assignment".into(),
+            }),
+        }
+    }
+    fn visit_binary(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> InterpreterResult<Value> {
+        let left = self.interpret_expr(left)?;
         let right = self.interpret_expr(right)?;
-        match operator {
-            Token::Minus { line } => {
-                let num = cast_f32(&right, line)?;
-                Ok(Value::Number(-num))
+        self.binary(&left, operator, &right)
+    }
+
+    fn visit_call(
+        &mut self,
+        callee: &Expr,
+        paren: &Token,
+        arguments: &[Expr],
+    ) -> InterpreterResult<Value> {
+        let callee = self.interpret_expr(callee)?;
+        let args = arguments
+            .iter()
+            .map(|a| self.interpret_expr(a))
+            .collect::<InterpreterResult<Vec<Value>>>()?;
+        let line = paren.get_line().unwrap_or(0);
+        match callee {
+            Value::Callable(callable) => self.call(&callable, args, line),
+            _ => Err(InterpreterError::RuntimeError {
+                line,
+                message: "Can only call functions".into(),
+            }),
+        }
+    }
+
+    fn visit_grouping(&mut self, expression: &Expr) -> InterpreterResult<Value> {
+        self.interpret_expr(expression)
+    }
+    fn visit_index(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+    ) -> InterpreterResult<Value> {
+        let object = self.interpret_expr(object)?;
+        let index = self.interpret_expr(index)?;
+        let line = bracket.get_line().unwrap_or(0);
+        let bytes = match object {
+            Value::Bytes(bytes) => bytes,
+            _ => {
+                return Err(InterpreterError::RuntimeError {
+                    line,
+                    message: "Can only index bytes".into(),
+                })
             }
+        };
+        let i = cast_f32(&index, &line)?;
+        if i.fract() != 0.0 || i < 0.0 || i as usize >= bytes.len() {
+            return Err(InterpreterError::RuntimeError {
+                line,
+                message: format!("Index {} out of range for {} bytes", index, bytes.len()),
+            });
+        }
+        Ok(Value::Number(f32::from(bytes[i as usize])))
+    }
+    fn visit_literal(&mut self, value: &Value) -> InterpreterResult<Value> {
+        Ok(value.clone())
+    }
+    fn visit_logical(
+        &mut self,
+        left: &Expr,
+        _operator: &Token,
+        right: &Expr,
+    ) -> InterpreterResult<Value> {
+        match self.interpret_expr(left)? {
+            Value::Nil => self.interpret_expr(right),
+            left => Ok(left),
+        }
+    }
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) -> InterpreterResult<Value> {
+        let right = self.interpret_expr(right)?;
+        match operator {
+            Token::Minus { line } => match right {
+                Value::BigInt(n) => Ok(Value::BigInt(Rc::new(-&*n))),
+                right => Ok(Value::Number(-cast_f32(&right, line)?)),
+            },
             Token::Bang { line } => {
                 let b = cast_bool(&right, line)?;
                 Ok(Value::Bool(!b))
             }
-            t => Err(InterpreterError::SyntaxError {
+            t => Err(InterpreterError::RuntimeError {
                 line: t.get_line().unwrap_or(0),
                 message: "Invalid unary expression".into(),
             }),
         }
     }
+    fn visit_variable(&mut self, id: NodeId, name: &Token) -> InterpreterResult<Value> {
+        // `this` is bound by the call, not by the declaration, so it is
+        // looked up through the environments rather than resolved.
+        if let Token::This { line } = name {
+            return self
+                .current_env()
+                .ok_or_else(|| InterpreterError::undefined_variable_error(String::from("this")))
+                .and_then(|env| env.borrow().get("this"))
+                .map_err(|e| e.add_line_to_undefined_error(*line));
+        }
+        self.get_variable(
+            &name.to_string(),
+            &name.get_line().unwrap_or(0),
+            self.depth(id),
+        )
+    }
+}
+
+fn sorted(values: &HashMap<String, Value>) -> Vec<(String, Value)> {
+    let mut values: Vec<(String, Value)> = values
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    values.sort_by(|a, b| a.0.cmp(&b.0));
+    values
+}
+
+fn bigint_binary(
+    left: &Value,
+    operator: &Token,
+    right: &Value,
+) -> InterpreterResult<Option<Value>> {
+    let arithmetic = matches!(
+        operator,
+        Token::Plus { .. }
+            | Token::Minus { .. }
+            | Token::Star { .. }
+            | Token::Slash { .. }
+            | Token::Greater { .. }
+            | Token::GreaterEqual { .. }
+            | Token::Less { .. }
+            | Token::LessEqual { .. }
+    );
+    if !arithmetic || !matches!((left, right), (Value::BigInt(_), _) | (_, Value::BigInt(_))) {
+        return Ok(None);
+    }
+    let line = operator.get_line().unwrap_or(0);
+    let left = cast_bigint(left, line)?;
+    let right = cast_bigint(right, line)?;
+    let big = |n: BigInt| Value::BigInt(Rc::new(n));
+    Ok(Some(match operator {
+        Token::Plus { .. } => big(left + right),
+        Token::Minus { .. } => big(left - right),
+        Token::Star { .. } => big(left * right),
+        Token::Slash { .. } if right.is_zero() => {
+            return Err(InterpreterError::RuntimeError {
+                line,
+                message: "Division by zero".into(),
+            })
+        }
+        Token::Slash { .. } => big(left / right),
+        Token::Greater { .. } => Value::Bool(left > right),
+        Token::GreaterEqual { .. } => Value::Bool(left >= right),
+        Token::Less { .. } => Value::Bool(left < right),
+        _ => Value::Bool(left <= right),
+    }))
+}
+
+fn cast_bigint(value: &Value, line: usize) -> InterpreterResult<BigInt> {
+    match value {
+        Value::BigInt(n) => Ok(BigInt::clone(n)),
+        Value::Number(n) => whole_number(*n).ok_or_else(|| {
+            InterpreterError::type_error("whole number".into(), value.to_string())
+                .add_line_to_type_error(line)
+        }),
+        _ => Err(
+            InterpreterError::type_error("bigint".into(), value.type_name().into())
+                .add_line_to_type_error(line),
+        ),
+    }
 }
 
 fn cast_f32(expr: &Value, line: &usize) -> InterpreterResult<f32> {
@@ -202,13 +841,14 @@ fn cast_bool(expr: &Value, line: &usize) -> InterpreterResult<bool> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast;
     #[test]
     fn interpreter_literal() -> InterpreterResult<()> {
         let interpreter = Interpreter::default();
         let e = Expr::literal_string("hello");
         assert_eq!(
             interpreter.interpret_expr(&e)?,
-            Value::r#String(String::from("hello"))
+            Value::r#String("hello".into())
         );
         let e = Expr::literal_num(3.0);
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Number(3.0));
@@ -221,34 +861,23 @@ mod tests {
     #[test]
     fn interpreter_grouping() -> InterpreterResult<()> {
         let interpreter = Interpreter::default();
-        let e = Expr::Grouping {
-            expression: Box::new(Expr::literal_num(3.0)),
-        };
+        let e = ast::grouping(Expr::literal_num(3.0));
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Number(3.0));
         Ok(())
     }
     #[test]
     fn interpreter_unary_ok() -> InterpreterResult<()> {
         let interpreter = Interpreter::default();
-        let e = Expr::Unary {
-            operator: Token::Minus { line: 1 },
-            right: Box::new(Expr::literal_num(3.0)),
-        };
+        let e = ast::unary(Token::Minus { line: 1 }, Expr::literal_num(3.0));
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Number(-3.0));
-        let e = Expr::Unary {
-            operator: Token::Bang { line: 1 },
-            right: Box::new(Expr::literal_bool(true)),
-        };
+        let e = ast::unary(Token::Bang { line: 1 }, Expr::literal_bool(true));
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(false));
         Ok(())
     }
     #[test]
     fn interpreter_unary_not_ok() {
         let interpreter = Interpreter::default();
-        let e = Expr::Unary {
-            operator: Token::Minus { line: 1 },
-            right: Box::new(Expr::literal_string("foo")),
-        };
+        let e = ast::unary(Token::Minus { line: 1 }, Expr::literal_string("foo"));
         if let Err(InterpreterError::Type {
             line,
             expected_type,
@@ -261,10 +890,7 @@ mod tests {
         } else {
             panic!("no error negating string")
         }
-        let e = Expr::Unary {
-            operator: Token::Bang { line: 1 },
-            right: Box::new(Expr::literal_string("foo")),
-        };
+        let e = ast::unary(Token::Bang { line: 1 }, Expr::literal_string("foo"));
         if let Err(InterpreterError::Type {
             line,
             expected_type,
@@ -281,72 +907,185 @@ mod tests {
     #[test]
     fn interpreter_binary_ok() -> InterpreterResult<()> {
         let interpreter = Interpreter::default();
-        let e = Expr::Binary {
-            operator: Token::Minus { line: 1 },
-            left: Box::new(Expr::literal_num(3.0)),
-            right: Box::new(Expr::literal_num(2.0)),
-        };
+        let e = ast::binary(
+            Expr::literal_num(3.0),
+            Token::Minus { line: 1 },
+            Expr::literal_num(2.0),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Number(1.0));
-        let e = Expr::Binary {
-            operator: Token::Slash { line: 1 },
-            left: Box::new(Expr::literal_num(4.0)),
-            right: Box::new(Expr::literal_num(2.0)),
-        };
+        let e = ast::binary(
+            Expr::literal_num(4.0),
+            Token::Slash { line: 1 },
+            Expr::literal_num(2.0),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Number(2.0));
-        let e = Expr::Binary {
-            operator: Token::Greater { line: 1 },
-            left: Box::new(Expr::literal_num(2.0)),
-            right: Box::new(Expr::literal_num(1.0)),
-        };
+        let e = ast::binary(
+            Expr::literal_num(2.0),
+            Token::Greater { line: 1 },
+            Expr::literal_num(1.0),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(true));
-        let e = Expr::Binary {
-            operator: Token::Less { line: 1 },
-            left: Box::new(Expr::literal_num(2.0)),
-            right: Box::new(Expr::literal_num(1.0)),
-        };
+        let e = ast::binary(
+            Expr::literal_num(2.0),
+            Token::Less { line: 1 },
+            Expr::literal_num(1.0),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(false));
-        let e = Expr::Binary {
-            operator: Token::GreaterEqual { line: 1 },
-            left: Box::new(Expr::literal_num(2.0)),
-            right: Box::new(Expr::literal_num(1.0)),
-        };
+        let e = ast::binary(
+            Expr::literal_num(2.0),
+            Token::GreaterEqual { line: 1 },
+            Expr::literal_num(1.0),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(true));
-        let e = Expr::Binary {
-            operator: Token::LessEqual { line: 1 },
-            left: Box::new(Expr::literal_num(2.0)),
-            right: Box::new(Expr::literal_num(1.0)),
-        };
+        let e = ast::binary(
+            Expr::literal_num(2.0),
+            Token::LessEqual { line: 1 },
+            Expr::literal_num(1.0),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(false));
         Ok(())
     }
     #[test]
     fn interpreter_binary_plus_ok() -> InterpreterResult<()> {
         let interpreter = Interpreter::default();
-        let e = Expr::Binary {
-            operator: Token::Plus { line: 1 },
-            left: Box::new(Expr::literal_num(1.0)),
-            right: Box::new(Expr::literal_num(1.0)),
-        };
+        let e = ast::binary(
+            Expr::literal_num(1.0),
+            Token::Plus { line: 1 },
+            Expr::literal_num(1.0),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Number(2.0));
-        let e = Expr::Binary {
-            operator: Token::Plus { line: 1 },
-            left: Box::new(Expr::literal_string("hello")),
-            right: Box::new(Expr::literal_string(" there")),
-        };
+        let e = ast::binary(
+            Expr::literal_string("hello"),
+            Token::Plus { line: 1 },
+            Expr::literal_string(" there"),
+        );
         assert_eq!(
             interpreter.interpret_expr(&e)?,
-            Value::r#String(String::from("hello there"))
+            Value::r#String("hello there".into())
         );
         Ok(())
     }
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn interpreter_tracing() -> InterpreterResult<()> {
+        use std::sync::{Arc, Mutex};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+        #[derive(Clone, Default)]
+        struct Names(Arc<Mutex<Vec<String>>>);
+        impl Subscriber for Names {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut names = self.0.lock().unwrap();
+                names.push(format!("span {}", span.metadata().name()));
+                Id::from_u64(names.len() as u64)
+            }
+            fn record(&self, _span: &Id, _values: &Record<'_>) {}
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut message = String::default();
+                event.record(
+                    &mut |field: &tracing::field::Field, value: &dyn fmt::Debug| {
+                        if field.name() == "message" {
+                            message = format!("{:?}", value);
+                        }
+                    },
+                );
+                self.0.lock().unwrap().push(format!("event {}", message));
+            }
+            fn enter(&self, _span: &Id) {}
+            fn exit(&self, _span: &Id) {}
+        }
+        let names = Names::default();
+        let (stmts, _) = tracing::subscriber::with_default(names.clone(), || {
+            let tokens = crate::scanner::scan_tokens(String::from("fun f() { return 1; } f();"))?;
+            InterpreterResult::Ok(crate::parser::parse(tokens))
+        })?;
+        tracing::subscriber::with_default(names.clone(), || {
+            Interpreter::default().interpret_all(&stmts)
+        })?;
+        assert_eq!(
+            *names.0.lock().unwrap(),
+            vec![
+                "span scan",
+                "span parse",
+                "span interpret",
+                "span resolve",
+                "event call",
+                "span call",
+                "event return",
+            ]
+        );
+        Ok(())
+    }
+    #[test]
+    fn interpreter_string_concat_loop() -> InterpreterResult<()> {
+        let (stmts, _) = crate::parser::parse(crate::scanner::scan_tokens(String::from(
+            "var s = \"\"; var t = s; var i = 0; while (i < 20000) { s = s + \"x\"; i = i + 1; }",
+        ))?);
+        let interpreter = Interpreter::default();
+        interpreter.interpret_all(&stmts)?;
+        match (interpreter.lookup("s"), interpreter.lookup("t")) {
+            (Some(Value::r#String(s)), Some(Value::r#String(t))) => {
+                assert_eq!(s.len(), 20000);
+                assert!(s.shares_buffer(&t));
+                assert_eq!(t, LoxString::from(""));
+            }
+            other => panic!("expected strings, got {:?}", other),
+        }
+        Ok(())
+    }
+    #[test]
+    fn interpreter_string_repeat() -> InterpreterResult<()> {
+        let interpreter = Interpreter::default();
+        let e = ast::binary(ast::string("ab"), Token::Star { line: 1 }, ast::num(3.0));
+        assert_eq!(
+            interpreter.interpret_expr(&e)?,
+            Value::r#String("ababab".into())
+        );
+        let e = ast::binary(ast::num(3.0), Token::Star { line: 1 }, ast::string("ab"));
+        assert_eq!(
+            interpreter.interpret_expr(&e)?,
+            Value::r#String("ababab".into())
+        );
+        let e = ast::binary(ast::string("ab"), Token::Star { line: 1 }, ast::num(0.0));
+        assert_eq!(interpreter.interpret_expr(&e)?, Value::r#String("".into()));
+        let e = ast::binary(ast::string("ab"), Token::Star { line: 2 }, ast::num(-1.0));
+        assert!(matches!(
+            interpreter.interpret_expr(&e),
+            Err(InterpreterError::Type { line: Some(2), .. })
+        ));
+        let e = ast::binary(
+            ast::string("ab"),
+            Token::Star { line: 2 },
+            ast::string("ab"),
+        );
+        assert!(matches!(
+            interpreter.interpret_expr(&e),
+            Err(InterpreterError::Type { line: Some(2), .. })
+        ));
+        for count in [1e11, 1e30] {
+            let e = ast::binary(ast::string("ab"), Token::Star { line: 3 }, ast::num(count));
+            assert!(matches!(
+                interpreter.interpret_expr(&e),
+                Err(InterpreterError::LimitExceeded {
+                    limit: "string byte",
+                    ..
+                })
+            ));
+        }
+        Ok(())
+    }
     #[test]
     fn interpreter_binary_not_ok() {
         let interpreter = Interpreter::default();
-        let e = Expr::Binary {
-            operator: Token::Minus { line: 1 },
-            left: Box::new(Expr::literal_num(3.0)),
-            right: Box::new(Expr::literal_string("hello")),
-        };
+        let e = ast::binary(
+            Expr::literal_num(3.0),
+            Token::Minus { line: 1 },
+            Expr::literal_string("hello"),
+        );
         if let Err(InterpreterError::Type {
             line,
             actual_type,
@@ -359,11 +1098,11 @@ mod tests {
         } else {
             panic!("no error subtracting string from number");
         }
-        let e = Expr::Binary {
-            operator: Token::Slash { line: 1 },
-            left: Box::new(Expr::literal_num(3.0)),
-            right: Box::new(Expr::literal_string("hello")),
-        };
+        let e = ast::binary(
+            Expr::literal_num(3.0),
+            Token::Slash { line: 1 },
+            Expr::literal_string("hello"),
+        );
         if let Err(InterpreterError::Type {
             line,
             actual_type,
@@ -376,11 +1115,11 @@ mod tests {
         } else {
             panic!("no error dividing number by string");
         }
-        let e = Expr::Binary {
-            operator: Token::Star { line: 1 },
-            left: Box::new(Expr::literal_num(3.0)),
-            right: Box::new(Expr::literal_string("hello")),
-        };
+        let e = ast::binary(
+            Expr::literal_num(3.5),
+            Token::Star { line: 1 },
+            Expr::literal_string("hello"),
+        );
         if let Err(InterpreterError::Type {
             line,
             actual_type,
@@ -388,16 +1127,33 @@ mod tests {
         }) = interpreter.interpret_expr(&e)
         {
             assert_eq!(Some(1), line);
-            assert_eq!(String::from("number"), expected_type);
-            assert_eq!(String::from("string"), actual_type);
+            assert_eq!(String::from("whole number"), expected_type);
+            assert_eq!(String::from("3.5"), actual_type);
         } else {
-            panic!("no error multiplying number by string");
+            panic!("no error multiplying string by a fraction");
         }
-        let e = Expr::Binary {
-            operator: Token::Greater { line: 1 },
-            left: Box::new(Expr::literal_num(3.0)),
-            right: Box::new(Expr::literal_string("hello")),
-        };
+        let e = ast::binary(
+            Expr::literal_bool(true),
+            Token::Plus { line: 1 },
+            Expr::literal_string("hello"),
+        );
+        if let Err(InterpreterError::Type {
+            line,
+            actual_type,
+            expected_type,
+        }) = interpreter.interpret_expr(&e)
+        {
+            assert_eq!(Some(1), line);
+            assert_eq!(String::from("number or string"), expected_type);
+            assert_eq!(String::from("bool"), actual_type);
+        } else {
+            panic!("no error adding a string to a bool");
+        }
+        let e = ast::binary(
+            Expr::literal_num(3.0),
+            Token::Greater { line: 1 },
+            Expr::literal_string("hello"),
+        );
         if let Err(InterpreterError::Type {
             line,
             actual_type,
@@ -410,11 +1166,11 @@ mod tests {
         } else {
             panic!("no error comparing number gt string");
         }
-        let e = Expr::Binary {
-            operator: Token::Less { line: 1 },
-            left: Box::new(Expr::literal_num(3.0)),
-            right: Box::new(Expr::literal_string("hello")),
-        };
+        let e = ast::binary(
+            Expr::literal_num(3.0),
+            Token::Less { line: 1 },
+            Expr::literal_string("hello"),
+        );
         if let Err(InterpreterError::Type {
             line,
             actual_type,
@@ -427,11 +1183,11 @@ mod tests {
         } else {
             panic!("no error comparing number lt string");
         }
-        let e = Expr::Binary {
-            operator: Token::GreaterEqual { line: 1 },
-            left: Box::new(Expr::literal_num(3.0)),
-            right: Box::new(Expr::literal_string("hello")),
-        };
+        let e = ast::binary(
+            Expr::literal_num(3.0),
+            Token::GreaterEqual { line: 1 },
+            Expr::literal_string("hello"),
+        );
         if let Err(InterpreterError::Type {
             line,
             actual_type,
@@ -444,11 +1200,11 @@ mod tests {
         } else {
             panic!("no error comparing number gte string");
         }
-        let e = Expr::Binary {
-            operator: Token::LessEqual { line: 1 },
-            left: Box::new(Expr::literal_num(3.0)),
-            right: Box::new(Expr::literal_string("hello")),
-        };
+        let e = ast::binary(
+            Expr::literal_num(3.0),
+            Token::LessEqual { line: 1 },
+            Expr::literal_string("hello"),
+        );
         if let Err(InterpreterError::Type {
             line,
             actual_type,
@@ -466,142 +1222,230 @@ mod tests {
     #[test]
     fn interpreter_binary_eq_same_type() -> InterpreterResult<()> {
         let interpreter = Interpreter::default();
-        let e = Expr::Binary {
-            operator: Token::EqualEqual { line: 1 },
-            left: Box::new(Expr::literal_num(1.0)),
-            right: Box::new(Expr::literal_num(1.0)),
-        };
+        let e = ast::binary(
+            Expr::literal_num(1.0),
+            Token::EqualEqual { line: 1 },
+            Expr::literal_num(1.0),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(true));
-        let e = Expr::Binary {
-            operator: Token::EqualEqual { line: 1 },
-            left: Box::new(Expr::literal_num(1.0)),
-            right: Box::new(Expr::literal_num(2.0)),
-        };
+        let e = ast::binary(
+            Expr::literal_num(1.0),
+            Token::EqualEqual { line: 1 },
+            Expr::literal_num(2.0),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(false));
-        let e = Expr::Binary {
-            operator: Token::EqualEqual { line: 1 },
-            left: Box::new(Expr::literal_string("hi")),
-            right: Box::new(Expr::literal_string("hi")),
-        };
+        let e = ast::binary(
+            Expr::literal_string("hi"),
+            Token::EqualEqual { line: 1 },
+            Expr::literal_string("hi"),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(true));
-        let e = Expr::Binary {
-            operator: Token::EqualEqual { line: 1 },
-            left: Box::new(Expr::literal_string("hi")),
-            right: Box::new(Expr::literal_string("bye")),
-        };
+        let e = ast::binary(
+            Expr::literal_string("hi"),
+            Token::EqualEqual { line: 1 },
+            Expr::literal_string("bye"),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(false));
-        let e = Expr::Binary {
-            operator: Token::EqualEqual { line: 1 },
-            left: Box::new(Expr::literal_bool(true)),
-            right: Box::new(Expr::literal_bool(true)),
-        };
+        let e = ast::binary(
+            Expr::literal_bool(true),
+            Token::EqualEqual { line: 1 },
+            Expr::literal_bool(true),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(true));
-        let e = Expr::Binary {
-            operator: Token::EqualEqual { line: 1 },
-            left: Box::new(Expr::literal_bool(true)),
-            right: Box::new(Expr::literal_bool(false)),
-        };
+        let e = ast::binary(
+            Expr::literal_bool(true),
+            Token::EqualEqual { line: 1 },
+            Expr::literal_bool(false),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(false));
-        let e = Expr::Binary {
-            operator: Token::EqualEqual { line: 1 },
-            left: Box::new(Expr::literal_nil()),
-            right: Box::new(Expr::literal_nil()),
-        };
+        let e = ast::binary(
+            Expr::literal_nil(),
+            Token::EqualEqual { line: 1 },
+            Expr::literal_nil(),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(true));
         Ok(())
     }
     #[test]
     fn interpreter_binary_eq_different_types() -> InterpreterResult<()> {
         let interpreter = Interpreter::default();
-        let e = Expr::Binary {
-            operator: Token::EqualEqual { line: 1 },
-            left: Box::new(Expr::literal_num(1.0)),
-            right: Box::new(Expr::literal_string("1.0")),
-        };
+        let e = ast::binary(
+            Expr::literal_num(1.0),
+            Token::EqualEqual { line: 1 },
+            Expr::literal_string("1.0"),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(false));
-        let e = Expr::Binary {
-            operator: Token::EqualEqual { line: 1 },
-            left: Box::new(Expr::literal_string("true")),
-            right: Box::new(Expr::literal_bool(true)),
-        };
+        let e = ast::binary(
+            Expr::literal_string("true"),
+            Token::EqualEqual { line: 1 },
+            Expr::literal_bool(true),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(false));
-        let e = Expr::Binary {
-            operator: Token::EqualEqual { line: 1 },
-            left: Box::new(Expr::literal_bool(false)),
-            right: Box::new(Expr::literal_nil()),
-        };
+        let e = ast::binary(
+            Expr::literal_bool(false),
+            Token::EqualEqual { line: 1 },
+            Expr::literal_nil(),
+        );
         assert_eq!(interpreter.interpret_expr(&e)?, Value::Bool(false));
         Ok(())
     }
     #[test]
     fn interpreter_define_variable_initializer() -> InterpreterResult<()> {
         let interpreter = Interpreter::default();
-        let s = Stmt::Variable {
-            name: Token::Identifier {
-                literal: String::from("foo"),
-                lexeme: String::from("foo"),
-                line: 0,
-            },
-            initializer: Some(Box::new(Expr::literal_num(3.0))),
-        };
+        let s = ast::var("foo", Some(ast::num(3.0)));
         interpreter.interpret(&s)?;
-        assert_eq!(interpreter.get_variable("foo", &0)?, Value::Number(3.0));
+        assert_eq!(
+            interpreter.get_variable("foo", &0, None)?,
+            Value::Number(3.0)
+        );
         Ok(())
     }
     #[test]
     fn interpreter_define_variable_no_initializer() -> InterpreterResult<()> {
         let interpreter = Interpreter::default();
-        let s = Stmt::Variable {
-            name: Token::Identifier {
-                literal: String::from("foo"),
-                lexeme: String::from("foo"),
-                line: 0,
-            },
-            initializer: None,
-        };
+        let s = ast::var("foo", None);
         interpreter.interpret(&s)?;
-        assert_eq!(interpreter.get_variable("foo", &0)?, Value::Nil);
+        assert_eq!(interpreter.get_variable("foo", &0, None)?, Value::Nil);
         Ok(())
     }
     #[test]
     fn interpreter_assign_ok() -> InterpreterResult<()> {
         let interpreter = Interpreter::default();
-        let s = Stmt::Expr {
-            expr: Box::new(Expr::Assign {
-                name: Token::Identifier {
-                    line: 0,
-                    literal: String::from("foo"),
-                    lexeme: String::from("foo"),
-                },
-                value: Box::new(Expr::literal_num(3.0)),
-            }),
-        };
-        interpreter
-            .env
-            .borrow_mut()
-            .define("foo".into(), (2.0).try_into().unwrap());
+        let s = ast::expression(ast::assign("foo", ast::num(3.0)));
+        interpreter.define("foo", (2.0).try_into().unwrap());
         assert_eq!(interpreter.interpret(&s)?, Value::Number(3.0));
-        assert_eq!(interpreter.get_variable("foo", &0)?, Value::Number(3.0));
+        assert_eq!(
+            interpreter.get_variable("foo", &0, None)?,
+            Value::Number(3.0)
+        );
         Ok(())
     }
     #[test]
     fn interpreter_assign_err() {
         let interpreter = Interpreter::default();
-        let s = Stmt::Expr {
-            expr: Box::new(Expr::Assign {
-                name: Token::Identifier {
-                    line: 0,
-                    literal: String::from("foo"),
-                    lexeme: String::from("foo"),
-                },
-                value: Box::new(Expr::literal_num(3.0)),
-            }),
-        };
+        let s = ast::expression(ast::assign("foo", ast::num(3.0)));
         assert!(matches!(
             interpreter.interpret(&s),
             Err(InterpreterError::UndefinedVariable { .. })
         ));
     }
+    #[test]
+    fn interpreter_define() -> InterpreterResult<()> {
+        let interpreter = Interpreter::default();
+        interpreter.define("_", Value::Number(3.0));
+        assert_eq!(interpreter.get_variable("_", &0, None)?, Value::Number(3.0));
+        interpreter.define("_", Value::Nil);
+        assert_eq!(interpreter.get_variable("_", &0, None)?, Value::Nil);
+        Ok(())
+    }
+    #[test]
+    fn interpreter_read_line() -> InterpreterResult<()> {
+        let mut interpreter = Interpreter::default();
+        interpreter.set_input(Box::new(io::Cursor::new("first\nsecond\r\n")));
+        let call = Expr::Call {
+            id: NodeId::next(),
+            callee: Box::new(Expr::Variable {
+                name: Token::Identifier {
+                    lexeme: String::from("readLine"),
+                    literal: String::from("readLine"),
+                    line: 1,
+                },
+                id: NodeId::next(),
+            }),
+            paren: Token::RightParen { line: 1 },
+            arguments: vec![],
+        };
+        assert_eq!(
+            interpreter.interpret_expr(&call)?,
+            Value::r#String("first".into())
+        );
+        assert_eq!(
+            interpreter.interpret_expr(&call)?,
+            Value::r#String("second".into())
+        );
+        assert_eq!(interpreter.interpret_expr(&call)?, Value::Nil);
+        Ok(())
+    }
+    #[test]
+    fn interpreter_snapshot_restore() -> InterpreterResult<()> {
+        let interpreter = Interpreter::default();
+        interpreter.define("foo", Value::Number(1.0));
+        let snapshot = interpreter.snapshot();
+        interpreter.define("foo", Value::Number(2.0));
+        interpreter.define("bar", Value::Nil);
+        interpreter.restore(snapshot);
+        assert_eq!(
+            interpreter.get_variable("foo", &0, None)?,
+            Value::Number(1.0)
+        );
+        assert!(interpreter.get_variable("bar", &0, None).is_err());
+        interpreter.reset();
+        assert!(interpreter.get_variable("foo", &0, None).is_err());
+        Ok(())
+    }
+    #[test]
+    fn interpreter_globals() -> InterpreterResult<()> {
+        let interpreter = Interpreter::default();
+        let (stmts, _) = crate::parser::parse(crate::scanner::scan_tokens(String::from(
+            "var a = 1; { var b = 2; fun f() { a = a + b; } f(); }",
+        ))?);
+        interpreter.interpret_all(&stmts)?;
+        assert_eq!(interpreter.globals().get("a")?, Value::Number(3.0));
+        assert!(interpreter.globals().get("b").is_err());
+        assert!(interpreter.globals().get("f").is_err());
+        assert_eq!(interpreter.lookup("a"), Some(Value::Number(3.0)));
+        assert_eq!(interpreter.scopes().len(), 1);
+        Ok(())
+    }
+    #[test]
+    fn interpreter_resolve_reports_errors() -> InterpreterResult<()> {
+        let interpreter = Interpreter::default();
+        let (stmts, _) =
+            crate::parser::parse(crate::scanner::scan_tokens(String::from("{ var a = a; }"))?);
+        let errs = interpreter.resolve(&stmts).unwrap_err();
+        assert_eq!(errs.len(), 1);
+        assert!(errs[0].is_static());
+        assert!(interpreter.interpret_all(&stmts).is_err());
+        Ok(())
+    }
+    #[test]
+    fn interpreter_locals_are_scoped_to_programs() -> InterpreterResult<()> {
+        let interpreter = Interpreter::default();
+        let run = |source: &str| {
+            let (stmts, _) =
+                crate::parser::parse(crate::scanner::scan_tokens(String::from(source))?);
+            interpreter.interpret_all(&stmts)
+        };
+        run("fun f(n) { var m = n + 1; { var k = m; return k; } }")?;
+        for _ in 0..3 {
+            run("{ var a = 1; { var b = a; a = b + f(a); } }")?;
+            assert!(interpreter.locals.borrow().is_empty());
+        }
+        assert_eq!(run("f(1);")?, Some(Value::Number(2.0)));
+        let table = match interpreter.lookup("f") {
+            Some(Value::Callable(Callable::Lox(f))) => Rc::downgrade(&f.locals),
+            other => panic!("{:?}", other),
+        };
+        run("fun f() {}")?;
+        assert!(table.upgrade().is_none());
+        Ok(())
+    }
+    #[test]
+    fn interpreter_execute_block_restores_env() -> InterpreterResult<()> {
+        let interpreter = Interpreter::default();
+        let run = |source: &str| {
+            let (stmts, _) =
+                crate::parser::parse(crate::scanner::scan_tokens(String::from(source))?);
+            interpreter.interpret_all(&stmts)
+        };
+        run("fun f() { { { return 1; } } } var x = f();")?;
+        assert_eq!(interpreter.scopes().len(), 1);
+        assert!(run("{ var a = 1; { print nope; } }").is_err());
+        assert_eq!(interpreter.scopes().len(), 1);
+        run("var a = 2;")?;
+        assert_eq!(interpreter.globals().get("a")?, Value::Number(2.0));
+        Ok(())
+    }
     #[cfg(unix)]
     #[test]
     #[ignore]
@@ -611,31 +1455,11 @@ mod tests {
         use std::io::Read;
         let v_name = "foo";
         let interpreter = Interpreter::default();
-        let s = Stmt::Block {
-            stmts: vec![
-                Stmt::Variable {
-                    name: Token::Identifier {
-                        literal: String::from(v_name),
-                        lexeme: String::from(v_name),
-                        line: 0,
-                    },
-                    initializer: Some(Box::new(Expr::literal_num(2.0))),
-                },
-                Stmt::Print {
-                    expr: Box::new(Expr::Variable {
-                        name: Token::Identifier {
-                            literal: String::from(v_name),
-                            lexeme: String::from(v_name),
-                            line: 0,
-                        },
-                    }),
-                },
-            ],
-        };
-        interpreter
-            .env
-            .borrow_mut()
-            .define(String::from(v_name), Value::Number(3.0));
+        let s = ast::block(vec![
+            ast::var(v_name, Some(ast::num(2.0))),
+            ast::print(ast::variable(v_name)),
+        ]);
+        interpreter.define(v_name, Value::Number(3.0));
         let mut output = String::default();
         {
             let mut buf = BufferRedirect::stdout().unwrap();
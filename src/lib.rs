@@ -1,73 +1,281 @@
+pub mod ast;
+mod bench;
+mod cancel;
+mod capabilities;
+mod class;
+mod cli;
+mod compile;
+mod config;
+mod coverage;
+mod cst;
+mod debugger;
+mod doc;
+mod document;
+#[cfg(feature = "dlopen")]
+mod dylib;
 mod environment;
 pub mod errors;
 mod expr;
 mod expr_printer;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod formatter;
+mod function;
+mod hooks;
 mod interpreter;
+mod limits;
+mod lox;
+mod macros;
+mod natives;
+mod node;
 mod parser;
+mod plugin;
+mod prelude;
 mod prompt;
+mod repl;
+mod resolver;
 mod scanner;
+mod source;
+#[cfg(feature = "serde")]
+mod state;
+mod stats;
 mod stmt;
+mod stmt_printer;
+mod string;
+mod suggest;
+mod test_runner;
+mod threads;
+mod to_source;
 mod token;
 mod value;
+mod worker;
 
+pub use crate::bench::CountingAllocator;
+pub use crate::cancel::CancelToken;
+pub use crate::capabilities::{Capabilities, Capability};
+use crate::cli::{FormatOutput, Mode, RunOptions};
+use crate::config::Config;
+use crate::coverage::Coverage;
+use crate::cst::Cst;
+use crate::debugger::Debugger;
+pub use crate::document::Document;
+use crate::environment::Globals;
 pub use crate::errors::{InterpreterError, InterpreterResult};
+pub use crate::expr::Expr;
+pub use crate::formatter::format_source;
+pub use crate::function::Callable;
+pub use crate::hooks::{HookContext, InterpreterHooks};
 use crate::interpreter::Interpreter;
-use crate::scanner::scan_tokens;
+pub use crate::limits::Limits;
+pub use crate::lox::{Function, Lox};
+pub use crate::plugin::{PluginFn, PluginRegistrar, PLUGIN_API_VERSION};
+pub use crate::prelude::Prelude;
+use crate::repl::{Command, Timings};
+use crate::scanner::{scan_limited, scan_tokens, TokenStream};
+#[doc(hidden)]
+pub use crate::source::__lox_program;
+pub use crate::source::{eval_source, parse_source, Diagnostic, Program};
+pub use crate::stats::Stats;
+pub use crate::stmt::Stmt;
+pub use crate::string::LoxString;
+pub use crate::to_source::ToSource;
+pub use crate::token::{Token, KEYWORDS};
+pub use crate::value::Value;
+pub use crate::worker::LoxWorker;
 use rustyline::error::ReadlineError;
+use std::collections::HashMap;
 use std::env;
-use std::fs::File;
-use std::io::Read;
+use std::io;
+use std::time::Instant;
 
 pub fn main() -> InterpreterResult<()> {
-    let mut args = env::args();
-    let mut runner = Runner::default();
-    if args.len() > 2 {
-        Err(InterpreterError::Usage)
-    } else if let Some(fname) = args.nth(1) {
-        runner.run_file(fname)
-    } else {
-        runner.prompt()
+    let mut runner = Runner::new();
+    match cli::parse_args(env::args().skip(1))? {
+        Mode::Prompt { options } => runner.with_options(options, |r| {
+            r.load_rc();
+            r.prompt()
+        }),
+        Mode::File { fname, options } => runner.with_options(options, |r| r.run_file(fname)),
+        Mode::Interactive { fname, options } => runner.with_options(options, |r| {
+            r.load_rc();
+            recover(r.run_file(fname))?;
+            r.prompt()
+        }),
+        Mode::Eval { source, options } => {
+            runner.with_options(options, |r| r.run(source).map(|_| ()))
+        }
+        Mode::Tokens { fname } => runner.dump_tokens(&fname),
+        Mode::Ast { fname } => runner.dump_ast(&fname),
+        Mode::Check {
+            fname,
+            strict,
+            prelude,
+        } => {
+            let options = Config::load()?.apply(RunOptions {
+                strict,
+                prelude,
+                ..RunOptions::default()
+            });
+            check(
+                read_file(&fname)?,
+                options.strict.unwrap_or(false),
+                options.prelude.unwrap_or(true),
+                &options.limits,
+            )
+        }
+        Mode::Debug { fname } => runner.debug_file(fname),
+        Mode::Coverage { fname, out } => runner.coverage_file(&fname, &out),
+        Mode::Test { path } => test_runner::run_tests(&path),
+        Mode::Doc { fname } => print_docs(&fname),
+        Mode::Bench { fname, iters } => {
+            println!("{}", bench::bench(&read_file(&fname)?, iters)?);
+            Ok(())
+        }
+        Mode::Compile { fname, out } => compile_file(&fname, &out),
+        Mode::Format { fname, output } => format_file(&fname, output),
     }
 }
 
+const LAST_RESULT: &str = "_";
+
 #[derive(Default)]
 pub struct Runner {
     interpreter: Interpreter,
+    timing: bool,
+    snapshots: HashMap<String, Globals>,
+    journal: Vec<String>,
+    latin1: bool,
+    prelude: Option<Prelude>,
 }
 
 impl Runner {
-    fn run(&self, s: String) -> InterpreterResult<()> {
-        let tokens = scan_tokens(s)?;
-        let (expr, errs) = parser::parse(tokens);
-        if let Some(ref res) = expr {
-            println!("{}", self.interpreter.interpret(res)?);
+    fn new() -> Self {
+        let runner = Self {
+            prelude: Some(Prelude::standard()),
+            ..Self::default()
+        };
+        runner.reset();
+        runner
+    }
+    // Back to the natives plus the prelude, if one is loaded.
+    fn reset(&self) {
+        match &self.prelude {
+            Some(prelude) => prelude.load(&self.interpreter),
+            None => self.interpreter.reset(),
+        }
+    }
+    fn with_options<F>(&mut self, options: RunOptions, f: F) -> InterpreterResult<()>
+    where
+        F: FnOnce(&mut Self) -> InterpreterResult<()>,
+    {
+        let options = Config::load()?.apply(options);
+        self.interpreter.set_strict(options.strict.unwrap_or(false));
+        self.interpreter.set_limits(options.limits);
+        self.interpreter.set_capabilities(options.capabilities);
+        self.latin1 = options.latin1.unwrap_or(false);
+        if options.prelude == Some(false) && self.prelude.take().is_some() {
+            self.reset();
+        }
+        #[cfg(feature = "dlopen")]
+        for path in options.plugins.iter() {
+            plugin::load(&mut self.interpreter, path)?;
+        }
+        let res = f(self);
+        if options.stats.unwrap_or(false) {
+            println!("{}", self.interpreter.stats());
+        }
+        res
+    }
+    fn run(&self, s: String) -> InterpreterResult<Option<Value>> {
+        let mut timings = Timings::default();
+        let start = Instant::now();
+        let (tokens, errs) = scan_limited(&s, self.interpreter.limits());
+        timings.scan = start.elapsed();
+        report_errors(errs)?;
+        let start = Instant::now();
+        let (stmts, errs) = parser::parse_with(tokens, self.interpreter.limits().nesting());
+        timings.parse = start.elapsed();
+        report_errors(errs)?;
+        let locals = match self.interpreter.resolve(&stmts) {
+            Ok(locals) => locals,
+            Err(errs) => return report_errors(errs).map(|()| None),
+        };
+        let start = Instant::now();
+        let last = self.interpreter.execute(&stmts, locals)?;
+        timings.interpret = start.elapsed();
+        if self.timing {
+            println!("{}", timings);
+        }
+        Ok(last)
+    }
+    fn run_file(&mut self, fname: String) -> InterpreterResult<()> {
+        let bytes = std::fs::read(&fname)?;
+        if compile::is_compiled(&bytes) {
+            self.interpreter.interpret_all(&compile::load(&bytes)?)?;
             Ok(())
         } else {
-            let mut e = InterpreterError::Unknown;
-            for err in errs.into_iter() {
-                println!("{}", &err);
-                e = err;
-            }
-            Err(e)
+            self.run(source::decode(&fname, bytes, self.latin1)?)
+                .map(|_| ())
         }
     }
-    fn run_file(&mut self, fname: String) -> InterpreterResult<()> {
-        let mut f = File::open(fname)?;
-        let mut s = String::default();
-        f.read_to_string(&mut s)?;
-        self.run(s)
+    fn debug_file(&mut self, fname: String) -> InterpreterResult<()> {
+        let debugger = Debugger::new(Box::new(io::stdin().lock()), Box::new(io::stdout()));
+        self.interpreter.add_hook(Box::new(debugger));
+        self.run_file(fname)
+    }
+    fn coverage_file(&mut self, fname: &str, out: &str) -> InterpreterResult<()> {
+        let (stmts, errs) = parser::parse_stream(TokenStream::new(&read_file(fname)?));
+        report_errors(errs)?;
+        let coverage = Coverage::new(&stmts);
+        self.interpreter.add_hook(Box::new(coverage.clone()));
+        let res = self.interpreter.interpret_all(&stmts);
+        println!("{}", coverage.summary(fname));
+        std::fs::write(out, coverage.lcov(fname)?)?;
+        res.map(|_| ())
+    }
+    fn dump_ast(&self, fname: &str) -> InterpreterResult<()> {
+        print_ast(read_file(fname)?)
+    }
+    fn dump_tokens(&self, fname: &str) -> InterpreterResult<()> {
+        for token in scan_tokens(read_file(fname)?)? {
+            println!("{}", token.dump());
+        }
+        Ok(())
+    }
+    // Problems in the rc file are reported but never stop the REPL.
+    fn load_rc(&mut self) {
+        let Some(path) = repl::rc_path() else {
+            return;
+        };
+        let rc = match std::fs::read_to_string(&path) {
+            Ok(rc) => rc,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+            Err(err) => return println!("Couldn't read {}: {}", path.display(), err),
+        };
+        let (source, commands) = repl::split_rc(&rc);
+        let res = self.run(source).map(|_| ());
+        for res in std::iter::once(res).chain(commands.into_iter().map(|cmd| self.command(cmd))) {
+            if let Err(err) = res {
+                if !err.is_static() {
+                    println!("{}", err);
+                }
+                println!("Error in {}", path.display());
+            }
+        }
     }
     fn prompt(&mut self) -> InterpreterResult<()> {
         let prompt = prompt::Prompt::new(">> ");
+        let cancel = self.interpreter.cancel_token();
+        _ = ctrlc::set_handler(move || cancel.cancel());
         for line in prompt {
             match line {
-                Ok(l) => match self.run(l) {
-                    Ok(_) => continue,
-                    Err(err @ InterpreterError::Interpreter { .. }) => {
-                        println!("{:?}", err);
-                    }
-                    Err(e) => return Err(e),
-                },
+                Ok(l) => {
+                    let res = match Command::parse(&l) {
+                        Some(cmd) => self.command(cmd),
+                        None => self.eval(l),
+                    };
+                    recover(res)?
+                }
                 Err(ReadlineError::Interrupted) => {
                     println!("Ctrl-C");
                 }
@@ -80,4 +288,254 @@ impl Runner {
         }
         Ok(())
     }
+    fn eval(&mut self, l: String) -> InterpreterResult<()> {
+        let last = self.run(l.clone())?;
+        self.journal.push(l);
+        if let Some(val) = last {
+            println!("{}", self.interpreter.stringify(&val, 0)?);
+            self.interpreter.define(LAST_RESULT, val);
+        }
+        Ok(())
+    }
+    fn command(&mut self, cmd: Command) -> InterpreterResult<()> {
+        match cmd {
+            Command::Time { source: None } => {
+                self.timing = !self.timing;
+                println!("Timing {}", if self.timing { "on" } else { "off" });
+                Ok(())
+            }
+            Command::Time {
+                source: Some(source),
+            } => {
+                let timing = std::mem::replace(&mut self.timing, true);
+                let res = self.eval(source);
+                self.timing = timing;
+                res
+            }
+            Command::Ast { source } => match print_ast(source) {
+                Err(InterpreterError::Parse { .. } | InterpreterError::SyntaxError { .. }) => {
+                    Ok(())
+                }
+                res => res,
+            },
+            Command::Reset => {
+                self.reset();
+                self.journal.clear();
+                println!("Environment reset");
+                Ok(())
+            }
+            Command::Save { name } => {
+                self.snapshots
+                    .insert(name.clone(), self.interpreter.snapshot());
+                println!("Saved {}", name);
+                Ok(())
+            }
+            Command::Restore { name } => {
+                match self.snapshots.get(&name) {
+                    Some(env) => {
+                        self.interpreter.restore(env.clone());
+                        println!("Restored {}", name);
+                    }
+                    None => println!("No snapshot named {}", name),
+                }
+                Ok(())
+            }
+            Command::SessionSave { name } => {
+                let path = repl::session_path(&name);
+                match std::fs::write(&path, repl::session_source(&self.journal)) {
+                    Ok(()) => println!("Saved session to {}", path.display()),
+                    Err(err) => println!("Couldn't save to {}: {}", path.display(), err),
+                }
+                Ok(())
+            }
+            Command::SessionLoad { name } => {
+                let path = repl::session_path(&name);
+                match std::fs::read_to_string(&path) {
+                    Ok(source) => {
+                        self.eval(source)?;
+                        println!("Loaded session from {}", path.display());
+                    }
+                    Err(err) => println!("Couldn't load {}: {}", path.display(), err),
+                }
+                Ok(())
+            }
+            Command::Dump { path } => {
+                if let Err(err) = dump_state(&self.interpreter, &path) {
+                    println!("Couldn't dump to {}: {}", path, err);
+                }
+                Ok(())
+            }
+            Command::Load { path } => {
+                if let Err(err) = load_state(&self.interpreter, &path) {
+                    println!("Couldn't load {}: {}", path, err);
+                }
+                Ok(())
+            }
+            Command::Doc { name } => {
+                println!(
+                    "{}",
+                    repl::describe(&name, self.interpreter.lookup(&name).as_ref())
+                );
+                Ok(())
+            }
+            Command::Tokens { source } => {
+                for token in scan_tokens(source)? {
+                    println!("{}", token.dump());
+                }
+                Ok(())
+            }
+            Command::Usage { usage } => {
+                println!("Usage: {}", usage);
+                Ok(())
+            }
+            Command::Unknown { name } => {
+                println!("Unknown command :{}", name);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn dump_state(interpreter: &Interpreter, path: &str) -> InterpreterResult<()> {
+    for name in state::save(interpreter, path)? {
+        println!("Skipped {} (closes over local state)", name);
+    }
+    println!("Dumped globals to {}", path);
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn load_state(interpreter: &Interpreter, path: &str) -> InterpreterResult<()> {
+    state::load(interpreter, path)?;
+    println!("Loaded globals from {}", path);
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn dump_state(_interpreter: &Interpreter, _path: &str) -> InterpreterResult<()> {
+    println!("Saving state to disk requires the serde feature");
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn load_state(_interpreter: &Interpreter, _path: &str) -> InterpreterResult<()> {
+    println!("Loading state from disk requires the serde feature");
+    Ok(())
+}
+
+fn read_file(fname: &str) -> InterpreterResult<String> {
+    read_source(fname, false)
+}
+
+fn read_source(fname: &str, latin1: bool) -> InterpreterResult<String> {
+    source::decode(fname, std::fs::read(fname)?, latin1)
+}
+
+fn print_ast(source: String) -> InterpreterResult<()> {
+    let (stmts, errs) = parser::parse_stream(TokenStream::new(&source));
+    report_errors(errs)?;
+    for stmt in stmts.iter() {
+        println!("{}", stmt.print()?);
+    }
+    Ok(())
+}
+
+fn print_docs(fname: &str) -> InterpreterResult<()> {
+    let source = read_file(fname)?;
+    let stmts: Vec<Stmt> = Cst::parse(&source)?
+        .nodes()
+        .iter()
+        .map(|node| node.stmt.clone())
+        .collect();
+    print!("{}", doc::markdown(fname, &stmts));
+    Ok(())
+}
+
+fn compile_file(fname: &str, out: &str) -> InterpreterResult<()> {
+    let (stmts, errs) = parser::parse_stream(TokenStream::new(&read_file(fname)?));
+    report_errors(errs)?;
+    report_errors(resolver::resolve(&stmts).1)?;
+    std::fs::write(out, compile::compile(&stmts)?)?;
+    Ok(())
+}
+
+fn format_file(fname: &str, output: FormatOutput) -> InterpreterResult<()> {
+    let source = read_file(fname)?;
+    let formatted = format_source(&source)?;
+    match output {
+        FormatOutput::InPlace if formatted != source => std::fs::write(fname, formatted)?,
+        FormatOutput::InPlace => {}
+        FormatOutput::Stdout => print!("{}", formatted),
+        FormatOutput::Check if formatted != source => {
+            println!("{} is not formatted", fname);
+            return Err(InterpreterError::Exit { code: 1 });
+        }
+        FormatOutput::Check => {}
+    }
+    Ok(())
+}
+
+// Scans and parses under the same limits as a run, so a file passes --check
+// exactly when it would get as far as running.
+fn check(source: String, strict: bool, prelude: bool, limits: &Limits) -> InterpreterResult<()> {
+    let (tokens, mut errs) = scan_limited(&source, limits);
+    let (stmts, parse_errs) = parser::parse_with(tokens, limits.nesting());
+    if parse_errs.is_empty() {
+        let mut interpreter = Interpreter::default();
+        if prelude {
+            Prelude::standard().load(&interpreter);
+        }
+        interpreter.set_strict(strict);
+        errs.extend(interpreter.resolve(&stmts).err().unwrap_or_default());
+    }
+    errs.extend(parse_errs);
+    report_errors(errs)
+}
+
+fn recover(res: InterpreterResult<()>) -> InterpreterResult<()> {
+    match res {
+        Err(err) if err.is_static() => Ok(()),
+        Err(err) if err.is_runtime() || matches!(err, InterpreterError::Interrupted) => {
+            println!("{}", err);
+            Ok(())
+        }
+        res => res,
+    }
+}
+
+fn report_errors(errs: Vec<InterpreterError>) -> InterpreterResult<()> {
+    let mut last = None;
+    for err in errs.into_iter() {
+        println!("{}", &err);
+        last = Some(err);
+    }
+    last.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn lib_check_applies_limits() {
+        let deep = format!("print {}1{};", "(".repeat(200), ")".repeat(200));
+        assert!(check(deep.clone(), false, true, &Limits::default()).is_err());
+        let limits = Limits {
+            max_nesting: Some(256),
+            max_tokens: Some(8),
+            ..Limits::default()
+        };
+        assert!(check(String::from("print (1);"), false, true, &limits).is_ok());
+        assert!(check(String::from("print 1 + 2 + 3 + 4;"), false, true, &limits).is_err());
+        assert!(check(
+            deep,
+            false,
+            true,
+            &Limits {
+                max_tokens: None,
+                ..limits
+            }
+        )
+        .is_ok());
+    }
 }
@@ -0,0 +1,135 @@
+use crate::errors::{InterpreterError, InterpreterResult};
+use std::cell::Cell;
+
+// The defaults are sized for the 2MB stack Rust gives spawned threads, in a
+// debug build. Embedders raising them should run the interpreter on a thread
+// with a correspondingly larger stack.
+pub(crate) const DEFAULT_MAX_NESTING: usize = 128;
+// Each Lox call takes several Rust frames, so unbounded recursion would
+// overflow the native stack and abort the process rather than fail in Lox.
+pub(crate) const DEFAULT_MAX_CALL_DEPTH: usize = 64;
+// With no string budget configured, a single string still can't outgrow
+// this, so `"ab" * 1e11` fails in Lox rather than aborting on allocation.
+pub(crate) const DEFAULT_MAX_STRING_BYTES: usize = 1 << 30;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    pub max_expressions: Option<usize>,
+    pub max_call_depth: Option<usize>,
+    pub max_string_bytes: Option<usize>,
+    pub max_nesting: Option<usize>,
+    pub max_literal_bytes: Option<usize>,
+    pub max_tokens: Option<usize>,
+}
+
+impl Limits {
+    pub(crate) fn nesting(&self) -> usize {
+        self.max_nesting.unwrap_or(DEFAULT_MAX_NESTING)
+    }
+    pub(crate) fn call_depth(&self) -> usize {
+        self.max_call_depth.unwrap_or(DEFAULT_MAX_CALL_DEPTH)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Usage {
+    expressions: Cell<usize>,
+    call_depth: Cell<usize>,
+    string_bytes: Cell<usize>,
+}
+
+impl Usage {
+    pub(crate) fn reset(&self) {
+        self.expressions.set(0);
+        self.call_depth.set(0);
+        self.string_bytes.set(0);
+    }
+    pub(crate) fn expression(&self, limits: &Limits) -> InterpreterResult<()> {
+        charge(&self.expressions, 1, limits.max_expressions, "expression")
+    }
+    pub(crate) fn enter_call(&self, limits: &Limits) -> InterpreterResult<()> {
+        charge(&self.call_depth, 1, Some(limits.call_depth()), "call depth")
+    }
+    pub(crate) fn call_depth(&self) -> usize {
+        self.call_depth.get()
+    }
+    pub(crate) fn exit_call(&self) {
+        self.call_depth.set(self.call_depth.get().saturating_sub(1));
+    }
+    pub(crate) fn string(&self, bytes: usize, limits: &Limits) -> InterpreterResult<()> {
+        if limits.max_string_bytes.is_none() && bytes > DEFAULT_MAX_STRING_BYTES {
+            return Err(InterpreterError::LimitExceeded {
+                limit: "string byte",
+                max: DEFAULT_MAX_STRING_BYTES,
+            });
+        }
+        charge(
+            &self.string_bytes,
+            bytes,
+            limits.max_string_bytes,
+            "string byte",
+        )
+    }
+}
+
+fn charge(
+    used: &Cell<usize>,
+    amount: usize,
+    max: Option<usize>,
+    limit: &'static str,
+) -> InterpreterResult<()> {
+    let total = used.get().saturating_add(amount);
+    used.set(total);
+    match max {
+        Some(max) if total > max => Err(InterpreterError::LimitExceeded { limit, max }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn limits_usage() {
+        let limits = Limits {
+            max_expressions: Some(2),
+            max_call_depth: Some(1),
+            max_string_bytes: Some(4),
+            ..Limits::default()
+        };
+        let usage = Usage::default();
+        assert!(usage.expression(&limits).is_ok());
+        assert!(usage.expression(&limits).is_ok());
+        assert!(matches!(
+            usage.expression(&limits),
+            Err(InterpreterError::LimitExceeded {
+                limit: "expression",
+                max: 2
+            })
+        ));
+        assert!(usage.enter_call(&limits).is_ok());
+        assert!(usage.enter_call(&limits).is_err());
+        usage.exit_call();
+        usage.exit_call();
+        assert!(usage.enter_call(&limits).is_ok());
+        assert!(usage.string(4, &limits).is_ok());
+        assert!(usage.string(1, &limits).is_err());
+        usage.reset();
+        assert!(usage.expression(&limits).is_ok());
+        assert!(usage.string(4, &Limits::default()).is_ok());
+        assert!(usage
+            .string(DEFAULT_MAX_STRING_BYTES + 1, &Limits::default())
+            .is_err());
+        usage.reset();
+        for _ in 0..DEFAULT_MAX_CALL_DEPTH {
+            assert!(usage.enter_call(&Limits::default()).is_ok());
+        }
+        assert!(matches!(
+            usage.enter_call(&Limits::default()),
+            Err(InterpreterError::LimitExceeded {
+                limit: "call depth",
+                max: DEFAULT_MAX_CALL_DEPTH
+            })
+        ));
+    }
+}
@@ -0,0 +1,489 @@
+use crate::cancel::CancelToken;
+use crate::capabilities::Capabilities;
+use crate::errors::InterpreterResult;
+use crate::function::{Callable, LoxFunction};
+use crate::hooks::InterpreterHooks;
+use crate::interpreter::Interpreter;
+use crate::limits::Limits;
+use crate::parser;
+use crate::prelude::Prelude;
+use crate::scanner::scan_limited;
+use crate::source::Program;
+use crate::stats::Stats;
+use crate::value::Value;
+use std::rc::Rc;
+
+#[derive(Debug, Default)]
+pub struct Lox {
+    interpreter: Interpreter,
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn from_prelude(prelude: &Prelude) -> Self {
+        let lox = Self::default();
+        prelude.load(&lox.interpreter);
+        lox
+    }
+    pub fn eval_str(&mut self, src: &str) -> InterpreterResult<Value> {
+        let (tokens, scan_errs) = scan_limited(src, self.interpreter.limits());
+        if let Some(err) = scan_errs.into_iter().next() {
+            return Err(err);
+        }
+        let (stmts, parse_errs) = parser::parse_with(tokens, self.interpreter.limits().nesting());
+        if let Some(err) = parse_errs.into_iter().next() {
+            return Err(err);
+        }
+        Ok(self
+            .interpreter
+            .interpret_all(&stmts)?
+            .unwrap_or(Value::Nil))
+    }
+    pub fn run(&mut self, program: &Program) -> InterpreterResult<Value> {
+        Ok(self
+            .interpreter
+            .interpret_all(&program.stmts)?
+            .unwrap_or(Value::Nil))
+    }
+    pub fn define_global<T>(&mut self, name: T, value: Value)
+    where
+        T: Into<String>,
+    {
+        self.interpreter.define(&name.into(), value);
+    }
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.interpreter.lookup(name)
+    }
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.interpreter.set_limits(limits);
+    }
+    pub fn set_strict(&mut self, strict: bool) {
+        self.interpreter.set_strict(strict);
+    }
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.interpreter.set_capabilities(capabilities);
+    }
+    pub fn add_hook(&mut self, hook: Box<dyn InterpreterHooks>) {
+        self.interpreter.add_hook(hook);
+    }
+    pub fn stats(&self) -> Stats {
+        self.interpreter.stats()
+    }
+    pub fn reset_stats(&mut self) {
+        self.interpreter.reset_stats();
+    }
+    pub fn cancel_token(&self) -> CancelToken {
+        self.interpreter.cancel_token()
+    }
+    pub fn set_input<R>(&mut self, input: R)
+    where
+        R: std::io::BufRead + 'static,
+    {
+        self.interpreter.set_input(Box::new(input));
+    }
+    pub fn capture_output(&mut self, capture: bool) {
+        self.interpreter.capture_output(capture);
+    }
+    pub fn take_output(&mut self) -> String {
+        self.interpreter.take_output()
+    }
+    #[cfg(feature = "serde")]
+    pub fn save_state<P>(&self, path: P) -> InterpreterResult<Vec<String>>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        crate::state::save(&self.interpreter, path)
+    }
+    #[cfg(feature = "serde")]
+    pub fn load_state<P>(&mut self, path: P) -> InterpreterResult<()>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        crate::state::load(&self.interpreter, path)
+    }
+    pub fn get_function(&self, name: &str) -> Option<Function> {
+        match self.interpreter.lookup(name) {
+            Some(Value::Callable(Callable::Lox(function))) => Some(Function { function }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Function {
+    function: Rc<LoxFunction>,
+}
+
+impl Function {
+    pub fn name(&self) -> String {
+        self.function.name()
+    }
+    pub fn arity(&self) -> usize {
+        self.function.arity()
+    }
+    pub fn call(&self, lox: &mut Lox, args: Vec<Value>) -> InterpreterResult<Value> {
+        lox.interpreter.reset_usage();
+        lox.interpreter
+            .call_function(&self.function, args, self.function.line())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::{LoxClass, LoxInstance};
+    use crate::errors::InterpreterError;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    #[test]
+    fn lox_eval_str() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        assert_eq!(lox.eval_str("1 + 2;")?, Value::Number(3.0));
+        assert_eq!(lox.eval_str("var a = 1;")?, Value::Nil);
+        assert_eq!(lox.eval_str("a = a + 1; a;")?, Value::Number(2.0));
+        Ok(())
+    }
+    #[test]
+    fn lox_globals() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.define_global("greeting", Value::r#String("hi".into()));
+        lox.eval_str("var shout = greeting + \"!\";")?;
+        assert_eq!(lox.get_global("shout"), Some(Value::r#String("hi!".into())));
+        assert_eq!(lox.get_global("nope"), None);
+        Ok(())
+    }
+    #[test]
+    fn lox_functions() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.eval_str("fun add(a, b) { return a + b; }")?;
+        assert_eq!(lox.eval_str("add(1, 2);")?, Value::Number(3.0));
+        lox.eval_str("fun noop() {}")?;
+        assert_eq!(lox.eval_str("noop();")?, Value::Nil);
+        lox.eval_str(
+            "fun counter() { var i = 0; fun count() { i = i + 1; return i; } return count; }",
+        )?;
+        lox.eval_str("var c = counter(); c();")?;
+        assert_eq!(lox.eval_str("c();")?, Value::Number(2.0));
+        assert!(matches!(
+            lox.eval_str("add(1);"),
+            Err(InterpreterError::RuntimeError { .. })
+        ));
+        assert!(matches!(
+            lox.eval_str("\"add\"(1);"),
+            Err(InterpreterError::RuntimeError { .. })
+        ));
+        Ok(())
+    }
+    #[test]
+    fn lox_get_function() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.eval_str("var total = 0; fun bump(n) { total = total + n; return total; }")?;
+        let bump = lox.get_function("bump").unwrap();
+        assert_eq!(bump.name(), "bump");
+        assert_eq!(bump.arity(), 1);
+        assert_eq!(
+            bump.call(&mut lox, vec![Value::Number(2.0)])?,
+            Value::Number(2.0)
+        );
+        assert_eq!(
+            bump.call(&mut lox, vec![Value::Number(3.0)])?,
+            Value::Number(5.0)
+        );
+        assert_eq!(lox.get_global("total"), Some(Value::Number(5.0)));
+        assert!(bump.call(&mut lox, vec![]).is_err());
+        assert!(lox.get_function("total").is_none());
+        assert!(lox.get_function("nope").is_none());
+        Ok(())
+    }
+    #[test]
+    fn lox_capture_output() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.capture_output(true);
+        lox.eval_str("print 1; print \"two\";")?;
+        assert_eq!(lox.take_output(), "1\ntwo\n");
+        assert_eq!(lox.take_output(), "");
+        lox.capture_output(false);
+        assert_eq!(lox.take_output(), "");
+        Ok(())
+    }
+    #[test]
+    fn lox_cancel() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.eval_str("var i = 0; while (i < 3) { i = i + 1; }")?;
+        assert_eq!(lox.get_global("i"), Some(Value::Number(3.0)));
+        let token = lox.cancel_token();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            token.cancel();
+        });
+        assert!(matches!(
+            lox.eval_str("while (true) {}"),
+            Err(InterpreterError::Interrupted)
+        ));
+        canceller.join().unwrap();
+        assert_eq!(lox.eval_str("1 + 1;")?, Value::Number(2.0));
+        Ok(())
+    }
+    #[test]
+    fn lox_read_line() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.set_input(std::io::Cursor::new("Ada\n"));
+        lox.capture_output(true);
+        lox.eval_str("var name = readLine(); print \"hi \" + name;")?;
+        assert_eq!(lox.take_output(), "hi Ada\n");
+        assert_eq!(lox.eval_str("readLine();")?, Value::Nil);
+        assert!(lox.eval_str("readLine(1);").is_err());
+        Ok(())
+    }
+    #[test]
+    fn lox_limits() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.set_limits(Limits {
+            max_expressions: Some(1000),
+            max_call_depth: Some(10),
+            max_string_bytes: Some(16),
+            max_nesting: Some(8),
+            max_literal_bytes: Some(8),
+            max_tokens: Some(64),
+        });
+        assert!(matches!(
+            lox.eval_str("while (true) {}"),
+            Err(InterpreterError::LimitExceeded {
+                limit: "expression",
+                ..
+            })
+        ));
+        lox.eval_str("fun down(n) { return down(n - 1); }")?;
+        assert!(matches!(
+            lox.eval_str("down(100);"),
+            Err(InterpreterError::LimitExceeded {
+                limit: "call depth",
+                ..
+            })
+        ));
+        assert!(matches!(
+            lox.eval_str("var s = \"ab\"; while (true) { s = s + s; }"),
+            Err(InterpreterError::LimitExceeded {
+                limit: "string byte",
+                ..
+            })
+        ));
+        assert!(matches!(
+            lox.eval_str("print \"abcdefghi\";"),
+            Err(InterpreterError::Interpreter { line: 1, message }) if message == "Literal longer than 8 bytes"
+        ));
+        assert!(lox.eval_str("print 123456789;").is_err());
+        assert!(matches!(
+            lox.eval_str(&"1 + ".repeat(40)),
+            Err(InterpreterError::Interpreter { message, .. }) if message == "More than 64 tokens"
+        ));
+        assert_eq!(lox.eval_str("1 + 2;")?, Value::Number(3.0));
+        Ok(())
+    }
+    #[test]
+    fn lox_default_limits_fit_thread_stacks() {
+        // A plain spawned thread gets 2MB of stack; the default limits should
+        // fail in Lox before any of these overflow it.
+        std::thread::spawn(|| {
+            let n = 10_000;
+            for source in [
+                format!("var a = {}1{};", "(".repeat(n), ")".repeat(n)),
+                format!("var a = {}1;", "-".repeat(n)),
+                format!("{}print 1;{}", "{".repeat(n), "}".repeat(n)),
+            ] {
+                assert!(matches!(
+                    Lox::new().eval_str(&source),
+                    Err(InterpreterError::SyntaxError { message, .. }) if message == "Too deeply nested"
+                ));
+            }
+            for source in [
+                "fun f(n) { return f(n + 1); } f(0);",
+                "fun f(n) { while (true) { { var x = 1 + (2 * (3 + (4 - f(n - 1)))); return x; } } }
+                 f(0);",
+            ] {
+                assert!(matches!(
+                    Lox::new().eval_str(source),
+                    Err(InterpreterError::LimitExceeded {
+                        limit: "call depth",
+                        ..
+                    })
+                ));
+            }
+            let mut lox = Lox::new();
+            assert_eq!(
+                lox.eval_str("fun f(n) { while (n > 0) return 1 + f(n - 1); return 0; } f(60);")
+                    .unwrap(),
+                Value::Number(60.0)
+            );
+        })
+        .join()
+        .unwrap();
+    }
+    #[test]
+    fn lox_strict() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.set_strict(true);
+        lox.eval_str("var a = 1; fun f() { return g(); } fun g() { return a; }")?;
+        assert_eq!(lox.eval_str("f();")?, Value::Number(1.0));
+        for source in [
+            "var a = 2;",
+            "{ var b = 1; var b = 2; }",
+            "fun h(x, x) {}",
+            "nope = 1;",
+            "print nope;",
+            "print later; var later = 1;",
+        ] {
+            assert!(
+                matches!(
+                    lox.eval_str(source),
+                    Err(InterpreterError::SyntaxError { .. })
+                ),
+                "{}",
+                source
+            );
+        }
+        assert_eq!(lox.get_global("later"), None);
+        lox.set_strict(false);
+        assert_eq!(lox.eval_str("var a = 2; a;")?, Value::Number(2.0));
+        Ok(())
+    }
+    #[test]
+    fn lox_eval_str_errors() {
+        let mut lox = Lox::new();
+        assert!(matches!(
+            lox.eval_str("1 @ 2;"),
+            Err(InterpreterError::Interpreter { .. })
+        ));
+        assert!(lox.eval_str("1 +;").unwrap_err().is_static());
+        assert!(matches!(
+            lox.eval_str("nope;"),
+            Err(InterpreterError::UndefinedVariable { .. })
+        ));
+    }
+    #[test]
+    fn lox_bigint() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.eval_str(
+            "fun fact(n) { var r = 1n; while (n > 0) { r = r * n; n = n - 1; } return r; }",
+        )?;
+        assert_eq!(
+            lox.eval_str("str(fact(25));")?,
+            Value::r#String("15511210043330985984000000".into())
+        );
+        assert_eq!(
+            lox.eval_str("str(2n * 3 - 10n);")?,
+            Value::r#String("-4".into())
+        );
+        assert_eq!(lox.eval_str("str(7n / 2n);")?, Value::r#String("3".into()));
+        assert_eq!(lox.eval_str("1n == 1;")?, Value::Bool(true));
+        assert_eq!(lox.eval_str("2n > 1;")?, Value::Bool(true));
+        assert_eq!(lox.eval_str("str(-5n);")?, Value::r#String("-5".into()));
+        assert!(matches!(
+            lox.eval_str("1n * 1.5;"),
+            Err(InterpreterError::Type { .. })
+        ));
+        assert!(matches!(
+            lox.eval_str("1n / 0n;"),
+            Err(InterpreterError::RuntimeError { .. })
+        ));
+        Ok(())
+    }
+    #[test]
+    fn lox_bytes() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.eval_str("var b = b\"A\\x00\\xff\";")?;
+        assert_eq!(lox.eval_str("b[0];")?, Value::Number(65.0));
+        assert_eq!(lox.eval_str("b[len(b) - 1];")?, Value::Number(255.0));
+        assert_eq!(lox.eval_str("b == b\"A\\x00\\xFF\";")?, Value::Bool(true));
+        assert_eq!(
+            lox.eval_str("str(b);")?,
+            Value::r#String("b\"A\\x00\\xff\"".into())
+        );
+        assert_eq!(
+            lox.eval_str("decode(encode(\"hé\", \"latin1\"), \"latin1\");")?,
+            Value::r#String("hé".into())
+        );
+        assert!(matches!(
+            lox.eval_str("b[3];"),
+            Err(InterpreterError::RuntimeError { .. })
+        ));
+        assert!(matches!(
+            lox.eval_str("\"abc\"[0];"),
+            Err(InterpreterError::RuntimeError { .. })
+        ));
+        Ok(())
+    }
+    #[test]
+    fn lox_nil_coalescing() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.eval_str("var calls = 0; fun f() { calls = calls + 1; return 2; }")?;
+        assert_eq!(lox.eval_str("nil ?? f();")?, Value::Number(2.0));
+        assert_eq!(lox.eval_str("false ?? f();")?, Value::Bool(false));
+        assert_eq!(lox.eval_str("0 ?? f();")?, Value::Number(0.0));
+        assert_eq!(lox.eval_str("nil ?? nil ?? 3;")?, Value::Number(3.0));
+        assert_eq!(lox.eval_str("calls;")?, Value::Number(1.0));
+        Ok(())
+    }
+    #[test]
+    fn lox_to_string() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.capture_output(true);
+        lox.eval_str("fun toString() { return \"(0, 0)\" + str(type(this) == \"instance\"); }")?;
+        let method = match lox.get_global("toString") {
+            Some(Value::Callable(Callable::Lox(method))) => method,
+            _ => unreachable!(),
+        };
+        let point = LoxClass::new(
+            String::from("Point"),
+            HashMap::from([(String::from("toString"), method)]),
+        );
+        let bare = LoxClass::new(String::from("Bare"), HashMap::default());
+        let instance =
+            |class| Value::Instance(Rc::new(RefCell::new(LoxInstance::new(Rc::new(class)))));
+        lox.define_global("p", instance(point));
+        lox.define_global("b", instance(bare));
+        lox.eval_str("print p; print str(p) + \"!\"; print b;")?;
+        assert_eq!(
+            lox.take_output(),
+            "(0, 0)true\n(0, 0)true!\nBare instance\n"
+        );
+        Ok(())
+    }
+    #[test]
+    fn lox_eq_method() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.eval_str(
+            "var seen; fun eq(other) { fun self() { return this; } seen = self(); return other == 1; }
+             fun bad(other) { return 1; }",
+        )?;
+        let method = |name| match lox.get_global(name) {
+            Some(Value::Callable(Callable::Lox(method))) => method,
+            _ => unreachable!(),
+        };
+        let one = LoxClass::new(
+            String::from("One"),
+            HashMap::from([(String::from("eq"), method("eq"))]),
+        );
+        let bad = LoxClass::new(
+            String::from("Bad"),
+            HashMap::from([(String::from("eq"), method("bad"))]),
+        );
+        let instance =
+            |class| Value::Instance(Rc::new(RefCell::new(LoxInstance::new(Rc::new(class)))));
+        let a = instance(one);
+        lox.define_global("a", a.clone());
+        lox.define_global("b", instance(bad));
+        assert_eq!(lox.eval_str("a == 1;")?, Value::Bool(true));
+        assert_eq!(lox.get_global("seen"), Some(a));
+        assert_eq!(lox.eval_str("1 == a;")?, Value::Bool(true));
+        assert_eq!(lox.eval_str("a != 2;")?, Value::Bool(true));
+        assert_eq!(lox.eval_str("a == nil;")?, Value::Bool(false));
+        assert!(matches!(
+            lox.eval_str("b == 1;"),
+            Err(InterpreterError::Type { line: Some(1), .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+#[macro_export]
+macro_rules! lox {
+    ($($t:tt)*) => {{
+        $($crate::__lox_token!($t);)*
+        $crate::__lox_program(stringify!($($t)*))
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __lox_token {
+    (( $($t:tt)* )) => { $($crate::__lox_token!($t);)* };
+    ({ $($t:tt)* }) => { $($crate::__lox_token!($t);)* };
+    ([ $($t:tt)* ]) => { $($crate::__lox_token!($t);)* };
+    ($i:ident) => {};
+    ($l:literal) => {};
+    (+) => {};
+    (-) => {};
+    (*) => {};
+    (/) => {};
+    (;) => {};
+    (,) => {};
+    (.) => {};
+    (!) => {};
+    (=) => {};
+    (==) => {};
+    (!=) => {};
+    (<) => {};
+    (<=) => {};
+    (>) => {};
+    (>=) => {};
+    (?) => {};
+    ($other:tt) => {
+        compile_error!(concat!("Unknown token in lox!: ", stringify!($other)))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::InterpreterResult;
+    use crate::lox::Lox;
+    use crate::value::Value;
+    #[test]
+    fn macros_lox() -> InterpreterResult<()> {
+        let program = crate::lox! {
+            var x = 1;
+            fun add(a, b) { return a + b; }
+            while (x < 4) { x = add(x, x); }
+            print x >= 4;
+            print x != 5;
+            print nil ?? b"ab"[1];
+            x;
+        };
+        assert_eq!(program.len(), 7);
+        let mut lox = Lox::new();
+        lox.capture_output(true);
+        assert_eq!(lox.run(&program)?, Value::Number(4.0));
+        assert_eq!(lox.take_output(), "true\ntrue\n98\n");
+        Ok(())
+    }
+    #[test]
+    #[should_panic(expected = "Invalid lox! source")]
+    fn macros_lox_parse_error() {
+        crate::lox! { var = 1; };
+    }
+}
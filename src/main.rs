@@ -1,18 +1,16 @@
+use crafting_interpreters::{CountingAllocator, InterpreterError};
 use std::process::exit;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
 fn main() {
     match crafting_interpreters::main() {
         Ok(()) => exit(0),
-        Err(err @ crafting_interpreters::InterpreterError::Usage) => {
+        Err(InterpreterError::Exit { code }) => exit(code),
+        Err(err) => {
             println!("{:?}", err);
-            exit(64)
-        }
-        Err(err @ crafting_interpreters::InterpreterError::Interpreter { .. }) => {
-            println!("{:?}", err);
-            exit(65)
-        }
-        Err(e) => {
-            println!("{:?}", e);
-            exit(70)
+            exit(err.exit_code())
         }
     }
 }
@@ -0,0 +1,1062 @@
+use crate::capabilities::Capability;
+use crate::class::{LoxClass, LoxInstance};
+#[cfg(feature = "dlopen")]
+use crate::dylib;
+use crate::environment::Globals;
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::function::{Callable, NativeCall, NativeFn, NativeFunction};
+use crate::interpreter::Interpreter;
+use crate::threads;
+use crate::value::{whole_number, Value};
+use float_eq::float_eq;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::process::Command;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const NATIVES: &[(&str, &str, RangeInclusive<usize>, NativeFn, &str)] = &[
+    (
+        "readLine",
+        "",
+        0..=0,
+        read_line,
+        "Reads a line from standard input, or returns nil at end of input.",
+    ),
+    (
+        "len",
+        "s",
+        1..=1,
+        len,
+        "Returns the number of characters in s, or of bytes if s is bytes.",
+    ),
+    (
+        "substring",
+        "s, start, end",
+        3..=3,
+        substring,
+        "Returns the characters of s from start up to, but not including, end.",
+    ),
+    (
+        "indexOf",
+        "s, needle",
+        2..=2,
+        index_of,
+        "Returns the character index of the first needle in s, or -1.",
+    ),
+    (
+        "trim",
+        "s",
+        1..=1,
+        trim,
+        "Returns s without leading and trailing whitespace.",
+    ),
+    (
+        "toUpper",
+        "s",
+        1..=1,
+        to_upper,
+        "Returns s in upper case.",
+    ),
+    (
+        "toLower",
+        "s",
+        1..=1,
+        to_lower,
+        "Returns s in lower case.",
+    ),
+    (
+        "replace",
+        "s, from, to",
+        3..=3,
+        replace,
+        "Returns s with every from replaced by to.",
+    ),
+    (
+        "encode",
+        "s, encoding",
+        1..=2,
+        encode,
+        "Encodes s as bytes in encoding (utf-8, latin1 or ascii; default utf-8), or returns nil.",
+    ),
+    (
+        "decode",
+        "bytes, encoding",
+        1..=2,
+        decode,
+        "Decodes bytes as a string in encoding (utf-8, latin1 or ascii; default utf-8), or returns nil.",
+    ),
+    (
+        "readFile",
+        "path",
+        1..=1,
+        read_file,
+        "Returns the contents of the file at path. Needs the fs capability.",
+    ),
+    (
+        "readBytes",
+        "path",
+        1..=1,
+        read_bytes,
+        "Returns the contents of the file at path as bytes. Needs the fs capability.",
+    ),
+    (
+        "writeFile",
+        "path, contents",
+        2..=2,
+        write_file,
+        "Writes contents to the file at path, replacing it. Needs the fs capability.",
+    ),
+    (
+        "appendFile",
+        "path, contents",
+        2..=2,
+        append_file,
+        "Appends contents to the file at path, creating it if needed. Needs the fs capability.",
+    ),
+    (
+        "fileExists",
+        "path",
+        1..=1,
+        file_exists,
+        "Returns whether path is an existing file. Needs the fs capability.",
+    ),
+    (
+        "getenv",
+        "name",
+        1..=1,
+        getenv,
+        "Returns the environment variable name, or nil if it isn't set. Needs the env capability.",
+    ),
+    (
+        "setenv",
+        "name, value",
+        2..=2,
+        setenv,
+        "Sets the environment variable name to value. Needs the env capability.",
+    ),
+    (
+        "exec",
+        "cmd, args...",
+        1..=256,
+        exec,
+        "Runs cmd with args and returns an instance with its stdout, stderr and exit code, nil if it was killed by a signal. Needs the process capability.",
+    ),
+    (
+        "getField",
+        "instance, name",
+        2..=2,
+        get_field,
+        "Returns the field called name on instance, or nil if it has none.",
+    ),
+    (
+        "type",
+        "value",
+        1..=1,
+        type_of,
+        "Returns the name of value's type.",
+    ),
+    (
+        "str",
+        "value",
+        1..=1,
+        str,
+        "Returns value as a string.",
+    ),
+    (
+        "num",
+        "value",
+        1..=1,
+        num,
+        "Parses value as a number, returning nil if it isn't one.",
+    ),
+    (
+        "bigint",
+        "value",
+        1..=1,
+        bigint,
+        "Converts a whole number or a string of digits to a bigint, or returns nil.",
+    ),
+    (
+        "approxEq",
+        "a, b, epsilon",
+        3..=3,
+        approx_eq,
+        "Returns whether a and b differ by at most epsilon.",
+    ),
+    (
+        "assert",
+        "condition, message?",
+        1..=2,
+        assert,
+        "Fails with message unless condition is true.",
+    ),
+    (
+        "exit",
+        "code",
+        1..=1,
+        exit,
+        "Exits the interpreter with code.",
+    ),
+    (
+        "clock",
+        "",
+        0..=0,
+        clock,
+        "Returns the seconds elapsed since the interpreter started.",
+    ),
+    (
+        "now",
+        "",
+        0..=0,
+        now,
+        "Returns the milliseconds since the Unix epoch as a bigint, exact to the millisecond. Subtract two readings for elapsed time.",
+    ),
+    (
+        "formatTime",
+        "millis, format",
+        2..=2,
+        format_time,
+        "Formats millis since the epoch, a number or a bigint such as now() returns, in UTC using %Y, %m, %d, %H, %M and %S.",
+    ),
+    (
+        "sleep",
+        "millis",
+        1..=1,
+        sleep,
+        "Pauses for millis milliseconds.",
+    ),
+    (
+        "spawn",
+        "fn, args...",
+        1..=256,
+        threads::spawn,
+        "Runs fn(args...) on a new thread with its own interpreter and returns a thread handle. fn can't see the caller's variables.",
+    ),
+    (
+        "join",
+        "thread",
+        1..=1,
+        threads::join,
+        "Waits for thread to finish and returns what its function returned.",
+    ),
+    (
+        "chanNew",
+        "",
+        0..=0,
+        threads::chan_new,
+        "Returns a handle to a new channel that any thread can send to or receive from.",
+    ),
+    (
+        "send",
+        "channel, value",
+        2..=2,
+        threads::send,
+        "Sends a string, number, bool, nil, bigint or bytes value on channel.",
+    ),
+    (
+        "recv",
+        "channel",
+        1..=1,
+        threads::recv,
+        "Waits for and returns the next value sent on channel, or fails once it is closed.",
+    ),
+    (
+        "chanClose",
+        "channel",
+        1..=1,
+        threads::chan_close,
+        "Closes channel, dropping values not yet received and waking any thread waiting on it.",
+    ),
+    #[cfg(feature = "dlopen")]
+    (
+        "ffiLoad",
+        "path",
+        1..=1,
+        dylib::ffi_load,
+        "Loads the shared library at path and returns a handle to it. Needs the ffi capability.",
+    ),
+    #[cfg(feature = "dlopen")]
+    (
+        "ffiCall",
+        "library, name, params, ret, args...",
+        4..=8,
+        dylib::ffi_call,
+        "Calls the C function name in library. params lists up to 4 parameter types, all f64 or all i64, separated by commas; ret is f64, i64 or void. Needs the ffi capability.",
+    ),
+];
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+pub(crate) fn globals() -> Globals {
+    START.get_or_init(Instant::now);
+    let mut env = Globals::default();
+    for (name, params, arity, call, doc) in NATIVES.iter() {
+        let native = NativeFunction {
+            name,
+            params,
+            arity: arity.clone(),
+            call: NativeCall::Builtin(*call),
+            doc,
+        };
+        env.define(
+            String::from(*name),
+            Value::Callable(Callable::Native(Rc::new(native))),
+        );
+    }
+    env
+}
+
+fn read_line(
+    interpreter: &Interpreter,
+    _args: Vec<Value>,
+    _line: usize,
+) -> InterpreterResult<Value> {
+    Ok(interpreter.read_line()?.map_or(Value::Nil, |l| {
+        Value::r#String(l.trim_end_matches(['\n', '\r']).into())
+    }))
+}
+
+fn len(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    if let Value::Bytes(b) = &args[0] {
+        return Ok(Value::Number(b.len() as f32));
+    }
+    let s = string_arg(&args, 0, line)?;
+    Ok(Value::Number(s.chars().count() as f32))
+}
+
+fn substring(
+    _interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    let s = string_arg(&args, 0, line)?;
+    let start = index_arg(&args, 1, line)?;
+    let end = index_arg(&args, 2, line)?;
+    let length = s.chars().count();
+    if start > end || end > length {
+        return Err(InterpreterError::RuntimeError {
+            line,
+            message: format!(
+                "Substring range {}..{} is out of bounds for a string of length {}",
+                start, end, length
+            ),
+        });
+    }
+    Ok(Value::r#String(
+        s.chars()
+            .skip(start)
+            .take(end - start)
+            .collect::<String>()
+            .into(),
+    ))
+}
+
+fn index_of(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    let s = string_arg(&args, 0, line)?;
+    let needle = string_arg(&args, 1, line)?;
+    Ok(Value::Number(
+        s.find(&needle)
+            .map_or(-1.0, |byte| s[..byte].chars().count() as f32),
+    ))
+}
+
+fn trim(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    Ok(Value::r#String(string_arg(&args, 0, line)?.trim().into()))
+}
+
+fn to_upper(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    Ok(Value::r#String(
+        string_arg(&args, 0, line)?.to_uppercase().into(),
+    ))
+}
+
+fn to_lower(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    Ok(Value::r#String(
+        string_arg(&args, 0, line)?.to_lowercase().into(),
+    ))
+}
+
+fn replace(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    let s = string_arg(&args, 0, line)?;
+    let from = string_arg(&args, 1, line)?;
+    let to = string_arg(&args, 2, line)?;
+    if from.is_empty() {
+        return Err(InterpreterError::RuntimeError {
+            line,
+            message: "Can't replace an empty string".into(),
+        });
+    }
+    Ok(Value::r#String(s.replace(&from, &to).into()))
+}
+
+#[derive(Clone, Copy)]
+enum Encoding {
+    Utf8,
+    Latin1,
+    Ascii,
+}
+
+fn encoding_arg(args: &[Value], i: usize, line: usize) -> InterpreterResult<Encoding> {
+    if args.len() <= i {
+        return Ok(Encoding::Utf8);
+    }
+    match string_arg(args, i, line)?.to_ascii_lowercase().as_str() {
+        "utf-8" | "utf8" => Ok(Encoding::Utf8),
+        "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+        "ascii" => Ok(Encoding::Ascii),
+        other => Err(InterpreterError::RuntimeError {
+            line,
+            message: format!("Unknown encoding {}", other),
+        }),
+    }
+}
+
+fn encode(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    let s = string_arg(&args, 0, line)?;
+    let bytes = match encoding_arg(&args, 1, line)? {
+        Encoding::Utf8 => Some(s.into_bytes()),
+        Encoding::Latin1 => s.chars().map(|c| u8::try_from(c).ok()).collect(),
+        Encoding::Ascii => s.is_ascii().then(|| s.into_bytes()),
+    };
+    Ok(bytes.map_or(Value::Nil, |b| Value::Bytes(Rc::from(b))))
+}
+
+fn decode(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    let bytes = bytes_arg(&args, 0, line)?;
+    let s = match encoding_arg(&args, 1, line)? {
+        Encoding::Utf8 => std::str::from_utf8(&bytes).ok().map(String::from),
+        Encoding::Latin1 => Some(bytes.iter().map(|&b| char::from(b)).collect()),
+        Encoding::Ascii => bytes
+            .is_ascii()
+            .then(|| bytes.iter().map(|&b| char::from(b)).collect()),
+    };
+    Ok(s.map_or(Value::Nil, |s| Value::r#String(s.into())))
+}
+
+fn read_file(interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Fs, line)?;
+    let path = string_arg(&args, 0, line)?;
+    fs::read_to_string(&path)
+        .map(|s| Value::r#String(s.into()))
+        .map_err(|e| io_error("read", &path, e, line))
+}
+
+fn read_bytes(
+    interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Fs, line)?;
+    let path = string_arg(&args, 0, line)?;
+    fs::read(&path)
+        .map(|b| Value::Bytes(Rc::from(b)))
+        .map_err(|e| io_error("read", &path, e, line))
+}
+
+fn write_file(
+    interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Fs, line)?;
+    let path = string_arg(&args, 0, line)?;
+    let contents = contents_arg(&args, 1, line)?;
+    fs::write(&path, contents)
+        .map(|_| Value::Nil)
+        .map_err(|e| io_error("write", &path, e, line))
+}
+
+fn append_file(
+    interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Fs, line)?;
+    let path = string_arg(&args, 0, line)?;
+    let contents = contents_arg(&args, 1, line)?;
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut f| f.write_all(&contents))
+        .map(|_| Value::Nil)
+        .map_err(|e| io_error("append to", &path, e, line))
+}
+
+fn file_exists(
+    interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Fs, line)?;
+    let path = string_arg(&args, 0, line)?;
+    Ok(Value::Bool(Path::new(&path).is_file()))
+}
+
+fn getenv(interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Env, line)?;
+    let name = string_arg(&args, 0, line)?;
+    Ok(env::var(name).map_or(Value::Nil, |v| Value::r#String(v.into())))
+}
+
+fn setenv(interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Env, line)?;
+    let name = string_arg(&args, 0, line)?;
+    let value = string_arg(&args, 1, line)?;
+    if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+        return Err(InterpreterError::RuntimeError {
+            line,
+            message: format!("Invalid environment variable {:?}", name),
+        });
+    }
+    env::set_var(name, value);
+    Ok(Value::Nil)
+}
+
+fn exec(interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Process, line)?;
+    let cmd = string_arg(&args, 0, line)?;
+    let cmd_args = (1..args.len())
+        .map(|i| string_arg(&args, i, line))
+        .collect::<InterpreterResult<Vec<String>>>()?;
+    let output = Command::new(&cmd)
+        .args(&cmd_args)
+        .output()
+        .map_err(|e| io_error("run", &cmd, e, line))?;
+    // A failing command isn't an error in Lox; the script reads the code and
+    // decides.
+    let class = LoxClass::new(String::from("ExecResult"), HashMap::default());
+    let mut result = LoxInstance::new(Rc::new(class));
+    let text = |bytes: &[u8]| Value::r#String(String::from_utf8_lossy(bytes).into());
+    result.set("stdout", text(&output.stdout));
+    result.set("stderr", text(&output.stderr));
+    result.set(
+        "code",
+        output
+            .status
+            .code()
+            .map_or(Value::Nil, |code| Value::Number(code as f32)),
+    );
+    Ok(Value::Instance(Rc::new(RefCell::new(result))))
+}
+
+fn get_field(
+    _interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    let name = string_arg(&args, 1, line)?;
+    match &args[0] {
+        Value::Instance(instance) => Ok(instance
+            .borrow()
+            .fields
+            .get(&name)
+            .cloned()
+            .unwrap_or(Value::Nil)),
+        value => Err(InterpreterError::type_error(
+            String::from("instance"),
+            String::from(value.type_name()),
+        )
+        .add_line_to_type_error(line)),
+    }
+}
+
+fn io_error(action: &str, path: &str, err: std::io::Error, line: usize) -> InterpreterError {
+    InterpreterError::RuntimeError {
+        line,
+        message: format!("Couldn't {} {}: {}", action, path, err),
+    }
+}
+
+fn type_of(_interpreter: &Interpreter, args: Vec<Value>, _line: usize) -> InterpreterResult<Value> {
+    Ok(Value::r#String(args[0].type_name().into()))
+}
+
+fn str(interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    Ok(Value::r#String(
+        interpreter.stringify(&args[0], line)?.into(),
+    ))
+}
+
+fn num(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    match &args[0] {
+        Value::Number(n) => return Ok(Value::Number(*n)),
+        Value::BigInt(n) => return Ok(n.to_f32().map_or(Value::Nil, Value::Number)),
+        _ => {}
+    }
+    let s = string_arg(&args, 0, line)?;
+    Ok(s.trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .map_or(Value::Nil, Value::Number))
+}
+
+fn bigint(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    match &args[0] {
+        Value::BigInt(n) => return Ok(Value::BigInt(Rc::clone(n))),
+        Value::Number(n) => {
+            return Ok(whole_number(*n).map_or(Value::Nil, |n| Value::BigInt(Rc::new(n))))
+        }
+        _ => {}
+    }
+    let s = string_arg(&args, 0, line)?;
+    Ok(s.trim()
+        .parse::<BigInt>()
+        .map_or(Value::Nil, |n| Value::BigInt(Rc::new(n))))
+}
+
+fn approx_eq(
+    _interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    let a = number_arg(&args, 0, line)?;
+    let b = number_arg(&args, 1, line)?;
+    let eps = number_arg(&args, 2, line)?;
+    if eps.is_nan() || eps < 0.0 {
+        return Err(InterpreterError::RuntimeError {
+            line,
+            message: format!(
+                "Expected a non-negative tolerance, got {}",
+                Value::Number(eps)
+            ),
+        });
+    }
+    Ok(Value::Bool(float_eq!(a, b, abs <= eps)))
+}
+
+fn assert(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    if bool::try_from(&args[0])? {
+        return Ok(Value::Nil);
+    }
+    Err(InterpreterError::RuntimeError {
+        line,
+        message: match args.get(1) {
+            Some(message) => format!("Assertion failed: {}", message),
+            None => String::from("Assertion failed"),
+        },
+    })
+}
+
+fn exit(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    let code = index_arg(&args, 0, line)?;
+    Err(InterpreterError::Exit {
+        code: i32::try_from(code).unwrap_or(i32::MAX),
+    })
+}
+
+fn clock(_interpreter: &Interpreter, _args: Vec<Value>, _line: usize) -> InterpreterResult<Value> {
+    Ok(Value::Number(
+        START.get_or_init(Instant::now).elapsed().as_secs_f32(),
+    ))
+}
+
+// A bigint, since an f32 of today's epoch milliseconds is only good to a couple
+// of minutes.
+fn now(_interpreter: &Interpreter, _args: Vec<Value>, _line: usize) -> InterpreterResult<Value> {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(Value::BigInt(Rc::new(BigInt::from(
+        since_epoch.as_millis(),
+    ))))
+}
+
+fn format_time(
+    _interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    let millis = match &args[0] {
+        Value::BigInt(n) => n.to_f64().unwrap_or(f64::INFINITY),
+        value => f64::try_from(value).map_err(|e| e.add_line_to_type_error(line))?,
+    };
+    let fmt = string_arg(&args, 1, line)?;
+    let secs = (millis / 1000.0).floor() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let secs_of_day = secs.rem_euclid(86_400);
+    let mut out = String::default();
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{:04}", year)),
+            Some('m') => out.push_str(&format!("{:02}", month)),
+            Some('d') => out.push_str(&format!("{:02}", day)),
+            Some('H') => out.push_str(&format!("{:02}", secs_of_day / 3600)),
+            Some('M') => out.push_str(&format!("{:02}", secs_of_day / 60 % 60)),
+            Some('S') => out.push_str(&format!("{:02}", secs_of_day % 60)),
+            Some('%') => out.push('%'),
+            other => {
+                return Err(InterpreterError::RuntimeError {
+                    line,
+                    message: format!(
+                        "Unsupported time format specifier %{}",
+                        other.map_or(String::default(), String::from)
+                    ),
+                })
+            }
+        }
+    }
+    Ok(Value::r#String(out.into()))
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian (year, month, day), after
+// Howard Hinnant's civil_from_days.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn sleep(_interpreter: &Interpreter, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+    let millis = index_arg(&args, 0, line)?;
+    thread::sleep(Duration::from_millis(millis as u64));
+    Ok(Value::Nil)
+}
+
+fn string_arg(args: &[Value], i: usize, line: usize) -> InterpreterResult<String> {
+    String::try_from(&args[i]).map_err(|e| e.add_line_to_type_error(line))
+}
+
+fn bytes_arg(args: &[Value], i: usize, line: usize) -> InterpreterResult<Rc<[u8]>> {
+    match &args[i] {
+        Value::Bytes(b) => Ok(Rc::clone(b)),
+        value => Err(InterpreterError::type_error(
+            String::from("bytes"),
+            String::from(value.type_name()),
+        )
+        .add_line_to_type_error(line)),
+    }
+}
+
+// File contents can be written as text or as raw bytes.
+fn contents_arg(args: &[Value], i: usize, line: usize) -> InterpreterResult<Vec<u8>> {
+    match &args[i] {
+        Value::Bytes(b) => Ok(b.to_vec()),
+        _ => string_arg(args, i, line).map(String::into_bytes),
+    }
+}
+
+fn number_arg(args: &[Value], i: usize, line: usize) -> InterpreterResult<f32> {
+    f32::try_from(&args[i]).map_err(|e| e.add_line_to_type_error(line))
+}
+
+fn index_arg(args: &[Value], i: usize, line: usize) -> InterpreterResult<usize> {
+    let n = number_arg(args, i, line)?;
+    if n < 0.0 || n.fract() != 0.0 {
+        Err(InterpreterError::RuntimeError {
+            line,
+            message: format!("Expected a non-negative whole number index, got {}", n),
+        })
+    } else {
+        Ok(n as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn call(name: &str, args: Vec<Value>) -> InterpreterResult<Value> {
+        let mut interpreter = Interpreter::default();
+        interpreter.set_capabilities(crate::capabilities::Capabilities::all());
+        call_with(&interpreter, name, args)
+    }
+    fn call_with(
+        interpreter: &Interpreter,
+        name: &str,
+        args: Vec<Value>,
+    ) -> InterpreterResult<Value> {
+        let (_, _, _, native, _) = NATIVES.iter().find(|(n, ..)| *n == name).unwrap();
+        native(interpreter, args, 1)
+    }
+    fn string(s: &str) -> Value {
+        Value::r#String(s.into())
+    }
+    #[test]
+    fn natives_strings() -> InterpreterResult<()> {
+        assert_eq!(call("len", vec![string("héllo")])?, Value::Number(5.0));
+        let bytes = |b: &[u8]| Value::Bytes(Rc::from(b));
+        assert_eq!(
+            call("len", vec![bytes("héllo".as_bytes())])?,
+            Value::Number(6.0)
+        );
+        assert_eq!(call("encode", vec![string("hé")])?, bytes("hé".as_bytes()));
+        assert_eq!(
+            call("encode", vec![string("hé"), string("latin1")])?,
+            bytes(&[b'h', 0xe9])
+        );
+        assert_eq!(
+            call("encode", vec![string("hé"), string("ascii")])?,
+            Value::Nil
+        );
+        assert_eq!(
+            call("decode", vec![bytes(&[b'h', 0xe9]), string("latin1")])?,
+            string("hé")
+        );
+        assert_eq!(call("decode", vec![bytes(&[0xff])])?, Value::Nil);
+        assert!(matches!(
+            call("decode", vec![bytes(b"hi"), string("ebcdic")]),
+            Err(InterpreterError::RuntimeError { line: 1, .. })
+        ));
+        assert!(matches!(
+            call("decode", vec![string("hi")]),
+            Err(InterpreterError::Type { line: Some(1), .. })
+        ));
+        assert_eq!(
+            call(
+                "substring",
+                vec![string("héllo"), Value::Number(1.0), Value::Number(3.0)]
+            )?,
+            string("él")
+        );
+        assert_eq!(
+            call("indexOf", vec![string("héllo"), string("llo")])?,
+            Value::Number(2.0)
+        );
+        assert_eq!(
+            call("indexOf", vec![string("héllo"), string("x")])?,
+            Value::Number(-1.0)
+        );
+        assert_eq!(call("trim", vec![string("  hi \n")])?, string("hi"));
+        assert_eq!(call("toUpper", vec![string("héllo")])?, string("HÉLLO"));
+        assert_eq!(call("toLower", vec![string("HÉLLO")])?, string("héllo"));
+        assert_eq!(
+            call("replace", vec![string("a-b-c"), string("-"), string("+")])?,
+            string("a+b+c")
+        );
+        Ok(())
+    }
+    #[test]
+    fn natives_strings_errors() {
+        assert!(matches!(
+            call("len", vec![Value::Number(1.0)]),
+            Err(InterpreterError::Type { line: Some(1), .. })
+        ));
+        assert!(matches!(
+            call(
+                "substring",
+                vec![string("abc"), Value::Number(2.0), Value::Number(5.0)]
+            ),
+            Err(InterpreterError::RuntimeError { line: 1, .. })
+        ));
+        assert!(call(
+            "substring",
+            vec![string("abc"), Value::Number(0.5), Value::Number(1.0)]
+        )
+        .is_err());
+        assert!(call("replace", vec![string("abc"), string(""), string("x")]).is_err());
+    }
+    #[test]
+    fn natives_approx_eq() -> InterpreterResult<()> {
+        let approx = |a: f32, b: f32, eps: f32| {
+            call(
+                "approxEq",
+                vec![Value::Number(a), Value::Number(b), Value::Number(eps)],
+            )
+        };
+        assert_eq!(approx(0.1 + 0.2, 0.3, 1e-6)?, Value::Bool(true));
+        assert_eq!(approx(0.000_05, 0.000_09, 1e-6)?, Value::Bool(false));
+        assert_eq!(approx(1.0, 1.0, 0.0)?, Value::Bool(true));
+        assert_eq!(approx(f32::NAN, f32::NAN, 1.0)?, Value::Bool(false));
+        assert!(approx(1.0, 1.0, -1.0).is_err());
+        assert!(matches!(
+            call(
+                "approxEq",
+                vec![string("1"), Value::Number(1.0), Value::Number(0.1)]
+            ),
+            Err(InterpreterError::Type { line: Some(1), .. })
+        ));
+        Ok(())
+    }
+    #[test]
+    fn natives_type() -> InterpreterResult<()> {
+        assert_eq!(call("type", vec![Value::Number(1.0)])?, string("number"));
+        assert_eq!(call("type", vec![string("hi")])?, string("string"));
+        assert_eq!(call("type", vec![Value::Bool(true)])?, string("bool"));
+        assert_eq!(call("type", vec![Value::Nil])?, string("nil"));
+        let len = Interpreter::default().lookup("len").unwrap();
+        assert_eq!(call("type", vec![len])?, string("function"));
+        Ok(())
+    }
+    #[test]
+    fn natives_conversions() -> InterpreterResult<()> {
+        assert_eq!(call("str", vec![Value::Number(3.0)])?, string("3"));
+        assert_eq!(call("str", vec![Value::Nil])?, string("nil"));
+        assert_eq!(call("str", vec![string("hi")])?, string("hi"));
+        assert_eq!(call("num", vec![string(" 42 ")])?, Value::Number(42.0));
+        assert_eq!(call("num", vec![string("1.5")])?, Value::Number(1.5));
+        assert_eq!(call("num", vec![Value::Number(2.0)])?, Value::Number(2.0));
+        assert_eq!(call("num", vec![string("abc")])?, Value::Nil);
+        assert_eq!(call("num", vec![string("inf")])?, Value::Nil);
+        let big = |n: i64| Value::BigInt(Rc::new(BigInt::from(n)));
+        assert_eq!(call("num", vec![big(7)])?, Value::Number(7.0));
+        assert_eq!(call("bigint", vec![Value::Number(12.0)])?, big(12));
+        assert_eq!(call("bigint", vec![Value::Number(1.5)])?, Value::Nil);
+        assert_eq!(call("bigint", vec![string(" -42 ")])?, big(-42));
+        assert_eq!(call("bigint", vec![string("4.2")])?, Value::Nil);
+        assert!(matches!(
+            call("num", vec![Value::Bool(true)]),
+            Err(InterpreterError::Type { .. })
+        ));
+        Ok(())
+    }
+    #[test]
+    fn natives_assert() {
+        assert!(matches!(
+            call("assert", vec![Value::Bool(true)]),
+            Ok(Value::Nil)
+        ));
+        assert!(matches!(
+            call("assert", vec![Value::Nil]),
+            Err(InterpreterError::RuntimeError { line: 1, message }) if message == "Assertion failed"
+        ));
+        assert!(matches!(
+            call("assert", vec![Value::Bool(false), string("oops")]),
+            Err(InterpreterError::RuntimeError { line: 1, message })
+                if message == "Assertion failed: oops"
+        ));
+    }
+    #[test]
+    fn natives_exit() {
+        assert!(matches!(
+            call("exit", vec![Value::Number(3.0)]),
+            Err(InterpreterError::Exit { code: 3 })
+        ));
+        assert!(call("exit", vec![Value::Number(-1.0)]).is_err());
+    }
+    #[test]
+    fn natives_env() -> InterpreterResult<()> {
+        let name = string(&format!("RLOX_NATIVES_{}", std::process::id()));
+        assert_eq!(call("getenv", vec![name.clone()])?, Value::Nil);
+        call("setenv", vec![name.clone(), string("on")])?;
+        assert_eq!(call("getenv", vec![name.clone()])?, string("on"));
+        assert!(call("setenv", vec![string("A=B"), string("on")]).is_err());
+        let mut sandboxed = Interpreter::default();
+        sandboxed.set_capabilities(crate::capabilities::Capabilities::none());
+        assert!(matches!(
+            call_with(&sandboxed, "getenv", vec![name]),
+            Err(InterpreterError::CapabilityDenied { line: 1, .. })
+        ));
+        Ok(())
+    }
+    #[test]
+    fn natives_time() -> InterpreterResult<()> {
+        let format =
+            |millis: f32, fmt: &str| call("formatTime", vec![Value::Number(millis), string(fmt)]);
+        assert_eq!(
+            format(0.0, "%Y-%m-%d %H:%M:%S")?,
+            string("1970-01-01 00:00:00")
+        );
+        assert_eq!(
+            format(951_782_400_000.0, "%d/%m/%Y %%")?,
+            string("29/02/2000 %")
+        );
+        assert_eq!(format(-1000.0, "%Y %H:%M:%S")?, string("1969 23:59:59"));
+        assert!(format(0.0, "%q").is_err());
+        let now = match call("now", vec![])? {
+            Value::BigInt(n) => n,
+            other => panic!("{:?}", other),
+        };
+        assert!(*now > BigInt::from(1_700_000_000_000_u64));
+        match call("now", vec![])? {
+            Value::BigInt(later) => assert!(&*later - &*now < BigInt::from(60_000)),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(
+            call(
+                "formatTime",
+                vec![
+                    Value::BigInt(Rc::new(BigInt::from(951_782_400_000_u64))),
+                    string("%Y-%m-%d")
+                ]
+            )?,
+            string("2000-02-29")
+        );
+        let before = f32::try_from(&call("clock", vec![])?)?;
+        call("sleep", vec![Value::Number(10.0)])?;
+        assert!(f32::try_from(&call("clock", vec![])?)? > before);
+        Ok(())
+    }
+    #[cfg(unix)]
+    #[test]
+    fn natives_exec() -> InterpreterResult<()> {
+        let field =
+            |result: &Value, name: &str| call("getField", vec![result.clone(), string(name)]);
+        let result = call(
+            "exec",
+            vec![string("echo"), string("hello  world"), string("again")],
+        )?;
+        assert_eq!(field(&result, "stdout")?, string("hello  world again\n"));
+        assert_eq!(field(&result, "stderr")?, string(""));
+        assert_eq!(field(&result, "code")?, Value::Number(0.0));
+        assert_eq!(field(&result, "missing")?, Value::Nil);
+        let result = call(
+            "exec",
+            vec![string("sh"), string("-c"), string("echo oops >&2; exit 3")],
+        )?;
+        assert_eq!(field(&result, "stdout")?, string(""));
+        assert_eq!(field(&result, "stderr")?, string("oops\n"));
+        assert_eq!(field(&result, "code")?, Value::Number(3.0));
+        assert!(matches!(
+            call("exec", vec![string("echo"), Value::Number(1.0)]),
+            Err(InterpreterError::Type { line: Some(1), .. })
+        ));
+        assert!(matches!(
+            call("getField", vec![string("echo"), string("stdout")]),
+            Err(InterpreterError::Type { line: Some(1), .. })
+        ));
+        assert!(call("exec", vec![string("rlox-no-such-command")]).is_err());
+        let mut sandboxed = Interpreter::default();
+        sandboxed.set_capabilities(crate::capabilities::Capabilities::none());
+        assert!(matches!(
+            call_with(&sandboxed, "exec", vec![string("echo")]),
+            Err(InterpreterError::CapabilityDenied { line: 1, .. })
+        ));
+        Ok(())
+    }
+    #[test]
+    fn natives_files() -> InterpreterResult<()> {
+        let path = std::env::temp_dir().join(format!("rlox-natives-{}.txt", std::process::id()));
+        let path = string(&path.to_string_lossy());
+        assert_eq!(call("fileExists", vec![path.clone()])?, Value::Bool(false));
+        assert!(call("readFile", vec![path.clone()]).is_err());
+        call("writeFile", vec![path.clone(), string("one\n")])?;
+        call("appendFile", vec![path.clone(), string("two\n")])?;
+        assert_eq!(call("fileExists", vec![path.clone()])?, Value::Bool(true));
+        assert_eq!(call("readFile", vec![path.clone()])?, string("one\ntwo\n"));
+        let raw = Value::Bytes(Rc::from(&[0u8, 0xff, b'\n'][..]));
+        call("writeFile", vec![path.clone(), raw.clone()])?;
+        assert_eq!(call("readBytes", vec![path.clone()])?, raw);
+        assert!(call("readFile", vec![path.clone()]).is_err());
+        let mut sandboxed = Interpreter::default();
+        sandboxed.set_capabilities(crate::capabilities::Capabilities::none());
+        assert!(matches!(
+            call_with(&sandboxed, "readFile", vec![path.clone()]),
+            Err(InterpreterError::CapabilityDenied { line: 1, .. })
+        ));
+        fs::remove_file(String::try_from(&path)?)?;
+        Ok(())
+    }
+}
@@ -1,179 +1,554 @@
+use crate::doc;
 use crate::errors::{InterpreterError, InterpreterResult};
 use crate::expr::Expr;
+use crate::limits::DEFAULT_MAX_NESTING;
+use crate::node::NodeId;
 use crate::stmt::Stmt;
+use crate::suggest;
 use crate::token::Token;
+use std::ops::Range;
 
-pub fn parse(tokens: Vec<Token>) -> (Option<Stmt>, Vec<InterpreterError>) {
+const MAX_ARGS: usize = 255;
+// Operators in a row don't deepen the parser's stack, so they don't spend the
+// nesting budget. They do build a left-leaning tree as deep as the chain is
+// long, which the resolver and interpreter recurse through; a level of that
+// costs far less stack than a nested group, so a chain may run to this many
+// times the levels that remain, counting its own.
+const CHAIN_PER_LEVEL: usize = 4;
+// Only keywords the grammar can parse a statement from; class, for and if
+// are reserved but not implemented.
+const STATEMENT_KEYWORDS: [&str; 5] = ["fun", "print", "return", "var", "while"];
+
+pub fn parse(tokens: Vec<Token>) -> (Vec<Stmt>, Vec<InterpreterError>) {
+    parse_with(tokens, DEFAULT_MAX_NESTING)
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "parse", level = "debug", skip_all, fields(tokens = tokens.len()))
+)]
+pub(crate) fn parse_with(
+    tokens: Vec<Token>,
+    max_nesting: usize,
+) -> (Vec<Stmt>, Vec<InterpreterError>) {
     let mut pos: usize = 0;
     let mut errors: Vec<InterpreterError> = Vec::default();
-    let mut final_stmt: Option<Stmt> = None;
-    let cleaned = clean_tokens(tokens);
-    loop {
-        match declaration(&cleaned, &mut pos, 0) {
-            Ok(stmt) => {
-                final_stmt.replace(stmt);
-                break;
-            }
+    let mut stmts: Vec<Stmt> = Vec::default();
+    let mut docs = doc::comments(&tokens);
+    let cleaned: Vec<Token> = tokens.into_iter().filter(|t| !t.is_trivia()).collect();
+    while !is_at_end(&cleaned, &pos) {
+        match declaration(&cleaned, &mut pos, 0, max_nesting) {
+            Ok(stmt) => stmts.push(stmt),
             Err(err) => {
                 errors.push(err);
-                if synchronize(&cleaned, &mut pos) {
-                    continue;
-                } else {
+                if !synchronize(&cleaned, &mut pos) {
                     break;
                 }
             }
         }
     }
-    (final_stmt, errors)
+    doc::attach(&mut stmts, &mut docs);
+    (stmts, errors)
+}
+
+// For the CST: each top-level statement with the range of tokens it consumed.
+// There's no recovery, since a lossless tree of broken code isn't useful.
+pub(crate) fn parse_ranges(tokens: &Vec<Token>) -> InterpreterResult<Vec<(Stmt, Range<usize>)>> {
+    let mut pos: usize = 0;
+    let mut stmts = Vec::default();
+    while !is_at_end(tokens, &pos) {
+        let start = pos;
+        let stmt = declaration(tokens, &mut pos, 0, DEFAULT_MAX_NESTING)?;
+        stmts.push((stmt, start..pos));
+    }
+    Ok(stmts)
 }
 
-fn declaration(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Stmt> {
+pub(crate) fn parse_stream<I>(tokens: I) -> (Vec<Stmt>, Vec<InterpreterError>)
+where
+    I: IntoIterator<Item = InterpreterResult<Token>>,
+{
+    let mut stmts: Vec<Stmt> = Vec::default();
+    let mut errors: Vec<InterpreterError> = Vec::default();
+    let mut chunk: Vec<Token> = Vec::default();
+    let mut depth: isize = 0;
+    for token in tokens {
+        let token = match token {
+            Ok(token) => token,
+            Err(err) => {
+                errors.push(err);
+                continue;
+            }
+        };
+        match token {
+            Token::LeftBrace { .. } | Token::LeftParen { .. } => depth += 1,
+            Token::RightBrace { .. } | Token::RightParen { .. } => depth -= 1,
+            _ => {}
+        }
+        let boundary = depth <= 0
+            && matches!(
+                token,
+                Token::Semicolon { .. } | Token::RightBrace { .. } | Token::Eof { .. }
+            );
+        let line = token.get_line().unwrap_or(0);
+        chunk.push(token);
+        if boundary {
+            if !matches!(chunk.last(), Some(Token::Eof { .. })) {
+                chunk.push(Token::Eof { line });
+            }
+            let (parsed, errs) = parse(std::mem::take(&mut chunk));
+            stmts.extend(parsed);
+            errors.extend(errs);
+            depth = 0;
+        }
+    }
+    // An unclosed bracket keeps the last chunk open to the end of input; parse
+    // what's left anyway so the error is reported instead of the code dropped.
+    if chunk
+        .iter()
+        .any(|t| !t.is_trivia() && !matches!(t, Token::Eof { .. }))
+    {
+        if !matches!(chunk.last(), Some(Token::Eof { .. })) {
+            let line = chunk.last().and_then(Token::get_line).unwrap_or(0);
+            chunk.push(Token::Eof { line });
+        }
+        let (parsed, errs) = parse(chunk);
+        stmts.extend(parsed);
+        errors.extend(errs);
+    }
+    (stmts, errors)
+}
+
+fn declaration(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Stmt> {
     if match_var(tokens, pos) {
-        variable(tokens, pos, line)
+        variable(tokens, pos, line, nesting)
+    } else if match_fun(tokens, pos) {
+        function(tokens, pos, line, nesting)
     } else {
-        statement(tokens, pos, line)
+        statement(tokens, pos, line, nesting)
     }
 }
 
-fn variable(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Stmt> {
-    let name = identifier(tokens, pos, line)?;
+fn variable(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Stmt> {
+    let name = identifier(tokens, pos, line, "variable name")?;
     let initializer = if match_assign(tokens, pos) {
-        Some(Box::new(expression(tokens, pos, line)?))
+        Some(Box::new(expression(tokens, pos, line, nesting)?))
     } else {
         None
     };
-    expect_semicolon(tokens, pos, line)?;
-    Ok(Stmt::Variable { name, initializer })
+    expect_semicolon(tokens, pos, line, "after variable declaration")?;
+    Ok(Stmt::Variable {
+        id: NodeId::next(),
+        name,
+        initializer,
+        doc: None,
+    })
 }
 
-fn statement(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Stmt> {
+fn function(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Stmt> {
+    let name = identifier(tokens, pos, line, "function name")?;
+    expect_left_paren(tokens, pos, line, "after function name")?;
+    let mut params = Vec::default();
+    if !check_right_paren(tokens, pos) {
+        loop {
+            if params.len() >= MAX_ARGS {
+                return Err(InterpreterError::SyntaxError {
+                    line,
+                    message: format!("Can't have more than {} parameters", MAX_ARGS),
+                });
+            }
+            params.push(identifier(tokens, pos, line, "parameter name")?);
+            if !match_comma(tokens, pos) {
+                break;
+            }
+        }
+    }
+    expect_right_paren(tokens, pos, line, "after parameters")?;
+    if !match_block(tokens, pos) {
+        return Err(unexpected(tokens, pos, line, "'{' before function body"));
+    }
+    let body = block(tokens, pos, line, nest(tokens, pos, line, nesting)?)?;
+    Ok(Stmt::Function {
+        id: NodeId::next(),
+        name,
+        params,
+        body,
+        doc: None,
+    })
+}
+
+fn statement(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Stmt> {
+    // Each kind of statement is parsed in its own function so that nested
+    // blocks only carry this small frame per level.
     if match_print(tokens, pos) {
-        let expr = expression(tokens, pos, line)?;
-        expect_semicolon(tokens, pos, line)?;
-        Ok(Stmt::Print {
-            expr: Box::new(expr),
-        })
+        print_statement(tokens, pos, line, nesting)
     } else if match_block(tokens, pos) {
-        let stmts = block(tokens, pos, line)?;
-        expect_semicolon(tokens, pos, line)?;
-        Ok(Stmt::Block { stmts })
+        let stmts = block(tokens, pos, line, nest(tokens, pos, line, nesting)?)?;
+        Ok(Stmt::Block {
+            id: NodeId::next(),
+            stmts,
+        })
+    } else if match_while(tokens, pos) {
+        while_statement(tokens, pos, line, nesting)
+    } else if match_return(tokens, pos) {
+        return_statement(tokens, pos, line, nesting)
+    } else {
+        expression_statement(tokens, pos, line, nesting)
+    }
+}
+
+fn print_statement(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Stmt> {
+    let keyword = previous(tokens, pos, line)?.clone();
+    let expr = expression(tokens, pos, line, nesting)?;
+    expect_semicolon(tokens, pos, line, "after value")?;
+    Ok(Stmt::Print {
+        id: NodeId::next(),
+        keyword,
+        expr: Box::new(expr),
+    })
+}
+
+fn while_statement(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Stmt> {
+    expect_left_paren(tokens, pos, line, "after 'while'")?;
+    let condition = expression(tokens, pos, line, nesting)?;
+    expect_right_paren(tokens, pos, line, "after condition")?;
+    let body = statement(tokens, pos, line, nesting)?;
+    Ok(Stmt::While {
+        id: NodeId::next(),
+        condition: Box::new(condition),
+        body: Box::new(body),
+    })
+}
+
+fn return_statement(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Stmt> {
+    let keyword = previous(tokens, pos, line)?.clone();
+    let value = if check_semicolon(tokens, pos) {
+        None
     } else {
-        let expr = expression(tokens, pos, line)?;
-        expect_semicolon(tokens, pos, line)?;
-        Ok(Stmt::Expr {
-            expr: Box::new(expr),
+        Some(Box::new(expression(tokens, pos, line, nesting)?))
+    };
+    expect_semicolon(tokens, pos, line, "after return value")?;
+    Ok(Stmt::Return {
+        id: NodeId::next(),
+        keyword,
+        value,
+    })
+}
+
+fn expression_statement(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Stmt> {
+    let start = tokens.get(*pos).cloned();
+    let expr = expression(tokens, pos, line, nesting)
+        .and_then(|expr| {
+            expect_semicolon(tokens, pos, line, "after expression")?;
+            Ok(expr)
         })
+        .map_err(|err| keyword_hint(err, start.as_ref()))?;
+    Ok(Stmt::Expr {
+        id: NodeId::next(),
+        expr: Box::new(expr),
+    })
+}
+
+// `pritn x;` fails as an expression statement; if it started with something
+// close to a keyword, that's the likelier mistake.
+fn keyword_hint(err: InterpreterError, start: Option<&Token>) -> InterpreterError {
+    let keyword = match start {
+        Some(Token::Identifier { lexeme, .. }) => suggest::closest(lexeme, STATEMENT_KEYWORDS),
+        _ => None,
+    };
+    match (err, keyword) {
+        (InterpreterError::SyntaxError { line, message }, Some(keyword)) => {
+            InterpreterError::SyntaxError {
+                line,
+                message: format!("{}; did you mean '{}'?", message, keyword),
+            }
+        }
+        (err, _) => err,
     }
 }
 
-fn block(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Vec<Stmt>> {
+fn block(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Vec<Stmt>> {
     let mut statements = Vec::default();
     while !check_right_brace(tokens, pos) {
-        statements.push(declaration(tokens, pos, line)?);
+        statements.push(declaration(tokens, pos, line, nesting)?);
     }
-    expect_right_brace(tokens, pos, line)?;
+    expect_right_brace(tokens, pos, line, "after block")?;
     Ok(statements)
 }
 
-fn expression(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Expr> {
-    assign(tokens, pos, line)
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub(crate) enum Precedence {
+    None,
+    Assignment,
+    Coalesce,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
 }
 
-fn assign(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Expr> {
-    let expr = equality(tokens, pos, line)?;
-    if match_assign(tokens, pos) {
-        let equals = previous(tokens, pos, line)?;
-        let value = assign(tokens, pos, line)?;
-        match expr {
-            Expr::Variable { name, .. } => Ok(Expr::Assign {
-                name,
-                value: Box::new(value),
-            }),
-            _ => Err(InterpreterError::SyntaxError {
-                line,
-                message: format!("Invalid assignment target {:?}", equals),
-            }),
+impl Precedence {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            Self::None => Self::Assignment,
+            Self::Assignment => Self::Coalesce,
+            Self::Coalesce => Self::Equality,
+            Self::Equality => Self::Comparison,
+            Self::Comparison => Self::Term,
+            Self::Term => Self::Factor,
+            Self::Factor => Self::Unary,
+            Self::Unary | Self::Call => Self::Call,
         }
-    } else {
-        Ok(expr)
     }
 }
 
-fn equality(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Expr> {
-    let mut expr = comparison(tokens, pos, line)?;
-    while match_eq(tokens, pos) {
-        let operator = previous(tokens, pos, line)?;
-        let right = comparison(tokens, pos, line)?;
-        let new_expr = Expr::Binary {
-            left: Box::new(expr),
-            operator: operator.clone(),
-            right: Box::new(right),
-        };
-        expr = new_expr;
+pub(crate) fn infix_precedence(token: &Token) -> Precedence {
+    match token {
+        Token::Equal { .. } => Precedence::Assignment,
+        Token::QuestionQuestion { .. } => Precedence::Coalesce,
+        Token::BangEqual { .. } | Token::EqualEqual { .. } => Precedence::Equality,
+        Token::Greater { .. }
+        | Token::GreaterEqual { .. }
+        | Token::Less { .. }
+        | Token::LessEqual { .. } => Precedence::Comparison,
+        Token::Minus { .. } | Token::Plus { .. } => Precedence::Term,
+        Token::Slash { .. } | Token::Star { .. } => Precedence::Factor,
+        Token::LeftParen { .. } | Token::LeftBracket { .. } => Precedence::Call,
+        _ => Precedence::None,
     }
-    Ok(expr)
 }
 
-fn comparison(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Expr> {
-    let mut expr = term(tokens, pos, line)?;
-    while match_comp(tokens, pos) {
-        let operator = previous(tokens, pos, line)?;
-        let right = term(tokens, pos, line)?;
-        let new_expr = Expr::Binary {
-            left: Box::new(expr),
-            operator: operator.clone(),
-            right: Box::new(right),
-        };
-        expr = new_expr;
+fn expression(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Expr> {
+    parse_precedence(tokens, pos, line, Precedence::Assignment, nesting)
+}
+
+fn parse_precedence(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    min: Precedence,
+    nesting: usize,
+) -> InterpreterResult<Expr> {
+    let nesting = nest(tokens, pos, line, nesting)?;
+    let mut expr = prefix(tokens, pos, line, nesting)?;
+    let mut chain = (nesting + 1).saturating_mul(CHAIN_PER_LEVEL);
+    while let Some(token) = tokens.get(*pos) {
+        let precedence = infix_precedence(token);
+        if precedence == Precedence::None || precedence < min {
+            break;
+        }
+        chain = chain
+            .checked_sub(1)
+            .ok_or_else(|| InterpreterError::SyntaxError {
+                line: token.get_line().unwrap_or(line),
+                message: String::from("Too many operators in a row"),
+            })?;
+        *pos += 1;
+        expr = infix(tokens, pos, line, expr, token, precedence, nesting)?;
     }
     Ok(expr)
 }
 
-fn term(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Expr> {
-    let mut expr = factor(tokens, pos, line)?;
-    while match_term(tokens, pos) {
-        let operator = previous(tokens, pos, line)?;
-        let right = factor(tokens, pos, line)?;
-        let new_expr = Expr::Binary {
-            left: Box::new(expr),
-            operator: operator.clone(),
-            right: Box::new(right),
-        };
-        expr = new_expr;
+fn nest(tokens: &[Token], pos: &usize, line: usize, nesting: usize) -> InterpreterResult<usize> {
+    nesting
+        .checked_sub(1)
+        .ok_or_else(|| InterpreterError::SyntaxError {
+            line: tokens.get(*pos).and_then(Token::get_line).unwrap_or(line),
+            message: String::from("Too deeply nested"),
+        })
+}
+
+fn prefix(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Expr> {
+    match tokens.get(*pos) {
+        Some(operator @ (Token::Bang { .. } | Token::Minus { .. })) => {
+            *pos += 1;
+            let right = parse_precedence(tokens, pos, line, Precedence::Unary, nesting)?;
+            Ok(Expr::Unary {
+                id: NodeId::next(),
+                operator: operator.clone(),
+                right: Box::new(right),
+            })
+        }
+        _ => primary(tokens, pos, line, nesting),
     }
-    Ok(expr)
 }
 
-fn factor(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Expr> {
-    let mut expr = unary(tokens, pos, line)?;
-    while match_factor(tokens, pos) {
-        let operator = previous(tokens, pos, line)?;
-        let right = unary(tokens, pos, line)?;
-        let new_expr = Expr::Binary {
-            left: Box::new(expr),
-            operator: operator.clone(),
-            right: Box::new(right),
-        };
-        expr = new_expr;
+fn infix(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    left: Expr,
+    operator: &Token,
+    precedence: Precedence,
+    nesting: usize,
+) -> InterpreterResult<Expr> {
+    match operator {
+        Token::LeftParen { .. } => finish_call(tokens, pos, line, left, nesting),
+        Token::LeftBracket { .. } => finish_index(tokens, pos, line, left, nesting),
+        Token::Equal { .. } => finish_assignment(tokens, pos, line, left, operator, nesting),
+        Token::QuestionQuestion { .. } => {
+            let right = parse_precedence(tokens, pos, line, precedence.next(), nesting)?;
+            Ok(Expr::Logical {
+                id: NodeId::next(),
+                left: Box::new(left),
+                operator: operator.clone(),
+                right: Box::new(right),
+            })
+        }
+        _ => {
+            let right = parse_precedence(tokens, pos, line, precedence.next(), nesting)?;
+            Ok(Expr::Binary {
+                id: NodeId::next(),
+                left: Box::new(left),
+                operator: operator.clone(),
+                right: Box::new(right),
+            })
+        }
     }
-    Ok(expr)
 }
 
-fn unary(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Expr> {
-    if match_unary(tokens, pos) {
-        let operator = previous(tokens, pos, line)?;
-        let right = unary(tokens, pos, line)?;
-        Ok(Expr::Unary {
-            operator: operator.clone(),
-            right: Box::new(right),
-        })
-    } else {
-        primary(tokens, pos, line)
+fn finish_index(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    object: Expr,
+    nesting: usize,
+) -> InterpreterResult<Expr> {
+    let index = parse_precedence(tokens, pos, line, Precedence::Assignment, nesting)?;
+    let bracket = expect_right_bracket(tokens, pos, line, "after index")?;
+    Ok(Expr::Index {
+        id: NodeId::next(),
+        object: Box::new(object),
+        bracket,
+        index: Box::new(index),
+    })
+}
+
+fn finish_assignment(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    target: Expr,
+    operator: &Token,
+    nesting: usize,
+) -> InterpreterResult<Expr> {
+    let value = parse_precedence(tokens, pos, line, Precedence::Assignment, nesting)?;
+    match target {
+        Expr::Variable { name, .. } if !matches!(name, Token::This { .. }) => Ok(Expr::Assign {
+            id: NodeId::next(),
+            name,
+            value: Box::new(value),
+        }),
+        _ => Err(InterpreterError::SyntaxError {
+            line,
+            message: format!("Invalid assignment target {:?}", operator),
+        }),
     }
 }
 
-fn primary(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResult<Expr> {
-    let t = tokens.get(*pos).ok_or(InterpreterError::Parse { line })?;
+fn finish_call(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    callee: Expr,
+    nesting: usize,
+) -> InterpreterResult<Expr> {
+    let mut arguments = Vec::default();
+    if !check_right_paren(tokens, pos) {
+        loop {
+            if arguments.len() >= MAX_ARGS {
+                return Err(InterpreterError::SyntaxError {
+                    line,
+                    message: format!("Can't have more than {} arguments", MAX_ARGS),
+                });
+            }
+            arguments.push(parse_precedence(
+                tokens,
+                pos,
+                line,
+                Precedence::Assignment,
+                nesting,
+            )?);
+            if !match_comma(tokens, pos) {
+                break;
+            }
+        }
+    }
+    let paren = expect_right_paren(tokens, pos, line, "after arguments")?;
+    Ok(Expr::Call {
+        id: NodeId::next(),
+        callee: Box::new(callee),
+        paren,
+        arguments,
+    })
+}
+
+fn primary(
+    tokens: &Vec<Token>,
+    pos: &mut usize,
+    line: usize,
+    nesting: usize,
+) -> InterpreterResult<Expr> {
+    let t = tokens
+        .get(*pos)
+        .ok_or_else(|| unexpected(tokens, pos, line, "expression"))?;
     match t {
         Token::True { .. } => {
             *pos += 1;
@@ -191,52 +566,55 @@ fn primary(tokens: &Vec<Token>, pos: &mut usize, line: usize) -> InterpreterResu
             *pos += 1;
             Ok(Expr::literal_num(*literal))
         }
+        Token::BigInt { literal, .. } => {
+            *pos += 1;
+            Ok(Expr::literal_bigint(literal.clone()))
+        }
         Token::r#String { literal, .. } => {
             *pos += 1;
-            Ok(Expr::literal_string(literal))
+            Ok(Expr::literal_string(literal.as_str()))
+        }
+        Token::Bytes { literal, .. } => {
+            *pos += 1;
+            Ok(Expr::literal_bytes(literal.as_slice()))
         }
         Token::LeftParen { line } => {
             *pos += 1;
-            let expr = expression(tokens, pos, *line)?;
-            let next = tokens
-                .get(*pos)
-                .ok_or(InterpreterError::Parse { line: *line })?;
-            if let Token::RightParen { .. } = *next {
-                *pos += 1;
-                Ok(Expr::Grouping {
-                    expression: Box::new(expr),
-                })
-            } else {
-                Err(InterpreterError::Parse { line: *line })
-            }
+            let expr = parse_precedence(tokens, pos, *line, Precedence::Assignment, nesting)?;
+            expect_right_paren(tokens, pos, *line, "after expression")?;
+            Ok(Expr::Grouping {
+                id: NodeId::next(),
+                expression: Box::new(expr),
+            })
         }
-        ident @ Token::Identifier { .. } => {
+        ident @ (Token::Identifier { .. } | Token::This { .. }) => {
             *pos += 1;
             Ok(Expr::Variable {
+                id: NodeId::next(),
                 name: ident.clone(),
             })
         }
-        t => Err(InterpreterError::Parse {
-            line: t.get_line().unwrap_or(line),
-        }),
+        _ => Err(unexpected(tokens, pos, line, "expression")),
     }
 }
 
-fn identifier(tokens: &[Token], pos: &mut usize, line: usize) -> InterpreterResult<Token> {
+fn identifier(
+    tokens: &[Token],
+    pos: &mut usize,
+    line: usize,
+    expected: &str,
+) -> InterpreterResult<Token> {
     if let Some(ident @ Token::Identifier { .. }) = tokens.get(*pos) {
         *pos += 1;
         Ok(ident.clone())
     } else {
-        Err(InterpreterError::SyntaxError {
-            line,
-            message: "Expected variable name".into(),
-        })
+        Err(unexpected(tokens, pos, line, expected))
     }
 }
 
-fn match_eq(tokens: &[Token], pos: &mut usize) -> bool {
-    tokens.get(*pos).map_or(false, |t| match t {
-        Token::BangEqual { .. } | Token::EqualEqual { .. } => {
+fn match_print(tokens: &[Token], pos: &mut usize) -> bool {
+    tokens.get(*pos).is_some_and(|t| match t {
+        Token::Print { .. } => {
             *pos += 1;
             true
         }
@@ -244,12 +622,9 @@ fn match_eq(tokens: &[Token], pos: &mut usize) -> bool {
     })
 }
 
-fn match_comp(tokens: &[Token], pos: &mut usize) -> bool {
-    tokens.get(*pos).map_or(false, |t| match t {
-        Token::Greater { .. }
-        | Token::GreaterEqual { .. }
-        | Token::Less { .. }
-        | Token::LessEqual { .. } => {
+fn match_var(tokens: &[Token], pos: &mut usize) -> bool {
+    tokens.get(*pos).is_some_and(|t| match t {
+        Token::Var { .. } => {
             *pos += 1;
             true
         }
@@ -257,9 +632,9 @@ fn match_comp(tokens: &[Token], pos: &mut usize) -> bool {
     })
 }
 
-fn match_term(tokens: &[Token], pos: &mut usize) -> bool {
-    tokens.get(*pos).map_or(false, |t| match t {
-        Token::Minus { .. } | Token::Plus { .. } => {
+fn match_while(tokens: &[Token], pos: &mut usize) -> bool {
+    tokens.get(*pos).is_some_and(|t| match t {
+        Token::While { .. } => {
             *pos += 1;
             true
         }
@@ -267,9 +642,9 @@ fn match_term(tokens: &[Token], pos: &mut usize) -> bool {
     })
 }
 
-fn match_factor(tokens: &[Token], pos: &mut usize) -> bool {
-    tokens.get(*pos).map_or(false, |t| match t {
-        Token::Slash { .. } | Token::Star { .. } => {
+fn match_fun(tokens: &[Token], pos: &mut usize) -> bool {
+    tokens.get(*pos).is_some_and(|t| match t {
+        Token::Fun { .. } => {
             *pos += 1;
             true
         }
@@ -277,9 +652,9 @@ fn match_factor(tokens: &[Token], pos: &mut usize) -> bool {
     })
 }
 
-fn match_unary(tokens: &[Token], pos: &mut usize) -> bool {
-    tokens.get(*pos).map_or(false, |t| match t {
-        Token::Bang { .. } | Token::Minus { .. } => {
+fn match_return(tokens: &[Token], pos: &mut usize) -> bool {
+    tokens.get(*pos).is_some_and(|t| match t {
+        Token::Return { .. } => {
             *pos += 1;
             true
         }
@@ -287,19 +662,9 @@ fn match_unary(tokens: &[Token], pos: &mut usize) -> bool {
     })
 }
 
-fn match_print(tokens: &[Token], pos: &mut usize) -> bool {
-    tokens.get(*pos).map_or(false, |t| match t {
-        Token::Print { .. } => {
-            *pos += 1;
-            true
-        }
-        _ => false,
-    })
-}
-
-fn match_var(tokens: &[Token], pos: &mut usize) -> bool {
-    tokens.get(*pos).map_or(false, |t| match t {
-        Token::Var { .. } => {
+fn match_comma(tokens: &[Token], pos: &mut usize) -> bool {
+    tokens.get(*pos).is_some_and(|t| match t {
+        Token::Comma { .. } => {
             *pos += 1;
             true
         }
@@ -308,7 +673,7 @@ fn match_var(tokens: &[Token], pos: &mut usize) -> bool {
 }
 
 fn match_assign(tokens: &[Token], pos: &mut usize) -> bool {
-    tokens.get(*pos).map_or(false, |t| match t {
+    tokens.get(*pos).is_some_and(|t| match t {
         Token::Equal { .. } => {
             *pos += 1;
             true
@@ -318,7 +683,7 @@ fn match_assign(tokens: &[Token], pos: &mut usize) -> bool {
 }
 
 fn match_block(tokens: &[Token], pos: &mut usize) -> bool {
-    tokens.get(*pos).map_or(false, |t| match t {
+    tokens.get(*pos).is_some_and(|t| match t {
         Token::LeftBrace { .. } => {
             *pos += 1;
             true
@@ -330,34 +695,113 @@ fn match_block(tokens: &[Token], pos: &mut usize) -> bool {
 fn check_right_brace(tokens: &[Token], pos: &usize) -> bool {
     tokens
         .get(*pos)
-        .map_or(false, |t| matches!(t, Token::RightBrace { .. }))
+        .is_some_and(|t| matches!(t, Token::RightBrace { .. }))
+}
+
+fn check_right_paren(tokens: &[Token], pos: &usize) -> bool {
+    tokens
+        .get(*pos)
+        .is_some_and(|t| matches!(t, Token::RightParen { .. }))
+}
+
+fn check_semicolon(tokens: &[Token], pos: &usize) -> bool {
+    tokens
+        .get(*pos)
+        .is_some_and(|t| matches!(t, Token::Semicolon { .. }))
+}
+
+fn is_at_end(tokens: &[Token], pos: &usize) -> bool {
+    tokens
+        .get(*pos)
+        .is_none_or(|t| matches!(t, Token::Eof { .. }))
 }
 
 fn previous<'a>(tokens: &'a [Token], pos: &usize, line: usize) -> InterpreterResult<&'a Token> {
     tokens.get(*pos - 1).ok_or(InterpreterError::Parse { line })
 }
 
-fn expect_semicolon(tokens: &[Token], pos: &mut usize, line: usize) -> InterpreterResult<()> {
+fn expect_semicolon(
+    tokens: &[Token],
+    pos: &mut usize,
+    line: usize,
+    after: &str,
+) -> InterpreterResult<()> {
     if let Some(Token::Semicolon { .. }) = tokens.get(*pos) {
         *pos += 1;
         Ok(())
     } else {
-        Err(InterpreterError::SyntaxError {
-            line,
-            message: "Expected semicolon".into(),
-        })
+        Err(unexpected(tokens, pos, line, &format!("';' {}", after)))
     }
 }
 
-fn expect_right_brace(tokens: &[Token], pos: &mut usize, line: usize) -> InterpreterResult<()> {
+fn expect_right_brace(
+    tokens: &[Token],
+    pos: &mut usize,
+    line: usize,
+    after: &str,
+) -> InterpreterResult<()> {
     if let Some(Token::RightBrace { .. }) = tokens.get(*pos) {
         *pos += 1;
         Ok(())
     } else {
-        Err(InterpreterError::SyntaxError {
-            line,
-            message: "Expected right brace".into(),
-        })
+        Err(unexpected(tokens, pos, line, &format!("'}}' {}", after)))
+    }
+}
+
+fn expect_left_paren(
+    tokens: &[Token],
+    pos: &mut usize,
+    line: usize,
+    after: &str,
+) -> InterpreterResult<()> {
+    if let Some(Token::LeftParen { .. }) = tokens.get(*pos) {
+        *pos += 1;
+        Ok(())
+    } else {
+        Err(unexpected(tokens, pos, line, &format!("'(' {}", after)))
+    }
+}
+
+fn expect_right_paren(
+    tokens: &[Token],
+    pos: &mut usize,
+    line: usize,
+    after: &str,
+) -> InterpreterResult<Token> {
+    if let Some(paren @ Token::RightParen { .. }) = tokens.get(*pos) {
+        *pos += 1;
+        Ok(paren.clone())
+    } else {
+        Err(unexpected(tokens, pos, line, &format!("')' {}", after)))
+    }
+}
+
+fn expect_right_bracket(
+    tokens: &[Token],
+    pos: &mut usize,
+    line: usize,
+    after: &str,
+) -> InterpreterResult<Token> {
+    if let Some(bracket @ Token::RightBracket { .. }) = tokens.get(*pos) {
+        *pos += 1;
+        Ok(bracket.clone())
+    } else {
+        Err(unexpected(tokens, pos, line, &format!("']' {}", after)))
+    }
+}
+
+// Reports the error on the offending token's line; running out of tokens
+// blames the last one there is.
+fn unexpected(tokens: &[Token], pos: &usize, line: usize, expected: &str) -> InterpreterError {
+    let token = tokens.get(*pos).or_else(|| tokens.last());
+    let found = match token {
+        None | Some(Token::Eof { .. }) => String::from("end of input"),
+        Some(Token::r#String { lexeme, .. }) => format!("'\"{}\"'", lexeme),
+        Some(t) => format!("'{}'", t.lexeme()),
+    };
+    InterpreterError::SyntaxError {
+        line: token.and_then(Token::get_line).unwrap_or(line),
+        message: format!("Expected {}, found {}", expected, found),
     }
 }
 
@@ -383,16 +827,10 @@ fn synchronize(tokens: &[Token], pos: &mut usize) -> bool {
     false
 }
 
-fn clean_tokens(tokens: Vec<Token>) -> Vec<Token> {
-    tokens
-        .into_iter()
-        .filter(|t| !matches!(t, Token::Comment | Token::Whitespace))
-        .collect()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast;
     use crate::expr::Expr;
     use crate::token::Token;
 
@@ -400,27 +838,42 @@ mod tests {
     fn parser_primary() -> InterpreterResult<()> {
         let mut pos: usize = 0;
         let ts = vec![Token::True { line: 0 }];
-        assert_eq!(primary(&ts, &mut pos, 0)?, Expr::literal_bool(true));
+        assert_eq!(
+            primary(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?,
+            Expr::literal_bool(true)
+        );
         let mut pos: usize = 0;
         let ts = vec![Token::False { line: 0 }];
-        assert_eq!(primary(&ts, &mut pos, 0)?, Expr::literal_bool(false));
+        assert_eq!(
+            primary(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?,
+            Expr::literal_bool(false)
+        );
         let mut pos: usize = 0;
         let ts = vec![Token::Nil { line: 0 }];
-        assert_eq!(primary(&ts, &mut pos, 0)?, Expr::literal_nil());
+        assert_eq!(
+            primary(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?,
+            Expr::literal_nil()
+        );
         let mut pos: usize = 0;
         let ts = vec![Token::Number {
             lexeme: String::from("3.0"),
             literal: 3.0,
             line: 0,
         }];
-        assert_eq!(primary(&ts, &mut pos, 0)?, Expr::literal_num(3.0));
+        assert_eq!(
+            primary(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?,
+            Expr::literal_num(3.0)
+        );
         let mut pos = 0;
         let ts = vec![Token::r#String {
             lexeme: String::from("hello"),
             literal: String::from("hello"),
             line: 0,
         }];
-        assert_eq!(primary(&ts, &mut pos, 0)?, Expr::literal_string("hello"));
+        assert_eq!(
+            primary(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?,
+            Expr::literal_string("hello")
+        );
         Ok(())
     }
     #[test]
@@ -435,10 +888,8 @@ mod tests {
             },
             Token::RightParen { line: 0 },
         ];
-        let expected = Expr::Grouping {
-            expression: Box::new(Expr::literal_num(3.0)),
-        };
-        assert_eq!(primary(&ts, &mut pos, 0)?, expected);
+        let expected = ast::grouping(Expr::literal_num(3.0));
+        assert_eq!(primary(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?, expected);
         let mut pos: usize = 0;
         let ts = vec![
             Token::LeftParen { line: 0 },
@@ -449,19 +900,22 @@ mod tests {
             },
             Token::Semicolon { line: 0 },
         ];
-        let err = primary(&ts, &mut pos, 0).unwrap_err();
-        assert!(matches!(err, InterpreterError::Parse { line: 0 }));
+        let err = primary(&ts, &mut pos, 0, DEFAULT_MAX_NESTING).unwrap_err();
+        assert!(matches!(
+            err,
+            InterpreterError::SyntaxError { line: 0, message } if message == "Expected ')' after expression, found ';'"
+        ));
         Ok(())
     }
     #[test]
     fn parser_unary() -> InterpreterResult<()> {
         let mut pos: usize = 0;
         let ts = vec![Token::Bang { line: 0 }, Token::False { line: 0 }];
-        let expected = Expr::Unary {
-            operator: Token::Bang { line: 0 },
-            right: Box::new(Expr::literal_bool(false)),
-        };
-        assert_eq!(unary(&ts, &mut pos, 0)?, expected);
+        let expected = ast::unary(Token::Bang { line: 0 }, Expr::literal_bool(false));
+        assert_eq!(
+            parse_precedence(&ts, &mut pos, 0, Precedence::Unary, DEFAULT_MAX_NESTING)?,
+            expected
+        );
         let mut pos: usize = 0;
         let ts = vec![
             Token::Minus { line: 0 },
@@ -471,11 +925,11 @@ mod tests {
                 line: 0,
             },
         ];
-        let expected = Expr::Unary {
-            operator: Token::Minus { line: 0 },
-            right: Box::new(Expr::literal_num(3.0)),
-        };
-        assert_eq!(unary(&ts, &mut pos, 0)?, expected);
+        let expected = ast::unary(Token::Minus { line: 0 }, Expr::literal_num(3.0));
+        assert_eq!(
+            parse_precedence(&ts, &mut pos, 0, Precedence::Unary, DEFAULT_MAX_NESTING)?,
+            expected
+        );
         Ok(())
     }
     #[test]
@@ -494,12 +948,15 @@ mod tests {
                 line: 0,
             },
         ];
-        let expected = Expr::Binary {
-            left: Box::new(Expr::literal_num(2.0)),
-            operator: Token::Slash { line: 0 },
-            right: Box::new(Expr::literal_num(3.0)),
-        };
-        assert_eq!(factor(&ts, &mut pos, 0)?, expected);
+        let expected = ast::binary(
+            Expr::literal_num(2.0),
+            Token::Slash { line: 0 },
+            Expr::literal_num(3.0),
+        );
+        assert_eq!(
+            parse_precedence(&ts, &mut pos, 0, Precedence::Factor, DEFAULT_MAX_NESTING)?,
+            expected
+        );
         let mut pos: usize = 0;
         let ts = vec![
             Token::Number {
@@ -514,12 +971,15 @@ mod tests {
                 line: 0,
             },
         ];
-        let expected = Expr::Binary {
-            left: Box::new(Expr::literal_num(2.0)),
-            operator: Token::Star { line: 0 },
-            right: Box::new(Expr::literal_num(3.0)),
-        };
-        assert_eq!(factor(&ts, &mut pos, 0)?, expected);
+        let expected = ast::binary(
+            Expr::literal_num(2.0),
+            Token::Star { line: 0 },
+            Expr::literal_num(3.0),
+        );
+        assert_eq!(
+            parse_precedence(&ts, &mut pos, 0, Precedence::Factor, DEFAULT_MAX_NESTING)?,
+            expected
+        );
         Ok(())
     }
     #[test]
@@ -538,12 +998,15 @@ mod tests {
                 line: 0,
             },
         ];
-        let expected = Expr::Binary {
-            left: Box::new(Expr::literal_num(3.0)),
-            operator: Token::Plus { line: 0 },
-            right: Box::new(Expr::literal_num(2.0)),
-        };
-        assert_eq!(term(&ts, &mut pos, 0)?, expected);
+        let expected = ast::binary(
+            Expr::literal_num(3.0),
+            Token::Plus { line: 0 },
+            Expr::literal_num(2.0),
+        );
+        assert_eq!(
+            parse_precedence(&ts, &mut pos, 0, Precedence::Term, DEFAULT_MAX_NESTING)?,
+            expected
+        );
         let mut pos: usize = 0;
         let ts = vec![
             Token::Number {
@@ -558,12 +1021,15 @@ mod tests {
                 line: 0,
             },
         ];
-        let expected = Expr::Binary {
-            left: Box::new(Expr::literal_num(3.0)),
-            operator: Token::Minus { line: 0 },
-            right: Box::new(Expr::literal_num(2.0)),
-        };
-        assert_eq!(term(&ts, &mut pos, 0)?, expected);
+        let expected = ast::binary(
+            Expr::literal_num(3.0),
+            Token::Minus { line: 0 },
+            Expr::literal_num(2.0),
+        );
+        assert_eq!(
+            parse_precedence(&ts, &mut pos, 0, Precedence::Term, DEFAULT_MAX_NESTING)?,
+            expected
+        );
         Ok(())
     }
     #[test]
@@ -594,20 +1060,21 @@ mod tests {
                 line: 0,
             },
         ];
-        let expected = Expr::Binary {
-            left: Box::new(Expr::Binary {
-                left: Box::new(Expr::literal_num(3.0)),
-                operator: Token::Minus { line: 0 },
-                right: Box::new(Expr::literal_num(2.0)),
-            }),
-            operator: Token::LessEqual { line: 0 },
-            right: Box::new(Expr::Binary {
-                left: Box::new(Expr::literal_num(1.0)),
-                operator: Token::Plus { line: 0 },
-                right: Box::new(Expr::literal_num(4.0)),
-            }),
-        };
-        assert_eq!(comparison(&ts, &mut pos, 0)?, expected);
+        let expected = ast::binary(
+            ast::binary(ast::num(3.0), Token::Minus { line: 0 }, ast::num(2.0)),
+            Token::LessEqual { line: 0 },
+            ast::binary(ast::num(1.0), Token::Plus { line: 0 }, ast::num(4.0)),
+        );
+        assert_eq!(
+            parse_precedence(
+                &ts,
+                &mut pos,
+                0,
+                Precedence::Comparison,
+                DEFAULT_MAX_NESTING
+            )?,
+            expected
+        );
         Ok(())
     }
     #[test]
@@ -626,12 +1093,15 @@ mod tests {
                 line: 0,
             },
         ];
-        let expected = Expr::Binary {
-            left: Box::new(Expr::literal_string("foo")),
-            operator: Token::EqualEqual { line: 0 },
-            right: Box::new(Expr::literal_string("foo")),
-        };
-        assert_eq!(equality(&ts, &mut pos, 0)?, expected);
+        let expected = ast::binary(
+            Expr::literal_string("foo"),
+            Token::EqualEqual { line: 0 },
+            Expr::literal_string("foo"),
+        );
+        assert_eq!(
+            parse_precedence(&ts, &mut pos, 0, Precedence::Equality, DEFAULT_MAX_NESTING)?,
+            expected
+        );
         Ok(())
     }
     #[test]
@@ -652,15 +1122,8 @@ mod tests {
             },
             Token::Semicolon { line: 0 },
         ];
-        let expected = Stmt::Variable {
-            name: Token::Identifier {
-                lexeme: String::from("foo"),
-                literal: String::from("foo"),
-                line: 0,
-            },
-            initializer: Some(Box::new(Expr::literal_num(3.0))),
-        };
-        let actual = declaration(&ts, &mut pos, 0)?;
+        let expected = ast::var("foo", Some(ast::num(3.0)));
+        let actual = declaration(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?;
         assert_eq!(actual, expected);
         Ok(())
     }
@@ -676,15 +1139,11 @@ mod tests {
             },
             Token::Semicolon { line: 0 },
         ];
-        let expected = Stmt::Variable {
-            name: Token::Identifier {
-                lexeme: String::from("foo"),
-                literal: String::from("foo"),
-                line: 0,
-            },
-            initializer: None,
-        };
-        assert_eq!(declaration(&ts, &mut pos, 0)?, expected);
+        let expected = ast::var("foo", None);
+        assert_eq!(
+            declaration(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?,
+            expected
+        );
         Ok(())
     }
     #[test]
@@ -710,8 +1169,274 @@ mod tests {
                 line: 0,
             },
             value: Box::new(Expr::literal_num(3.0)),
+            id: NodeId::next(),
+        };
+        assert_eq!(expression(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?, expected);
+        Ok(())
+    }
+    #[test]
+    fn parser_parse_program() {
+        let ts = vec![
+            Token::Var { line: 1 },
+            Token::Identifier {
+                lexeme: String::from("foo"),
+                literal: String::from("foo"),
+                line: 1,
+            },
+            Token::Semicolon { line: 1 },
+            Token::Whitespace {
+                text: String::from("\n"),
+                line: 1,
+            },
+            Token::Print { line: 2 },
+            Token::Identifier {
+                lexeme: String::from("foo"),
+                literal: String::from("foo"),
+                line: 2,
+            },
+            Token::Semicolon { line: 2 },
+            Token::Eof { line: 2 },
+        ];
+        let (stmts, errs) = parse(ts);
+        assert!(errs.is_empty());
+        assert_eq!(stmts.len(), 2);
+        assert!(matches!(stmts[0], Stmt::Variable { .. }));
+        assert!(matches!(stmts[1], Stmt::Print { .. }));
+    }
+    #[test]
+    fn parser_precedence() -> InterpreterResult<()> {
+        let parse_expr = |source: &str| -> InterpreterResult<String> {
+            let (tokens, _) = crate::scanner::scan(String::from(source));
+            expression(&tokens, &mut 0, 1, DEFAULT_MAX_NESTING)?.print()
         };
-        assert_eq!(assign(&ts, &mut pos, 0)?, expected);
+        assert_eq!(parse_expr("1 + 2 * 3 == 7")?, "(== (+ 1 (* 2 3)) 7)");
+        assert_eq!(parse_expr("1 - 2 - 3")?, "(- (- 1 2) 3)");
+        assert_eq!(parse_expr("a = b = 1")?, "(= a (= b 1))");
+        assert_eq!(
+            parse_expr("-f(1)(2) < !x")?,
+            "(< (- (call (call f 1) 2)) (! x))"
+        );
+        assert_eq!(
+            parse_expr("a = b ?? c ?? 1 == 2")?,
+            "(= a (?? (?? b c) (== 1 2)))"
+        );
+        assert_eq!(parse_expr("b[0] ?? 1")?, "(?? (index b 0) 1)");
+        assert!(parse_expr("a + b = 1").is_err());
+        assert!(parse_expr("a ?? b = 1").is_err());
+        Ok(())
+    }
+    #[test]
+    fn parser_nesting_limit() -> InterpreterResult<()> {
+        let run = |source: String, max_nesting: usize| -> InterpreterResult<()> {
+            let (stmts, errs) = parse_with(crate::scanner::scan(source).0, max_nesting);
+            if let Some(err) = errs.into_iter().next() {
+                return Err(err);
+            }
+            crate::interpreter::Interpreter::default().interpret_all(&stmts)?;
+            Ok(())
+        };
+        let too_deep = |res: InterpreterResult<()>| {
+            matches!(
+                res,
+                Err(InterpreterError::SyntaxError { message, .. }) if message == "Too deeply nested"
+            )
+        };
+        let n = DEFAULT_MAX_NESTING / 2;
+        run(
+            format!("var a = {}1{};", "(".repeat(n), ")".repeat(n)),
+            DEFAULT_MAX_NESTING,
+        )?;
+        // A flat chain is one level deep however long it runs.
+        run(
+            format!("var a = 1{};", " + 1".repeat(DEFAULT_MAX_NESTING * 3)),
+            DEFAULT_MAX_NESTING,
+        )?;
+        run(format!("var a = {}1;", "-".repeat(n)), DEFAULT_MAX_NESTING)?;
+        run(
+            format!("{}{}", "{".repeat(n), "}".repeat(n)),
+            DEFAULT_MAX_NESTING,
+        )?;
+        for source in [
+            format!("var a = {}1{};", "(".repeat(10_000), ")".repeat(10_000)),
+            format!("var a = {}1;", "!".repeat(10_000)),
+            format!("var a; var b; a = {}1;", "b = ".repeat(10_000)),
+            format!("{}{}", "{".repeat(10_000), "}".repeat(10_000)),
+        ] {
+            assert!(too_deep(run(source, DEFAULT_MAX_NESTING)));
+        }
+        assert!(matches!(
+            run(format!("var a = 1{};", " + 1".repeat(10_000)), DEFAULT_MAX_NESTING),
+            Err(InterpreterError::SyntaxError { message, .. }) if message == "Too many operators in a row"
+        ));
+        run(String::from("print ((1));"), 3)?;
+        run(String::from("print 1 + 2 * 3 - 4 + 5;"), 3)?;
+        assert!(too_deep(run(String::from("print (((1)));"), 3)));
+        assert!(too_deep(run(String::from("{ { print 1; } }"), 2)));
+        Ok(())
+    }
+    #[test]
+    fn parser_parse_stream() {
+        use crate::scanner::{scan_with_trivia, TokenStream};
+        let source = "/// Doubles.\nfun double(x) {\n  var y = (x + x);\n  return y;\n}\nvar i = 0;\nwhile (i < 3) { print double(i); i = i + 1; }\n";
+        let (streamed, errs) = parse_stream(TokenStream::with_trivia(source));
+        assert!(errs.is_empty());
+        assert_eq!(streamed, parse(scan_with_trivia(String::from(source)).0).0);
+        assert!(matches!(&streamed[0], Stmt::Function { doc: Some(d), .. } if d == "Doubles."));
+        let (stmts, errs) = parse_stream(TokenStream::new("1 +;\nprint @;\nprint 2;"));
+        assert_eq!(errs.len(), 3);
+        assert_eq!(stmts.len(), 1);
+        let (_, errs) = parse_stream(TokenStream::new("print (1;\nprint 2;"));
+        assert!(!errs.is_empty());
+        let (_, errs) = parse_stream(TokenStream::new("print 1;\nfun f() {\n  print 2;\n"));
+        assert!(!errs.is_empty());
+    }
+    #[test]
+    fn parser_keyword_hint() {
+        let messages = |source: &str| -> Vec<String> {
+            parse(crate::scanner::scan(String::from(source)).0)
+                .1
+                .iter()
+                .map(|err| err.to_string())
+                .collect()
+        };
+        assert_eq!(
+            messages("pritn \"hi\";"),
+            vec!["Syntax error on line 1: Expected ';' after expression, found '\"hi\"'; did you mean 'print'?"]
+        );
+        assert_eq!(
+            messages("whle (true) {}"),
+            vec!["Syntax error on line 1: Expected ';' after expression, found '{'; did you mean 'while'?"]
+        );
+        assert_eq!(
+            messages("clas Point {}"),
+            vec!["Syntax error on line 1: Expected ';' after expression, found 'Point'"]
+        );
+        assert_eq!(
+            messages("total 1;"),
+            vec!["Syntax error on line 1: Expected ';' after expression, found '1'"]
+        );
+    }
+    #[test]
+    fn parser_parse_program_recovers() {
+        let ts = vec![
+            Token::Number {
+                lexeme: String::from("1"),
+                literal: 1.0,
+                line: 1,
+            },
+            Token::Plus { line: 1 },
+            Token::Semicolon { line: 1 },
+            Token::Print { line: 2 },
+            Token::Number {
+                lexeme: String::from("2"),
+                literal: 2.0,
+                line: 2,
+            },
+            Token::Semicolon { line: 2 },
+            Token::Eof { line: 2 },
+        ];
+        let (stmts, errs) = parse(ts);
+        assert_eq!(errs.len(), 1);
+        assert_eq!(
+            errs[0].to_string(),
+            "Syntax error on line 1: Expected expression, found ';'"
+        );
+        assert_eq!(
+            stmts,
+            vec![Stmt::Print {
+                id: NodeId::next(),
+                keyword: Token::Print { line: 2 },
+                expr: Box::new(Expr::literal_num(2.0))
+            }]
+        );
+    }
+    #[test]
+    fn parser_call() -> InterpreterResult<()> {
+        let mut pos: usize = 0;
+        let ts = vec![
+            Token::Identifier {
+                lexeme: String::from("add"),
+                literal: String::from("add"),
+                line: 0,
+            },
+            Token::LeftParen { line: 0 },
+            Token::Number {
+                lexeme: String::from("1"),
+                literal: 1.0,
+                line: 0,
+            },
+            Token::Comma { line: 0 },
+            Token::Number {
+                lexeme: String::from("2"),
+                literal: 2.0,
+                line: 0,
+            },
+            Token::RightParen { line: 0 },
+            Token::LeftParen { line: 0 },
+            Token::RightParen { line: 0 },
+        ];
+        let expected = ast::call(
+            ast::call(ast::variable("add"), vec![ast::num(1.0), ast::num(2.0)]),
+            vec![],
+        );
+        assert_eq!(
+            parse_precedence(&ts, &mut pos, 0, Precedence::Call, DEFAULT_MAX_NESTING)?,
+            expected
+        );
+        Ok(())
+    }
+    #[test]
+    fn parser_while() -> InterpreterResult<()> {
+        let mut pos: usize = 0;
+        let ts = vec![
+            Token::While { line: 0 },
+            Token::LeftParen { line: 0 },
+            Token::True { line: 0 },
+            Token::RightParen { line: 0 },
+            Token::LeftBrace { line: 0 },
+            Token::RightBrace { line: 0 },
+        ];
+        let expected = ast::while_loop(ast::boolean(true), ast::block(vec![]));
+        assert_eq!(
+            declaration(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?,
+            expected
+        );
+        assert_eq!(pos, ts.len());
+        Ok(())
+    }
+    #[test]
+    fn parser_function() -> InterpreterResult<()> {
+        let mut pos: usize = 0;
+        let ts = vec![
+            Token::Fun { line: 0 },
+            Token::Identifier {
+                lexeme: String::from("id"),
+                literal: String::from("id"),
+                line: 0,
+            },
+            Token::LeftParen { line: 0 },
+            Token::Identifier {
+                lexeme: String::from("x"),
+                literal: String::from("x"),
+                line: 0,
+            },
+            Token::RightParen { line: 0 },
+            Token::LeftBrace { line: 0 },
+            Token::Return { line: 0 },
+            Token::Identifier {
+                lexeme: String::from("x"),
+                literal: String::from("x"),
+                line: 0,
+            },
+            Token::Semicolon { line: 0 },
+            Token::RightBrace { line: 0 },
+        ];
+        let expected = ast::function("id", &["x"], vec![ast::ret(Some(ast::variable("x")))]);
+        assert_eq!(
+            declaration(&ts, &mut pos, 0, DEFAULT_MAX_NESTING)?,
+            expected
+        );
+        assert_eq!(pos, ts.len());
         Ok(())
     }
     #[test]
@@ -0,0 +1,386 @@
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::function::{Callable, NativeCall, NativeFunction};
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::ops::RangeInclusive;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+// Everything that crosses between the interpreter and a plugin is #[repr(C)]
+// and every function pointer is extern "C", so the two sides need only agree
+// on the version below, not on a compiler. Each side links its own copy of
+// this module and converts to its own Rust types at the boundary.
+//
+// Bumped whenever a #[repr(C)] type or extern "C" signature here changes.
+pub const PLUGIN_API_VERSION: u32 = 2;
+
+pub type PluginFn = fn(Vec<Value>, usize) -> InterpreterResult<Value>;
+
+type Trampoline = unsafe extern "C" fn(
+    data: *const c_void,
+    args: *const PluginValue,
+    len: usize,
+    line: usize,
+    out: *mut PluginValue,
+) -> bool;
+
+type Free = unsafe extern "C" fn(value: *mut PluginValue);
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PluginStr {
+    ptr: *const u8,
+    len: usize,
+}
+
+const TAG_NIL: u32 = 0;
+const TAG_BOOL: u32 = 1;
+const TAG_NUMBER: u32 = 2;
+const TAG_STRING: u32 = 3;
+
+// A nil, boolean, number or string. Whichever side builds one owns its string
+// and is the one to free it.
+#[repr(C)]
+pub struct PluginValue {
+    tag: u32,
+    boolean: bool,
+    number: f64,
+    string: PluginStr,
+}
+
+#[repr(C)]
+pub struct PluginNative {
+    name: PluginStr,
+    params: PluginStr,
+    doc: PluginStr,
+    min_arity: usize,
+    max_arity: usize,
+    data: *const c_void,
+    call: Trampoline,
+    free: Free,
+}
+
+// Handed to a plugin's register function. The plugin calls register_native,
+// compiled from its own copy of this module, which calls back into the
+// interpreter through `register`.
+#[repr(C)]
+pub struct PluginRegistrar<'a> {
+    host: *mut c_void,
+    register: unsafe extern "C" fn(host: *mut c_void, native: *const PluginNative),
+    interpreter: PhantomData<&'a mut Interpreter>,
+}
+
+#[macro_export]
+macro_rules! declare_plugin {
+    ($register:path) => {
+        #[no_mangle]
+        pub extern "C" fn lox_plugin_api_version() -> u32 {
+            $crate::PLUGIN_API_VERSION
+        }
+        /// # Safety
+        /// `registrar` must point to a registrar from a host with the same
+        /// plugin API version.
+        #[no_mangle]
+        pub unsafe extern "C" fn lox_plugin_register(registrar: *mut $crate::PluginRegistrar) {
+            $register(&mut *registrar)
+        }
+    };
+}
+
+impl PluginStr {
+    fn new(s: &str) -> Self {
+        Self {
+            ptr: s.as_ptr(),
+            len: s.len(),
+        }
+    }
+    unsafe fn to_string(self) -> String {
+        String::from_utf8_lossy(std::slice::from_raw_parts(self.ptr, self.len)).into_owned()
+    }
+}
+
+impl PluginValue {
+    fn nil() -> Self {
+        Self {
+            tag: TAG_NIL,
+            boolean: false,
+            number: 0.0,
+            string: PluginStr::new(""),
+        }
+    }
+    // Fails with the type name of anything a plugin can't be given.
+    fn new(value: &Value) -> Result<Self, &'static str> {
+        let nil = Self::nil();
+        Ok(match value {
+            Value::Nil => nil,
+            Value::Bool(b) => Self {
+                tag: TAG_BOOL,
+                boolean: *b,
+                ..nil
+            },
+            Value::Number(n) => Self {
+                tag: TAG_NUMBER,
+                number: f64::from(*n),
+                ..nil
+            },
+            Value::r#String(s) => {
+                let owned: &str = Box::leak(s.to_string().into_boxed_str());
+                Self {
+                    tag: TAG_STRING,
+                    string: PluginStr::new(owned),
+                    ..nil
+                }
+            }
+            value => return Err(value.type_name()),
+        })
+    }
+    unsafe fn to_value(&self) -> Value {
+        match self.tag {
+            TAG_BOOL => Value::Bool(self.boolean),
+            TAG_NUMBER => Value::Number(self.number as f32),
+            TAG_STRING => Value::r#String(self.string.to_string().into()),
+            _ => Value::Nil,
+        }
+    }
+}
+
+// Frees a value built by PluginValue::new on this side of the boundary.
+unsafe extern "C" fn free(value: *mut PluginValue) {
+    let value = &mut *value;
+    if value.tag == TAG_STRING {
+        let bytes = std::slice::from_raw_parts_mut(value.string.ptr as *mut u8, value.string.len);
+        drop(Box::from_raw(bytes as *mut [u8]));
+    }
+    *value = PluginValue::nil();
+}
+
+// Runs on the plugin's side: `data` is one of its PluginFns.
+unsafe extern "C" fn trampoline(
+    data: *const c_void,
+    args: *const PluginValue,
+    len: usize,
+    line: usize,
+    out: *mut PluginValue,
+) -> bool {
+    let call = std::mem::transmute::<*const c_void, PluginFn>(data);
+    let args = std::slice::from_raw_parts(args, len)
+        .iter()
+        .map(|arg| arg.to_value())
+        .collect();
+    let result = match panic::catch_unwind(AssertUnwindSafe(|| call(args, line))) {
+        Ok(Ok(value)) => PluginValue::new(&value)
+            .map_err(|type_name| format!("A plugin can't return a {}", type_name)),
+        Ok(Err(InterpreterError::RuntimeError { message, .. })) => Err(message),
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(_) => Err(String::from("The plugin panicked")),
+    };
+    let (ok, value) = match result {
+        Ok(value) => (true, value),
+        Err(message) => (
+            false,
+            PluginValue::new(&Value::r#String(message.into())).unwrap(),
+        ),
+    };
+    out.write(value);
+    ok
+}
+
+impl PluginRegistrar<'_> {
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        params: &str,
+        arity: RangeInclusive<usize>,
+        call: PluginFn,
+        doc: &str,
+    ) {
+        let native = PluginNative {
+            name: PluginStr::new(name),
+            params: PluginStr::new(params),
+            doc: PluginStr::new(doc),
+            min_arity: *arity.start(),
+            max_arity: *arity.end(),
+            data: call as *const c_void,
+            call: trampoline,
+            free,
+        };
+        unsafe { (self.register)(self.host, &native) }
+    }
+}
+
+// Only the dlopen feature loads plugins.
+#[cfg_attr(not(feature = "dlopen"), allow(dead_code))]
+impl<'a> PluginRegistrar<'a> {
+    pub(crate) fn new(interpreter: &'a mut Interpreter) -> Self {
+        Self {
+            host: interpreter as *mut Interpreter as *mut c_void,
+            register,
+            interpreter: PhantomData,
+        }
+    }
+}
+
+// Runs on the interpreter's side, copying out everything the plugin passed.
+#[cfg_attr(not(feature = "dlopen"), allow(dead_code))]
+unsafe extern "C" fn register(host: *mut c_void, native: *const PluginNative) {
+    let interpreter = &*(host as *const Interpreter);
+    let native = &*native;
+    // Plugins are never unloaded, so neither are the names they register.
+    let leak = |s: PluginStr| -> &'static str { Box::leak(s.to_string().into_boxed_str()) };
+    let name = leak(native.name);
+    let function = NativeFunction {
+        name,
+        params: leak(native.params),
+        arity: native.min_arity..=native.max_arity,
+        call: NativeCall::Plugin(PluginCall {
+            data: native.data,
+            call: native.call,
+            free: native.free,
+        }),
+        doc: leak(native.doc),
+    };
+    interpreter.define(name, Value::Callable(Callable::Native(Rc::new(function))));
+}
+
+// A registered native, called through the plugin's trampoline.
+pub(crate) struct PluginCall {
+    data: *const c_void,
+    call: Trampoline,
+    free: Free,
+}
+
+impl PluginCall {
+    pub(crate) fn call(&self, args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+        let mut args = args
+            .iter()
+            .map(PluginValue::new)
+            .collect::<Result<Vec<PluginValue>, &str>>()
+            .map_err(|type_name| {
+                InterpreterError::type_error(
+                    String::from("nil, boolean, number or string"),
+                    String::from(type_name),
+                )
+                .add_line_to_type_error(line)
+            })?;
+        let mut out = PluginValue::nil();
+        let ok = unsafe { (self.call)(self.data, args.as_ptr(), args.len(), line, &mut out) };
+        let value = unsafe { out.to_value() };
+        unsafe {
+            (self.free)(&mut out);
+            for arg in args.iter_mut() {
+                free(arg);
+            }
+        }
+        if ok {
+            Ok(value)
+        } else {
+            Err(InterpreterError::RuntimeError {
+                line,
+                message: value.to_string(),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "dlopen")]
+pub(crate) fn load(interpreter: &mut Interpreter, path: &str) -> InterpreterResult<()> {
+    use libloading::{Library, Symbol};
+
+    let error = |message: String| InterpreterError::RuntimeError { line: 0, message };
+    // Loading runs the library's initializers; plugins are as trusted as the
+    // interpreter binary itself.
+    let library = unsafe { Library::new(path) }
+        .map_err(|e| error(format!("Couldn't load plugin {}: {}", path, e)))?;
+    unsafe {
+        let version: Symbol<extern "C" fn() -> u32> = library
+            .get(b"lox_plugin_api_version")
+            .map_err(|_| error(format!("{} isn't a Lox plugin", path)))?;
+        if version() != PLUGIN_API_VERSION {
+            return Err(error(format!(
+                "{} was built for plugin API {}, not {}",
+                path,
+                version(),
+                PLUGIN_API_VERSION
+            )));
+        }
+        let register: Symbol<unsafe extern "C" fn(*mut PluginRegistrar)> = library
+            .get(b"lox_plugin_register")
+            .map_err(|_| error(format!("{} isn't a Lox plugin", path)))?;
+        register(&mut PluginRegistrar::new(interpreter));
+    }
+    // The natives point into the library, so it stays loaded for good.
+    std::mem::forget(library);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shout(args: Vec<Value>, line: usize) -> InterpreterResult<Value> {
+        match &args[0] {
+            Value::r#String(s) => Ok(Value::r#String(format!("{}!", s).into())),
+            Value::Bool(true) => panic!("too loud"),
+            Value::Number(_) => Ok(Value::Callable(Callable::Native(Rc::new(NativeFunction {
+                name: "n",
+                params: "",
+                arity: 0..=0,
+                call: NativeCall::Builtin(|_, _, _| Ok(Value::Nil)),
+                doc: "",
+            })))),
+            _ => Err(InterpreterError::RuntimeError {
+                line,
+                message: String::from("shout needs a string"),
+            }),
+        }
+    }
+
+    fn register(registrar: &mut PluginRegistrar) {
+        registrar.register_native("shout", "s", 1..=1, shout, "Returns s with a bang.");
+    }
+
+    #[test]
+    fn plugin_registers_natives() -> InterpreterResult<()> {
+        let mut interpreter = Interpreter::default();
+        register(&mut PluginRegistrar::new(&mut interpreter));
+        let shout = match interpreter.lookup("shout") {
+            Some(Value::Callable(callable)) => callable,
+            _ => unreachable!(),
+        };
+        assert_eq!(shout.signature(), "shout(s)");
+        let greeting = Value::r#String("hi".into());
+        assert_eq!(
+            shout.call(&interpreter, vec![greeting], 1)?,
+            Value::r#String("hi!".into())
+        );
+        let message = |arg: Value| match shout.call(&interpreter, vec![arg], 2) {
+            Err(InterpreterError::RuntimeError { line: 2, message }) => message,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(message(Value::Nil), "shout needs a string");
+        assert_eq!(message(Value::Bool(true)), "The plugin panicked");
+        assert_eq!(
+            message(Value::Number(1.0)),
+            "A plugin can't return a function"
+        );
+        assert!(matches!(
+            shout.call(&interpreter, vec![Value::Callable(shout.clone())], 3),
+            Err(InterpreterError::Type { line: Some(3), .. })
+        ));
+        assert!(shout.call(&interpreter, vec![], 3).is_err());
+        Ok(())
+    }
+
+    #[cfg(all(feature = "dlopen", target_os = "linux"))]
+    #[test]
+    fn plugin_load_rejects_other_libraries() {
+        let mut interpreter = Interpreter::default();
+        assert!(load(&mut interpreter, "/no/such/plugin.so").is_err());
+        assert!(matches!(
+            load(&mut interpreter, "libm.so.6"),
+            Err(InterpreterError::RuntimeError { message, .. }) if message.contains("isn't a Lox plugin")
+        ));
+    }
+}
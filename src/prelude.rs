@@ -0,0 +1,144 @@
+use crate::environment::Globals;
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::function::Callable;
+use crate::interpreter::Interpreter;
+use crate::parser;
+use crate::scanner::scan;
+use crate::value::Value;
+use std::sync::Arc;
+
+const STANDARD: &str = include_str!("prelude.lox");
+
+thread_local! {
+    static STANDARD_PRELUDE: Prelude =
+        Prelude::new(STANDARD).expect("the standard prelude should run cleanly");
+}
+
+// Lox source run once, whose globals seed new interpreters. Functions are
+// shared by reference, along with their resolved locals, so an instance costs
+// a copy of the globals rather than a parse and resolve; each instance then
+// assigns to its own copy.
+#[derive(Clone, Debug)]
+pub struct Prelude {
+    source: Arc<str>,
+    globals: Globals,
+}
+
+impl Prelude {
+    pub fn new(source: &str) -> InterpreterResult<Self> {
+        let (tokens, scan_errs) = scan(String::from(source));
+        if let Some(err) = scan_errs.into_iter().next() {
+            return Err(err);
+        }
+        let (stmts, parse_errs) = parser::parse(tokens);
+        if let Some(err) = parse_errs.into_iter().next() {
+            return Err(err);
+        }
+        let interpreter = Interpreter::default();
+        interpreter.interpret_all(&stmts)?;
+        // A closure's environment or an instance's fields would be mutable
+        // state that every instance shares.
+        for (name, value) in interpreter.globals().values() {
+            let line = match value {
+                Value::Callable(Callable::Lox(f)) if f.closure.is_some() => f.name.get_line(),
+                Value::Instance(_) => Some(0),
+                _ => continue,
+            };
+            return Err(InterpreterError::RuntimeError {
+                line: line.unwrap_or(0),
+                message: format!("Prelude global {} would share mutable state", name),
+            });
+        }
+        Ok(Self {
+            source: Arc::from(source),
+            globals: interpreter.snapshot(),
+        })
+    }
+    // The prelude shipped with the interpreter, parsed and run once per
+    // thread.
+    pub fn standard() -> Self {
+        STANDARD_PRELUDE.with(Prelude::clone)
+    }
+    // Rebuilds a prelude on another thread from the source it was loaded
+    // from; the standard one comes from that thread's cache.
+    pub(crate) fn from_source(source: &str) -> InterpreterResult<Self> {
+        if source == STANDARD {
+            Ok(Self::standard())
+        } else {
+            Self::new(source)
+        }
+    }
+    pub(crate) fn load(&self, interpreter: &Interpreter) {
+        interpreter.restore(self.globals.clone());
+        interpreter.set_prelude(Some(Arc::clone(&self.source)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::Lox;
+    #[test]
+    fn prelude_instances_are_isolated() -> InterpreterResult<()> {
+        let prelude = Prelude::new(
+            "fun sum(n) { var total = 0; while (n > 0) { total = total + n; n = n - 1; } return total; }
+             var count = 0;
+             fun bump() { count = count + 1; return count; }",
+        )?;
+        let mut a = Lox::from_prelude(&prelude);
+        let mut b = Lox::from_prelude(&prelude);
+        assert_eq!(a.eval_str("sum(4);")?, Value::Number(10.0));
+        assert_eq!(a.eval_str("bump(); bump();")?, Value::Number(2.0));
+        assert_eq!(b.eval_str("bump();")?, Value::Number(1.0));
+        b.eval_str("fun sum(n) { return -n; }")?;
+        assert_eq!(a.eval_str("sum(3);")?, Value::Number(6.0));
+        assert_eq!(b.eval_str("sum(3);")?, Value::Number(-3.0));
+        match (
+            a.get_global("bump"),
+            Lox::from_prelude(&prelude).get_global("bump"),
+        ) {
+            (Some(Value::Callable(Callable::Lox(x))), Some(Value::Callable(Callable::Lox(y)))) => {
+                assert!(std::rc::Rc::ptr_eq(&x, &y))
+            }
+            other => panic!("{:?}", other),
+        }
+        Ok(())
+    }
+    #[test]
+    fn prelude_standard() -> InterpreterResult<()> {
+        let mut lox = Lox::from_prelude(&Prelude::standard());
+        assert_eq!(lox.eval_str("max(2, 3);")?, Value::Number(3.0));
+        assert_eq!(lox.eval_str("abs(-4) + sign(-4);")?, Value::Number(3.0));
+        assert_eq!(lox.eval_str("clamp(12, 0, 10);")?, Value::Number(10.0));
+        assert_eq!(
+            lox.eval_str("padLeft(\"7\", 3, \"0\");")?,
+            Value::r#String("007".into())
+        );
+        assert_eq!(
+            lox.eval_str("startsWith(\"prelude\", \"pre\");")?,
+            Value::Bool(true)
+        );
+        assert_eq!(
+            lox.eval_str("endsWith(\"prelude\", \"lude\");")?,
+            Value::Bool(true)
+        );
+        assert_eq!(
+            lox.eval_str("endsWith(\"a\", \"abc\");")?,
+            Value::Bool(false)
+        );
+        assert_eq!(Lox::new().get_global("max"), None);
+        Ok(())
+    }
+    #[test]
+    fn prelude_rejects_shared_state() {
+        assert!(matches!(
+            Prelude::new(
+                "fun counter() { var n = 0; fun inc() { n = n + 1; return n; } return inc; }
+                 var next = counter();"
+            ),
+            Err(InterpreterError::RuntimeError { line: 1, .. })
+        ));
+        assert!(Prelude::new("print nope;").is_err());
+        assert!(Prelude::new("fun (").is_err());
+    }
+}
@@ -1,6 +1,9 @@
+use crate::repl::{is_incomplete, PASTE, PASTE_END};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
+const CONTINUATION: &str = ".. ";
+
 pub struct Prompt {
     rl: Editor<()>,
     prompt: String,
@@ -16,6 +19,32 @@ impl Prompt {
             prompt: prompt.into(),
         }
     }
+    fn paste(&mut self) -> Result<String, ReadlineError> {
+        println!("Paste mode: finish with {} or Ctrl-D", PASTE_END);
+        let mut lines = Vec::default();
+        loop {
+            match self.rl.readline("") {
+                Ok(l) if l.trim() == PASTE_END => break,
+                Ok(l) => lines.push(l),
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+    fn continue_lines(&mut self, mut source: String) -> Result<String, ReadlineError> {
+        while is_incomplete(&source) {
+            match self.rl.readline(CONTINUATION) {
+                Ok(l) => {
+                    source.push('\n');
+                    source.push_str(&l);
+                }
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(source)
+    }
 }
 
 impl Iterator for Prompt {
@@ -23,7 +52,8 @@ impl Iterator for Prompt {
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.rl.readline(&self.prompt) {
-            Ok(l) => Some(Ok(l)),
+            Ok(l) if l.trim() == PASTE => Some(self.paste()),
+            Ok(l) => Some(self.continue_lines(l)),
             Err(ReadlineError::Eof) => None,
             Err(e) => Some(Err(e)),
         }
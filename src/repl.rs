@@ -0,0 +1,355 @@
+use crate::errors::InterpreterError;
+use crate::function::Callable;
+use crate::scanner::TokenStream;
+use crate::token::Token;
+use crate::value::Value;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub(crate) const PASTE: &str = ":paste";
+pub(crate) const PASTE_END: &str = ":end";
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Command {
+    Ast { source: String },
+    Doc { name: String },
+    Dump { path: String },
+    Load { path: String },
+    Reset,
+    Restore { name: String },
+    Save { name: String },
+    SessionLoad { name: String },
+    SessionSave { name: String },
+    Time { source: Option<String> },
+    Tokens { source: String },
+    Usage { usage: &'static str },
+    Unknown { name: String },
+}
+
+impl Command {
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let line = line.trim().strip_prefix(':')?;
+        let (name, rest) = match line.split_once(char::is_whitespace) {
+            Some((name, rest)) => (name, Some(rest.trim())),
+            None => (line, None),
+        };
+        let rest = rest.filter(|r| !r.is_empty()).map(String::from);
+        match (name, rest) {
+            ("ast", Some(source)) => Some(Self::Ast { source }),
+            ("doc", Some(name)) => Some(Self::Doc { name }),
+            ("dump", Some(path)) => Some(Self::Dump { path }),
+            ("load", Some(path)) => Some(Self::Load { path }),
+            ("reset", _) => Some(Self::Reset),
+            ("restore", Some(name)) => Some(Self::Restore { name }),
+            ("save", Some(name)) => Some(Self::Save { name }),
+            ("session", Some(rest)) => match rest.split_once(char::is_whitespace) {
+                Some(("save", name)) => Some(Self::SessionSave {
+                    name: name.trim().into(),
+                }),
+                Some(("load", name)) => Some(Self::SessionLoad {
+                    name: name.trim().into(),
+                }),
+                _ => Some(Self::Usage {
+                    usage: ":session save|load <name>",
+                }),
+            },
+            ("session", None) => Some(Self::Usage {
+                usage: ":session save|load <name>",
+            }),
+            ("time", source) => Some(Self::Time { source }),
+            ("tokens", Some(source)) => Some(Self::Tokens { source }),
+            ("ast", None) => Some(Self::Usage {
+                usage: ":ast <source>",
+            }),
+            ("doc", None) => Some(Self::Usage {
+                usage: ":doc <name>",
+            }),
+            ("tokens", None) => Some(Self::Usage {
+                usage: ":tokens <source>",
+            }),
+            ("restore", None) => Some(Self::Usage {
+                usage: ":restore <name>",
+            }),
+            ("save", None) => Some(Self::Usage {
+                usage: ":save <name>",
+            }),
+            ("dump", None) => Some(Self::Usage {
+                usage: ":dump <path>",
+            }),
+            ("load", None) => Some(Self::Usage {
+                usage: ":load <path>",
+            }),
+            (name, _) => Some(Self::Unknown { name: name.into() }),
+        }
+    }
+}
+
+pub(crate) fn describe(name: &str, value: Option<&Value>) -> String {
+    match value {
+        Some(Value::Callable(Callable::Native(f))) => format!("{}\n    {}", f.signature(), f.doc()),
+        Some(Value::Callable(f)) => f.signature(),
+        Some(value) => format!("{}: {}", name, value.type_name()),
+        None => format!("No global named {}", name),
+    }
+}
+
+// A session is the inputs that evaluated cleanly, saved as a script so that
+// loading it is just running it again.
+pub(crate) fn session_path(name: &str) -> PathBuf {
+    let path = PathBuf::from(name);
+    if path.extension().is_some() {
+        path
+    } else {
+        path.with_extension("lox")
+    }
+}
+
+pub(crate) fn session_source(journal: &[String]) -> String {
+    journal.iter().fold(String::default(), |mut source, input| {
+        source.push_str(input.trim_end());
+        source.push('\n');
+        source
+    })
+}
+
+// ~/.loxrc, run at the start of every REPL session.
+pub(crate) fn rc_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".loxrc"))
+}
+
+// Splits an rc file into its Lox source and the REPL commands on lines of
+// their own, such as `:time`. Command lines are blanked rather than removed so
+// errors in the source keep their line numbers.
+pub(crate) fn split_rc(rc: &str) -> (String, Vec<Command>) {
+    let mut source = String::default();
+    let mut commands = Vec::default();
+    for line in rc.lines() {
+        match Command::parse(line) {
+            Some(command) => commands.push(command),
+            None => source.push_str(line),
+        }
+        source.push('\n');
+    }
+    (source, commands)
+}
+
+pub(crate) fn is_incomplete(source: &str) -> bool {
+    if source.trim_start().starts_with(':') {
+        return false;
+    }
+    let mut depth: isize = 0;
+    for token in TokenStream::new(source) {
+        match token {
+            Ok(Token::LeftBrace { .. } | Token::LeftParen { .. }) => depth += 1,
+            Ok(Token::RightBrace { .. } | Token::RightParen { .. }) => depth -= 1,
+            Err(InterpreterError::UnterminatedString { .. }) => return true,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Timings {
+    pub(crate) scan: Duration,
+    pub(crate) parse: Duration,
+    pub(crate) interpret: Duration,
+}
+
+impl Timings {
+    pub(crate) fn total(&self) -> Duration {
+        self.scan + self.parse + self.interpret
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "scan: {:?}, parse: {:?}, interpret: {:?}, total: {:?}",
+            self.scan,
+            self.parse,
+            self.interpret,
+            self.total()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn repl_parse_not_a_command() {
+        assert_eq!(Command::parse("print 1;"), None);
+        assert_eq!(Command::parse(""), None);
+    }
+    #[test]
+    fn repl_parse_time() {
+        assert_eq!(
+            Command::parse(":time"),
+            Some(Command::Time { source: None })
+        );
+        assert_eq!(
+            Command::parse("  :time   1 + 2;"),
+            Some(Command::Time {
+                source: Some(String::from("1 + 2;"))
+            })
+        );
+    }
+    #[test]
+    fn repl_parse_ast_and_tokens() {
+        assert_eq!(
+            Command::parse(":ast var x = 1;"),
+            Some(Command::Ast {
+                source: String::from("var x = 1;")
+            })
+        );
+        assert_eq!(
+            Command::parse(":tokens 1 + 2"),
+            Some(Command::Tokens {
+                source: String::from("1 + 2")
+            })
+        );
+        assert_eq!(
+            Command::parse(":ast"),
+            Some(Command::Usage {
+                usage: ":ast <source>"
+            })
+        );
+    }
+    #[test]
+    fn repl_parse_snapshots() {
+        assert_eq!(Command::parse(":reset"), Some(Command::Reset));
+        assert_eq!(
+            Command::parse(":save before"),
+            Some(Command::Save {
+                name: String::from("before")
+            })
+        );
+        assert_eq!(
+            Command::parse(":restore before"),
+            Some(Command::Restore {
+                name: String::from("before")
+            })
+        );
+        assert_eq!(
+            Command::parse(":save"),
+            Some(Command::Usage {
+                usage: ":save <name>"
+            })
+        );
+        assert_eq!(
+            Command::parse(":dump session.json"),
+            Some(Command::Dump {
+                path: String::from("session.json")
+            })
+        );
+        assert_eq!(
+            Command::parse(":load session.json"),
+            Some(Command::Load {
+                path: String::from("session.json")
+            })
+        );
+        assert_eq!(
+            Command::parse(":load"),
+            Some(Command::Usage {
+                usage: ":load <path>"
+            })
+        );
+    }
+    #[test]
+    fn repl_parse_session() {
+        assert_eq!(
+            Command::parse(":session save scratch"),
+            Some(Command::SessionSave {
+                name: String::from("scratch")
+            })
+        );
+        assert_eq!(
+            Command::parse(":session load  scratch.lox"),
+            Some(Command::SessionLoad {
+                name: String::from("scratch.lox")
+            })
+        );
+        for usage in [":session", ":session save", ":session drop scratch"] {
+            assert_eq!(
+                Command::parse(usage),
+                Some(Command::Usage {
+                    usage: ":session save|load <name>"
+                })
+            );
+        }
+    }
+    #[test]
+    fn repl_session_files() {
+        assert_eq!(session_path("scratch"), PathBuf::from("scratch.lox"));
+        assert_eq!(session_path("a/b.txt"), PathBuf::from("a/b.txt"));
+        let journal = [
+            String::from("var a = 1;"),
+            String::from("fun f() {\n  return a;\n}\n"),
+        ];
+        assert_eq!(
+            session_source(&journal),
+            "var a = 1;\nfun f() {\n  return a;\n}\n"
+        );
+        assert_eq!(session_source(&[]), "");
+    }
+    #[test]
+    fn repl_parse_doc() {
+        assert_eq!(
+            Command::parse(":doc clock"),
+            Some(Command::Doc {
+                name: String::from("clock")
+            })
+        );
+        assert_eq!(
+            Command::parse(":doc"),
+            Some(Command::Usage {
+                usage: ":doc <name>"
+            })
+        );
+    }
+    #[test]
+    fn repl_describe() {
+        let globals = crate::natives::globals();
+        assert_eq!(
+            describe("substring", globals.values().get("substring")),
+            "substring(s, start, end)\n    Returns the characters of s from start up to, but not including, end."
+        );
+        for (name, value) in globals.values() {
+            let doc = describe(name, Some(value));
+            assert!(doc.starts_with(&format!("{}(", name)), "{}", doc);
+            assert!(doc.ends_with('.'), "{}", doc);
+        }
+        assert_eq!(describe("n", Some(&Value::Number(1.0))), "n: number");
+        assert_eq!(describe("nope", None), "No global named nope");
+    }
+    #[test]
+    fn repl_incomplete() {
+        assert!(!is_incomplete("print 1;"));
+        assert!(is_incomplete("fun f() {"));
+        assert!(is_incomplete("fun f() {\n  print (1 +"));
+        assert!(!is_incomplete("fun f() {\n  print 1;\n}"));
+        assert!(is_incomplete("print \"a"));
+        assert!(!is_incomplete("print \"{\";"));
+        assert!(!is_incomplete("// {"));
+        assert!(!is_incomplete(":ast {"));
+        assert!(!is_incomplete("}"));
+    }
+    #[test]
+    fn repl_parse_unknown() {
+        assert_eq!(
+            Command::parse(":frobnicate now"),
+            Some(Command::Unknown {
+                name: String::from("frobnicate")
+            })
+        );
+    }
+    #[test]
+    fn repl_split_rc() {
+        let (source, commands) = split_rc("fun sq(n) {\n  return n * n;\n}\n:time\nvar x = 1;\n");
+        assert_eq!(source, "fun sq(n) {\n  return n * n;\n}\n\nvar x = 1;\n");
+        assert_eq!(commands, vec![Command::Time { source: None }]);
+    }
+}
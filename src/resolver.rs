@@ -0,0 +1,377 @@
+use crate::errors::InterpreterError;
+use crate::expr::{Expr, ExprVisitor};
+use crate::node::NodeId;
+use crate::stmt::{Stmt, StmtVisitor};
+use crate::suggest;
+use crate::token::Token;
+use crate::value::Value;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Resolved scope depths for one program. Functions keep the table of the
+// program that declared them, so it lives exactly as long as code that can
+// still run against it.
+pub(crate) type Locals = Rc<HashMap<NodeId, usize>>;
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "resolve", level = "debug", skip_all)
+)]
+pub(crate) fn resolve(stmts: &[Stmt]) -> (HashMap<NodeId, usize>, Vec<InterpreterError>) {
+    let mut resolver = Resolver::default();
+    resolver.stmts(stmts);
+    (resolver.locals, resolver.errors)
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "resolve", level = "debug", skip_all, fields(strict = true))
+)]
+pub(crate) fn resolve_strict<'a>(
+    stmts: &[Stmt],
+    globals: impl Iterator<Item = &'a String>,
+) -> (HashMap<NodeId, usize>, Vec<InterpreterError>) {
+    let mut resolver = Resolver {
+        strict: true,
+        globals: globals.map(|name| (name.clone(), true)).collect(),
+        ..Resolver::default()
+    };
+    for stmt in stmts.iter() {
+        if let Stmt::Variable { name, .. } | Stmt::Function { name, .. } = stmt {
+            resolver.globals.entry(name.to_string()).or_insert(false);
+        }
+    }
+    resolver.stmts(stmts);
+    (resolver.locals, resolver.errors)
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn resolve_function(params: &[Token], body: &[Stmt]) -> HashMap<NodeId, usize> {
+    let mut resolver = Resolver::default();
+    resolver.function(params, body);
+    resolver.locals
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum FunctionType {
+    #[default]
+    None,
+    Function,
+}
+
+#[derive(Default)]
+struct Resolver {
+    scopes: Vec<HashMap<String, bool>>,
+    locals: HashMap<NodeId, usize>,
+    errors: Vec<InterpreterError>,
+    strict: bool,
+    globals: HashMap<String, bool>,
+    current_function: FunctionType,
+}
+
+impl StmtVisitor<()> for Resolver {
+    fn visit_block(&mut self, stmts: &[Stmt]) {
+        self.scopes.push(HashMap::default());
+        self.stmts(stmts);
+        self.scopes.pop();
+    }
+    fn visit_variable(&mut self, name: &Token, initializer: Option<&Expr>) {
+        self.check_redeclared(name);
+        self.declare(name, false);
+        if let Some(initializer) = initializer {
+            initializer.accept(self);
+        }
+        self.declare(name, true);
+    }
+    fn visit_print(&mut self, _keyword: &Token, expr: &Expr) {
+        expr.accept(self)
+    }
+    fn visit_expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &[Stmt]) {
+        self.check_redeclared(name);
+        self.declare(name, true);
+        self.function(params, body);
+    }
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) {
+        if self.current_function == FunctionType::None {
+            self.error(keyword, String::from("Can't return from top-level code."));
+        }
+        if let Some(value) = value {
+            value.accept(self);
+        }
+    }
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt) {
+        condition.accept(self);
+        body.accept(self)
+    }
+}
+
+impl ExprVisitor<()> for Resolver {
+    fn visit_assign(&mut self, id: NodeId, name: &Token, value: &Expr) {
+        value.accept(self);
+        if !self.local(id, &name.to_string()) {
+            self.check_global(name, "Can't assign to undeclared variable");
+        }
+    }
+    fn visit_binary(&mut self, left: &Expr, _operator: &Token, right: &Expr) {
+        left.accept(self);
+        right.accept(self)
+    }
+    fn visit_call(&mut self, callee: &Expr, _paren: &Token, arguments: &[Expr]) {
+        callee.accept(self);
+        for argument in arguments.iter() {
+            argument.accept(self);
+        }
+    }
+    fn visit_grouping(&mut self, expression: &Expr) {
+        expression.accept(self)
+    }
+    fn visit_index(&mut self, object: &Expr, _bracket: &Token, index: &Expr) {
+        object.accept(self);
+        index.accept(self)
+    }
+    fn visit_literal(&mut self, _value: &Value) {}
+    fn visit_logical(&mut self, left: &Expr, _operator: &Token, right: &Expr) {
+        left.accept(self);
+        right.accept(self)
+    }
+    fn visit_unary(&mut self, _operator: &Token, right: &Expr) {
+        right.accept(self)
+    }
+    fn visit_variable(&mut self, id: NodeId, name: &Token) {
+        if let Token::This { .. } = name {
+            if self.current_function == FunctionType::None {
+                self.error(
+                    name,
+                    String::from("Can't use 'this' outside of a function."),
+                );
+            }
+            return;
+        }
+        let name_str = name.to_string();
+        if self
+            .scopes
+            .last()
+            .and_then(|scope| scope.get(&name_str))
+            .is_some_and(|defined| !defined)
+        {
+            self.error(
+                name,
+                format!(
+                    "Can't read local variable {} in its own initializer",
+                    name_str
+                ),
+            );
+        }
+        if !self.local(id, &name_str) {
+            self.check_global(name, "Undefined variable");
+        }
+    }
+}
+
+impl Resolver {
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts.iter() {
+            stmt.accept(self);
+        }
+    }
+    fn function(&mut self, params: &[Token], body: &[Stmt]) {
+        let enclosing = std::mem::replace(&mut self.current_function, FunctionType::Function);
+        self.scopes.push(HashMap::default());
+        for param in params.iter() {
+            self.check_redeclared(param);
+            self.declare(param, true);
+        }
+        self.stmts(body);
+        self.scopes.pop();
+        self.current_function = enclosing;
+    }
+    fn declare(&mut self, name: &Token, defined: bool) {
+        match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.to_string(), defined),
+            None if self.strict => self.globals.insert(name.to_string(), defined),
+            None => None,
+        };
+    }
+    fn local(&mut self, id: NodeId, name: &str) -> bool {
+        match self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name))
+        {
+            Some(depth) => {
+                self.locals.insert(id, depth);
+                true
+            }
+            None => false,
+        }
+    }
+    fn check_redeclared(&mut self, name: &Token) {
+        let name_str = name.to_string();
+        let declared = match self.scopes.last() {
+            Some(scope) => scope.contains_key(&name_str),
+            None => self.globals.get(&name_str) == Some(&true),
+        };
+        if self.strict && declared {
+            self.error(
+                name,
+                format!("{} is already declared in this scope", name_str),
+            );
+        }
+    }
+    fn check_global(&mut self, name: &Token, undeclared: &str) {
+        if !self.strict {
+            return;
+        }
+        let name_str = name.to_string();
+        match self.globals.get(&name_str) {
+            None => {
+                let message =
+                    match suggest::closest(&name_str, self.globals.keys().map(String::as_str)) {
+                        Some(similar) => {
+                            format!("{} {}; did you mean '{}'?", undeclared, name_str, similar)
+                        }
+                        None => format!("{} {}", undeclared, name_str),
+                    };
+                self.error(name, message)
+            }
+            Some(false) if self.current_function == FunctionType::None => {
+                self.error(name, format!("{} is used before its definition", name_str))
+            }
+            Some(_) => {}
+        }
+    }
+    fn error(&mut self, name: &Token, message: String) {
+        self.errors.push(InterpreterError::SyntaxError {
+            line: name.get_line().unwrap_or(0),
+            message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::InterpreterResult;
+    use crate::interpreter::Interpreter;
+    use crate::parser;
+    use crate::scanner::scan_tokens;
+    fn run(source: &str) -> InterpreterResult<String> {
+        let (stmts, errs) = parser::parse(scan_tokens(String::from(source))?);
+        if let Some(err) = errs.into_iter().next() {
+            return Err(err);
+        }
+        let interpreter = Interpreter::default();
+        interpreter.capture_output(true);
+        interpreter.interpret_all(&stmts)?;
+        Ok(interpreter.take_output())
+    }
+    #[test]
+    fn resolver_depths() -> InterpreterResult<()> {
+        let (stmts, _) = parser::parse(scan_tokens(String::from(
+            "var a = 1; { var b = a; { b = 2; } }",
+        ))?);
+        let (locals, errors) = resolve(&stmts);
+        assert!(errors.is_empty());
+        let depths = |stmt: &Stmt| match stmt {
+            Stmt::Variable {
+                initializer: Some(init),
+                ..
+            } => locals.get(&init.id()).copied(),
+            Stmt::Expr { expr, .. } => locals.get(&expr.id()).copied(),
+            _ => None,
+        };
+        match &stmts[1] {
+            Stmt::Block { stmts, .. } => {
+                assert_eq!(depths(&stmts[0]), None);
+                match &stmts[1] {
+                    Stmt::Block { stmts, .. } => assert_eq!(depths(&stmts[0]), Some(1)),
+                    stmt => panic!("expected a block, got {:?}", stmt),
+                }
+            }
+            stmt => panic!("expected a block, got {:?}", stmt),
+        }
+        Ok(())
+    }
+    #[test]
+    fn resolver_closure_shadowing() -> InterpreterResult<()> {
+        let source = "var a = \"global\";\n{\n  fun show() { print a; }\n  show();\n  var a = \"block\";\n  show();\n  print a;\n}\n";
+        assert_eq!(run(source)?, "global\nglobal\nblock\n");
+        Ok(())
+    }
+    #[test]
+    fn resolver_own_initializer() {
+        assert!(matches!(
+            run("var a = 1; { var a = a; }"),
+            Err(InterpreterError::SyntaxError { .. })
+        ));
+        assert!(run("var a = 1; var a = a + 1; print a;").is_ok());
+    }
+    #[test]
+    fn resolver_top_level_return() -> InterpreterResult<()> {
+        let errors = |source: &str| -> InterpreterResult<Vec<String>> {
+            let (stmts, _) = parser::parse(scan_tokens(String::from(source))?);
+            Ok(resolve(&stmts)
+                .1
+                .iter()
+                .map(|err| err.to_string())
+                .collect())
+        };
+        assert_eq!(
+            errors("print 1;\nreturn 2;")?,
+            vec![String::from(
+                "Syntax error on line 2: Can't return from top-level code."
+            )]
+        );
+        assert_eq!(errors("{ while (true) return; }")?.len(), 1);
+        assert!(errors("fun f() { fun g() { return 1; } return g(); }")?.is_empty());
+        assert!(matches!(
+            run("return;"),
+            Err(err @ InterpreterError::SyntaxError { .. }) if err.exit_code() == 65
+        ));
+        Ok(())
+    }
+    #[test]
+    fn resolver_this() -> InterpreterResult<()> {
+        let errors = |source: &str| -> InterpreterResult<usize> {
+            let (stmts, _) = parser::parse(scan_tokens(String::from(source))?);
+            Ok(resolve_strict(&stmts, std::iter::empty()).1.len())
+        };
+        assert_eq!(errors("print this;")?, 1);
+        assert_eq!(errors("fun f() { fun g() { return this; } return g; }")?, 0);
+        Ok(())
+    }
+    #[test]
+    fn resolver_strict() -> InterpreterResult<()> {
+        let strict = |source: &str| -> InterpreterResult<Vec<String>> {
+            let (stmts, _) = parser::parse(scan_tokens(String::from(source))?);
+            let globals = [String::from("clock")];
+            Ok(resolve_strict(&stmts, globals.iter())
+                .1
+                .iter()
+                .map(|err| err.to_string())
+                .collect())
+        };
+        assert!(strict("var a = 1; { var b = a + 1; { var a = clock(); } }")?.is_empty());
+        assert!(strict("fun f() { return g; } var g = 1;")?.is_empty());
+        assert_eq!(strict("var clock = 1;")?.len(), 1);
+        assert_eq!(strict("{ var a = 1; a = b; var a = 2; }")?.len(), 2);
+        assert_eq!(strict("print g; var g = 1;")?.len(), 1);
+        assert_eq!(
+            strict("var count = 1; print coutn;")?,
+            vec![String::from(
+                "Syntax error on line 1: Undefined variable coutn; did you mean 'count'?"
+            )]
+        );
+        assert_eq!(
+            strict("print lock();")?,
+            vec![String::from(
+                "Syntax error on line 1: Undefined variable lock; did you mean 'clock'?"
+            )]
+        );
+        Ok(())
+    }
+}
@@ -1,29 +1,176 @@
 use crate::errors::{InterpreterError, InterpreterResult};
-use crate::token::Token;
+use crate::limits::Limits;
+use crate::token::{keyword, Token};
 use peekmore::{PeekMore, PeekMoreIterator};
 use std::str::Chars;
 
-type Cs<'a> = PeekMoreIterator<Chars<'a>>;
+type Cs<'a> = Cursor<'a>;
+
+struct Cursor<'a> {
+    chars: PeekMoreIterator<Chars<'a>>,
+    column: usize,
+    offset: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek()
+    }
+    fn peek_nth(&mut self, n: usize) -> Option<&char> {
+        self.chars.peek_nth(n)
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        match c {
+            Some('\n' | '\r') => self.column = 0,
+            Some(_) => self.column += 1,
+            None => {}
+        }
+        self.offset += c.map_or(0, char::len_utf8);
+        c
+    }
+}
 
 pub(crate) fn scan_tokens(s: String) -> InterpreterResult<Vec<Token>> {
-    let mut tokens = Vec::with_capacity(s.capacity());
-    let mut chars = s.chars().peekmore();
-    let mut line = 1;
-    let mut errored = false;
-    while let Some(result) = scan_token(&mut chars, &mut line) {
+    let (tokens, errors) = scan(s);
+    for e in errors.iter() {
+        println!("{:?}", e);
+    }
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(InterpreterError::Parse {
+            line: tokens.last().and_then(Token::get_line).unwrap_or(1),
+        })
+    }
+}
+
+pub(crate) fn scan(s: String) -> (Vec<Token>, Vec<InterpreterError>) {
+    collect(TokenStream::new(&s))
+}
+
+pub(crate) fn scan_limited(s: &str, limits: &Limits) -> (Vec<Token>, Vec<InterpreterError>) {
+    collect(TokenStream::new(s).limited(limits))
+}
+
+#[cfg(test)]
+pub(crate) fn scan_with_trivia(s: String) -> (Vec<Token>, Vec<InterpreterError>) {
+    collect(TokenStream::with_trivia(&s))
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "scan", level = "debug", skip_all)
+)]
+fn collect(stream: TokenStream<'_>) -> (Vec<Token>, Vec<InterpreterError>) {
+    let mut tokens = Vec::default();
+    let mut errors = Vec::default();
+    for result in stream {
         match result {
             Ok(t) => tokens.push(t),
-            Err(e) => {
-                errored = true;
-                println!("{:?}", e)
-            }
+            Err(e) => errors.push(e),
         };
     }
-    tokens.push(Token::Eof { line });
-    if errored {
-        Err(InterpreterError::Parse { line })
-    } else {
-        Ok(tokens)
+    (tokens, errors)
+}
+
+pub(crate) struct TokenStream<'a> {
+    chars: Cs<'a>,
+    line: usize,
+    done: bool,
+    trivia: bool,
+    tokens: usize,
+    max_tokens: Option<usize>,
+    max_literal_bytes: Option<usize>,
+}
+
+impl<'a> TokenStream<'a> {
+    pub(crate) fn new(s: &'a str) -> Self {
+        Self {
+            chars: Cursor {
+                chars: s.chars().peekmore(),
+                column: 0,
+                offset: 0,
+            },
+            line: 1,
+            done: false,
+            trivia: false,
+            tokens: 0,
+            max_tokens: None,
+            max_literal_bytes: None,
+        }
+    }
+    pub(crate) fn with_trivia(s: &'a str) -> Self {
+        Self {
+            trivia: true,
+            ..Self::new(s)
+        }
+    }
+    // For rescanning a slice of a larger source.
+    pub(crate) fn at_line(self, line: usize) -> Self {
+        Self { line, ..self }
+    }
+    // Byte offset of the next unscanned character.
+    pub(crate) fn offset(&self) -> usize {
+        self.chars.offset
+    }
+    pub(crate) fn limited(self, limits: &Limits) -> Self {
+        Self {
+            max_tokens: limits.max_tokens,
+            max_literal_bytes: limits.max_literal_bytes,
+            ..self
+        }
+    }
+    // Untrusted input can't be stopped from being long, but it can be stopped
+    // from turning into an equally long token list and AST.
+    fn check(&mut self, token: Token) -> InterpreterResult<Token> {
+        self.tokens += 1;
+        let line = token.get_line().unwrap_or(self.line);
+        if let Some(max) = self.max_tokens.filter(|&max| self.tokens > max) {
+            self.done = true;
+            return Err(InterpreterError::Interpreter {
+                line,
+                message: format!("More than {} tokens", max),
+            });
+        }
+        match (&token, self.max_literal_bytes) {
+            (
+                Token::r#String { lexeme, .. }
+                | Token::Bytes { lexeme, .. }
+                | Token::Number { lexeme, .. }
+                | Token::BigInt { lexeme, .. },
+                Some(max),
+            ) if lexeme.len() > max => Err(InterpreterError::Interpreter {
+                line,
+                message: format!("Literal longer than {} bytes", max),
+            }),
+            _ => Ok(token),
+        }
+    }
+}
+
+impl Iterator for TokenStream<'_> {
+    type Item = InterpreterResult<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            match scan_token(&mut self.chars, &mut self.line) {
+                Some(Ok(token)) if token.is_trivia() && !self.trivia => {}
+                Some(result) => return Some(result.and_then(|t| self.check(t))),
+                None => {
+                    self.done = true;
+                    return Some(Ok(Token::Eof { line: self.line }));
+                }
+            }
+        }
     }
 }
 
@@ -33,12 +180,15 @@ fn scan_token(cs: &mut Cs<'_>, line: &mut usize) -> Option<InterpreterResult<Tok
         Some(')') => Some(Ok(Token::RightParen { line: *line })),
         Some('{') => Some(Ok(Token::LeftBrace { line: *line })),
         Some('}') => Some(Ok(Token::RightBrace { line: *line })),
+        Some('[') => Some(Ok(Token::LeftBracket { line: *line })),
+        Some(']') => Some(Ok(Token::RightBracket { line: *line })),
         Some(',') => Some(Ok(Token::Comma { line: *line })),
         Some('.') => Some(Ok(Token::Dot { line: *line })),
         Some('-') => Some(Ok(Token::Minus { line: *line })),
         Some('+') => Some(Ok(Token::Plus { line: *line })),
         Some(';') => Some(Ok(Token::Semicolon { line: *line })),
         Some('*') => Some(Ok(Token::Star { line: *line })),
+        Some('?') if match_c(cs, '?') => Some(Ok(Token::QuestionQuestion { line: *line })),
         Some('!') => {
             if match_c(cs, '=') {
                 Some(Ok(Token::BangEqual { line: *line }))
@@ -69,12 +219,13 @@ fn scan_token(cs: &mut Cs<'_>, line: &mut usize) -> Option<InterpreterResult<Tok
         }
         Some('/') => Some(match_slash(cs, *line)),
         Some('"') => Some(string(cs, line)),
+        Some('b') if cs.peek() == Some(&'"') => Some(bytes(cs, *line)),
         Some(c) if c.is_ascii_whitespace() => Some(whitespace(c, cs, line)),
         Some(c) if c.is_ascii_digit() => Some(number(c, cs, *line)),
         Some(c) if c.is_ascii_alphabetic() || c == '_' => Some(identifier(c, cs, *line)),
         Some(c) => Some(Err(InterpreterError::Interpreter {
             line: *line,
-            message: format!("Unknown token {c}"),
+            message: format!("Unknown token {c} at column {}", cs.column),
         })),
         None => None,
     }
@@ -95,37 +246,39 @@ fn match_c(cs: &mut Cs<'_>, to_match: char) -> bool {
 
 fn match_slash(cs: &mut Cs<'_>, line: usize) -> InterpreterResult<Token> {
     if match_c(cs, '/') {
+        let mut text = String::from("//");
         while let Some(c) = cs.peek() {
-            if *c == '\n' {
+            if *c == '\n' || *c == '\r' {
                 break;
             } else {
-                cs.next();
+                text.push(cs.next().unwrap());
             }
         }
-        Ok(Token::Comment)
+        Ok(Token::Comment { text, line })
     } else {
         Ok(Token::Slash { line })
     }
 }
 
 fn whitespace(c: char, cs: &mut Cs<'_>, line: &mut usize) -> InterpreterResult<Token> {
-    if c == '\n' {
-        *line += 1;
-    }
-    while let Some(c) = cs.peek() {
-        if *c == '\n' {
+    let start = *line;
+    let mut text = String::default();
+    let mut c = Some(c);
+    while let Some(ch) = c {
+        if ch == '\n' || ch == '\r' && cs.peek() != Some(&'\n') {
             *line += 1;
+        }
+        text.push(ch);
+        c = cs.peek().copied().filter(char::is_ascii_whitespace);
+        if c.is_some() {
             cs.next();
-        } else if c.is_ascii_whitespace() {
-            cs.next();
-        } else {
-            break;
         }
     }
-    Ok(Token::Whitespace)
+    Ok(Token::Whitespace { text, line: start })
 }
 
 fn string(cs: &mut Cs<'_>, line: &mut usize) -> InterpreterResult<Token> {
+    let (start_line, start_column) = (*line, cs.column);
     let mut s = String::default();
     while let Some(c) = cs.peek() {
         match c {
@@ -141,36 +294,103 @@ fn string(cs: &mut Cs<'_>, line: &mut usize) -> InterpreterResult<Token> {
                 s.push(cs.next().unwrap());
                 *line += 1;
             }
+            '\r' => {
+                cs.next();
+                if cs.peek() != Some(&'\n') {
+                    s.push('\n');
+                    *line += 1;
+                }
+            }
             _ => {
                 s.push(cs.next().unwrap());
             }
         }
     }
-    Err(InterpreterError::Interpreter {
-        line: *line,
-        message: String::from("Unterminated string"),
+    Err(InterpreterError::UnterminatedString {
+        line: start_line,
+        column: start_column,
     })
 }
 
-fn number(c: char, cs: &mut Cs<'_>, line: usize) -> InterpreterResult<Token> {
-    let mut s = String::from(c);
-    while let Some(c) = cs.peek() {
+// Byte literals stay on one line and accept \xHH, \n, \r, \t, \0, \\ and \"
+// escapes; any other character contributes its UTF-8 encoding.
+fn bytes(cs: &mut Cs<'_>, line: usize) -> InterpreterResult<Token> {
+    let start_column = cs.column;
+    let mut lexeme = String::from("b");
+    lexeme.push(cs.next().unwrap());
+    let mut literal = Vec::default();
+    let error = |message: String| InterpreterError::Interpreter { line, message };
+    loop {
+        let c = match cs.next() {
+            Some('\n' | '\r') | None => {
+                return Err(error(format!(
+                    "Unterminated byte string starting at column {}",
+                    start_column
+                )))
+            }
+            Some(c) => c,
+        };
+        lexeme.push(c);
         match c {
-            '.' => {
-                if let Some(nxt) = cs.peek_nth(2) {
-                    if nxt.is_ascii_digit() {
-                        s.push(cs.next().unwrap());
+            '"' => {
+                return Ok(Token::Bytes {
+                    lexeme,
+                    literal,
+                    line,
+                })
+            }
+            '\\' => {
+                let escape = cs.next().unwrap_or_default();
+                lexeme.push(escape);
+                match escape {
+                    'n' => literal.push(b'\n'),
+                    'r' => literal.push(b'\r'),
+                    't' => literal.push(b'\t'),
+                    '0' => literal.push(0),
+                    '\\' | '"' => literal.push(escape as u8),
+                    'x' => {
+                        let hex: String = cs.by_ref().take(2).collect();
+                        lexeme.push_str(&hex);
+                        let byte = (hex.len() == 2)
+                            .then(|| u8::from_str_radix(&hex, 16).ok())
+                            .flatten()
+                            .ok_or_else(|| error(format!("Invalid byte escape \\x{}", hex)))?;
+                        literal.push(byte);
                     }
-                } else {
-                    break;
+                    _ => return Err(error(format!("Unknown escape \\{} in byte string", escape))),
                 }
             }
+            c => literal.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+}
+
+fn number(c: char, cs: &mut Cs<'_>, line: usize) -> InterpreterResult<Token> {
+    let mut s = String::from(c);
+    while let Some(c) = cs.peek().copied() {
+        match c {
+            '.' if !s.contains('.') && cs.peek_nth(1).is_some_and(char::is_ascii_digit) => {
+                s.push(cs.next().unwrap());
+            }
             ch if ch.is_ascii_digit() => {
                 s.push(cs.next().unwrap());
             }
             _ => break,
         }
     }
+    if !s.contains('.')
+        && cs.peek() == Some(&'n')
+        && !cs.peek_nth(1).is_some_and(|c| c.is_ascii_alphanumeric())
+    {
+        cs.next();
+        let literal = s.parse().expect("digits parse as a bigint");
+        s.push('n');
+        return Ok(Token::BigInt {
+            lexeme: s,
+            literal,
+            line,
+        });
+    }
     if let Ok(literal) = s.parse::<f32>() {
         Ok(Token::Number {
             lexeme: s,
@@ -198,30 +418,11 @@ fn identifier(c: char, cs: &mut Cs<'_>, line: usize) -> InterpreterResult<Token>
 }
 
 fn ident_t(s: String, line: usize) -> InterpreterResult<Token> {
-    let res = match s.as_str() {
-        "and" => Token::And { line },
-        "class" => Token::Class { line },
-        "else" => Token::Else { line },
-        "false" => Token::False { line },
-        "for" => Token::For { line },
-        "fun" => Token::Fun { line },
-        "if" => Token::If { line },
-        "nil" => Token::Nil { line },
-        "or" => Token::Or { line },
-        "print" => Token::Print { line },
-        "return" => Token::Return { line },
-        "super" => Token::Super { line },
-        "this" => Token::This { line },
-        "true" => Token::True { line },
-        "var" => Token::Var { line },
-        "while" => Token::While { line },
-        _ => Token::Identifier {
-            lexeme: s.clone(),
-            literal: s,
-            line,
-        },
-    };
-    Ok(res)
+    Ok(keyword(&s, line).unwrap_or_else(|| Token::Identifier {
+        lexeme: s.clone(),
+        literal: s,
+        line,
+    }))
 }
 
 #[cfg(test)]
@@ -229,7 +430,88 @@ mod test {
     use super::*;
 
     fn st(s: &str) -> InterpreterResult<Vec<Token>> {
-        scan_tokens(s.into())
+        let (tokens, errors) = scan_with_trivia(s.into());
+        match errors.into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(tokens),
+        }
+    }
+    fn ws(text: &str, line: usize) -> Token {
+        Token::Whitespace {
+            text: String::from(text),
+            line,
+        }
+    }
+    #[test]
+    fn scanner_crlf_and_unicode() -> InterpreterResult<()> {
+        let tokens = st("// hi\r\nvar s = \"x\r\ny\";\r\nprint s;\rprint 2;")?;
+        assert_eq!(
+            tokens[0],
+            Token::Comment {
+                text: String::from("// hi"),
+                line: 1
+            }
+        );
+        assert!(tokens.iter().any(|t| matches!(
+            t,
+            Token::r#String { literal, line: 3, .. } if literal == "x\ny"
+        )));
+        assert!(tokens.contains(&Token::Print { line: 4 }));
+        assert!(tokens.contains(&Token::Print { line: 5 }));
+        assert_eq!(tokens.last(), Some(&Token::Eof { line: 5 }));
+        let (_, errors) = scan("print \"héllo\"; @\nvar é = 1;\nprint \"ünterminated".into());
+        let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "[1] Error: Unknown token @ at column 16",
+                "[2] Error: Unknown token é at column 5",
+                "[3] Error: Unterminated string starting at column 7",
+            ]
+        );
+        Ok(())
+    }
+    #[test]
+    fn scanner_skips_trivia_by_default() -> InterpreterResult<()> {
+        let source = "// note\nprint 1; // trailing\n";
+        assert_eq!(
+            scan_tokens(source.into())?,
+            vec![
+                Token::Print { line: 2 },
+                Token::Number {
+                    lexeme: "1".into(),
+                    literal: 1.0,
+                    line: 2
+                },
+                Token::Semicolon { line: 2 },
+                Token::Eof { line: 3 },
+            ]
+        );
+        let (tokens, _) = scan_with_trivia(source.into());
+        let text: String = tokens.iter().map(Token::lexeme).collect();
+        assert_eq!(text, source);
+        Ok(())
+    }
+    #[test]
+    fn scanner_token_stream() {
+        let mut stream = TokenStream::with_trivia("print 1; @");
+        assert!(matches!(stream.next(), Some(Ok(Token::Print { line: 1 }))));
+        assert!(matches!(stream.nth(1), Some(Ok(Token::Number { .. }))));
+        let rest: Vec<_> = stream.collect();
+        assert_eq!(rest.len(), 4);
+        assert!(rest[2].is_err());
+        assert!(matches!(rest[3], Ok(Token::Eof { line: 1 })));
+    }
+    #[test]
+    fn scanner_scan_collects_errors() {
+        let (tokens, errors) = scan("1 @ 2 #".into());
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(
+            errors[0],
+            InterpreterError::Interpreter { line: 1, .. }
+        ));
+        assert_eq!(tokens.last(), Some(&Token::Eof { line: 1 }));
+        assert!(scan_tokens("1 @ 2".into()).is_err());
     }
     #[test]
     fn scanner_singletons() -> InterpreterResult<()> {
@@ -295,14 +577,26 @@ mod test {
     }
     #[test]
     fn scanner_slash() -> InterpreterResult<()> {
-        assert_eq!(Token::Comment, st("// comment\n")?[0]);
+        assert_eq!(
+            Token::Comment {
+                text: String::from("// comment"),
+                line: 1
+            },
+            st("// comment\n")?[0]
+        );
         assert_eq!(Token::Slash { line: 1 }, st("/")?[0]);
         let res = st("/,")?;
         assert_eq!(Token::Slash { line: 1 }, res[0]);
         assert_eq!(Token::Comma { line: 1 }, res[1]);
         let res = st("// comment\n,")?;
-        assert_eq!(Token::Comment, res[0]);
-        assert_eq!(Token::Whitespace, res[1]);
+        assert_eq!(
+            Token::Comment {
+                text: String::from("// comment"),
+                line: 1
+            },
+            res[0]
+        );
+        assert_eq!(ws("\n", 1), res[1]);
         assert_eq!(Token::Comma { line: 2 }, res[2]);
         Ok(())
     }
@@ -340,19 +634,20 @@ mod test {
     }
     #[test]
     fn scanner_whitespace_dont_inc_line() -> InterpreterResult<()> {
-        assert_eq!(Token::Whitespace, st(" ")?[0]);
-        assert_eq!(Token::Whitespace, st("\t")?[0]);
-        assert_eq!(Token::Whitespace, st("     \t\t\r   ")?[0]);
+        assert_eq!(ws(" ", 1), st(" ")?[0]);
+        assert_eq!(ws("\t", 1), st("\t")?[0]);
+        assert_eq!(ws("     \t\t", 1), st("     \t\t")?[0]);
+        assert_eq!(st("     \t\t")?[1], Token::Eof { line: 1 });
         Ok(())
     }
     #[test]
     fn scanner_whitespace_inc_line() -> InterpreterResult<()> {
         let res = st("  ,\n,  ")?;
-        assert_eq!(Token::Whitespace, res[0]);
+        assert_eq!(ws("  ", 1), res[0]);
         assert_eq!(Token::Comma { line: 1 }, res[1]);
-        assert_eq!(Token::Whitespace, res[2]);
+        assert_eq!(ws("\n", 1), res[2]);
         assert_eq!(Token::Comma { line: 2 }, res[3]);
-        assert_eq!(Token::Whitespace, res[4]);
+        assert_eq!(ws("  ", 2), res[4]);
         Ok(())
     }
     #[test]
@@ -402,6 +697,61 @@ mod test {
         );
         assert_eq!(Token::Dot { line: 1 }, res[1]);
         assert_eq!(Token::Comma { line: 1 }, res[2]);
+        let res = st("3.0;")?;
+        assert_eq!(
+            Token::Number {
+                lexeme: "3.0".into(),
+                literal: 3.0,
+                line: 1
+            },
+            res[0]
+        );
+        assert_eq!(Token::Semicolon { line: 1 }, res[1]);
+        assert_eq!(
+            Token::Number {
+                lexeme: "1.5".into(),
+                literal: 1.5,
+                line: 1
+            },
+            st("1.5")?[0]
+        );
+        Ok(())
+    }
+    #[test]
+    fn scanner_bytes() -> InterpreterResult<()> {
+        assert_eq!(
+            Token::Bytes {
+                lexeme: "b\"a\\x00\\xFF\\n\\\"é\"".into(),
+                literal: vec![b'a', 0, 0xff, b'\n', b'"', 0xc3, 0xa9],
+                line: 1
+            },
+            st("b\"a\\x00\\xFF\\n\\\"é\"")?[0]
+        );
+        let res = st("b[0]")?;
+        assert!(matches!(res[0], Token::Identifier { .. }));
+        assert_eq!(Token::LeftBracket { line: 1 }, res[1]);
+        assert_eq!(Token::RightBracket { line: 1 }, res[3]);
+        assert!(st("b\"\\x0\"").is_err());
+        assert!(st("b\"\\q\"").is_err());
+        assert!(st("b\"abc").is_err());
+        Ok(())
+    }
+    #[test]
+    fn scanner_bigint() -> InterpreterResult<()> {
+        assert_eq!(
+            Token::BigInt {
+                lexeme: "123n".into(),
+                literal: 123.into(),
+                line: 1
+            },
+            st("123n")?[0]
+        );
+        let res = st("1.5n")?;
+        assert!(matches!(res[0], Token::Number { .. }));
+        assert!(matches!(res[1], Token::Identifier { .. }));
+        let res = st("12nope")?;
+        assert!(matches!(res[0], Token::Number { .. }));
+        assert!(matches!(res[1], Token::Identifier { .. }));
         Ok(())
     }
     #[test]
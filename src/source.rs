@@ -0,0 +1,225 @@
+use crate::capabilities::Capabilities;
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::interpreter::Interpreter;
+use crate::limits::Limits;
+use crate::parser;
+use crate::resolver;
+use crate::scanner::scan_limited;
+use crate::stmt::Stmt;
+use std::cell::Cell;
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+use std::thread;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    pub(crate) stmts: Vec<Stmt>,
+}
+
+impl Program {
+    pub fn len(&self) -> usize {
+        self.stmts.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.stmts.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl From<InterpreterError> for Diagnostic {
+    fn from(err: InterpreterError) -> Self {
+        Self {
+            line: err.line(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+pub fn parse_source(source: &str) -> Result<Program, Vec<Diagnostic>> {
+    parse_limited(source, &Limits::default())
+}
+
+pub fn __lox_program(source: &str) -> Program {
+    parse_source(source).unwrap_or_else(|errs| {
+        let errs: Vec<String> = errs.iter().map(Diagnostic::to_string).collect();
+        panic!("Invalid lox! source: {}", errs.join("; "))
+    })
+}
+
+fn parse_limited(source: &str, limits: &Limits) -> Result<Program, Vec<Diagnostic>> {
+    guarded(|| {
+        let (tokens, mut errs) = scan_limited(source, limits);
+        let (stmts, parse_errs) = parser::parse_with(tokens, limits.nesting());
+        errs.extend(parse_errs);
+        if errs.is_empty() {
+            errs.extend(resolver::resolve(&stmts).1);
+        }
+        if errs.is_empty() {
+            Ok(Program { stmts })
+        } else {
+            Err(errs.into_iter().map(Diagnostic::from).collect())
+        }
+    })
+}
+
+// Runs untrusted source with no capabilities and captured output. Evaluation
+// happens on its own thread with a stack sized for the default limits, so
+// deep recursion ends in a call depth error rather than a native stack
+// overflow, which no panic handler could catch. A panic in the interpreter
+// comes back as a diagnostic, without the panic hook's message on stderr.
+pub fn eval_source(source: &str, limits: Limits) -> Result<String, Vec<Diagnostic>> {
+    let source = String::from(source);
+    thread::Builder::new()
+        .name(String::from("eval_source"))
+        .stack_size(EVAL_STACK)
+        .spawn(move || {
+            let program = parse_limited(&source, &limits)?;
+            guarded(|| {
+                let mut interpreter = Interpreter::default();
+                interpreter.set_capabilities(Capabilities::none());
+                interpreter.set_limits(limits);
+                interpreter.set_input(Box::new(io::empty()));
+                interpreter.capture_output(true);
+                match interpreter.interpret_all(&program.stmts) {
+                    Ok(_) => Ok(interpreter.take_output()),
+                    Err(err) => Err(vec![Diagnostic::from(err)]),
+                }
+            })
+        })
+        .map_err(|err| vec![Diagnostic::from(InterpreterError::from(err))])?
+        .join()
+        .unwrap_or_else(|_| Err(internal_error()))
+}
+
+fn guarded<T>(f: impl FnOnce() -> Result<T, Vec<Diagnostic>>) -> Result<T, Vec<Diagnostic>> {
+    QUIET_HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !QUIET.with(Cell::get) {
+                hook(info)
+            }
+        }));
+    });
+    let quiet = QUIET.with(|q| q.replace(true));
+    let res = panic::catch_unwind(AssertUnwindSafe(f));
+    QUIET.with(|q| q.set(quiet));
+    res.unwrap_or_else(|_| Err(internal_error()))
+}
+
+fn internal_error() -> Vec<Diagnostic> {
+    vec![Diagnostic {
+        line: None,
+        message: String::from("Internal error: the interpreter panicked"),
+    }]
+}
+
+// Room for DEFAULT_MAX_CALL_DEPTH calls even in a debug build.
+const EVAL_STACK: usize = 64 * 1024 * 1024;
+
+// The panic hook is process-wide, so it's replaced once with one that stays
+// quiet only on threads inside `guarded`.
+static QUIET_HOOK: Once = Once::new();
+
+thread_local! {
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// Scripts saved by some Windows editors start with a byte order mark, and
+// older ones may be Latin-1, which maps each byte straight to a code point.
+pub(crate) fn decode(fname: &str, bytes: Vec<u8>, latin1: bool) -> InterpreterResult<String> {
+    let (skipped, bytes) = match bytes.strip_prefix(BOM) {
+        Some(rest) => (BOM.len(), rest.to_vec()),
+        None => (0, bytes),
+    };
+    String::from_utf8(bytes).or_else(|e| {
+        let valid = e.utf8_error().valid_up_to();
+        let bytes = e.into_bytes();
+        if latin1 {
+            return Ok(bytes.iter().map(|&b| char::from(b)).collect());
+        }
+        Err(InterpreterError::Encoding {
+            fname: String::from(fname),
+            offset: skipped + valid,
+            line: bytes[..valid].iter().filter(|&&b| b == b'\n').count() + 1,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn source_parse() {
+        assert_eq!(parse_source("var a = 1; print a;").map(|p| p.len()), Ok(2));
+        let errs = parse_source("var = 1;\n@").unwrap_err();
+        assert_eq!(errs.len(), 2);
+        assert_eq!(errs[0].line, Some(2));
+    }
+    #[test]
+    fn source_eval() {
+        assert_eq!(
+            eval_source("print 1 + 2; print 3.5;", Limits::default()),
+            Ok(String::from("3\n3.5\n"))
+        );
+        let errs = eval_source("print getenv(\"HOME\");", Limits::default()).unwrap_err();
+        assert_eq!(errs[0].line, Some(1));
+        assert_eq!(
+            errs[0].message,
+            "Runtime error on line 1: environment access is not allowed"
+        );
+        let limits = Limits {
+            max_expressions: Some(100),
+            ..Limits::default()
+        };
+        assert!(eval_source("while (true) {}", limits).is_err());
+        assert_eq!(
+            eval_source("print readLine();", limits),
+            Ok(String::from("nil\n"))
+        );
+        let errs = eval_source("fun f() { f(); } f();", Limits::default()).unwrap_err();
+        assert_eq!(errs[0].message, "Exceeded call depth limit of 64");
+    }
+    #[test]
+    fn source_guarded() {
+        let errs = guarded::<()>(|| panic!("boom")).unwrap_err();
+        assert_eq!(errs[0].message, "Internal error: the interpreter panicked");
+        assert!(!QUIET.with(Cell::get));
+    }
+    #[test]
+    fn source_decode() -> InterpreterResult<()> {
+        assert_eq!(
+            decode("a.lox", b"\xEF\xBB\xBFprint 1;".to_vec(), false)?,
+            "print 1;"
+        );
+        assert_eq!(decode("a.lox", b"print 1;".to_vec(), false)?, "print 1;");
+        let latin1 = b"\xEF\xBB\xBFprint 1;\nprint \"caf\xE9\";".to_vec();
+        assert_eq!(
+            decode("a.lox", latin1.clone(), true)?,
+            "print 1;\nprint \"caf\u{e9}\";"
+        );
+        match decode("a.lox", latin1, false) {
+            Err(InterpreterError::Encoding {
+                fname,
+                offset,
+                line,
+            }) => assert_eq!((fname.as_str(), offset, line), ("a.lox", 22, 2)),
+            res => panic!("{:?}", res),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use crate::errors::InterpreterResult;
+use crate::function::{Callable, LoxFunction};
+use crate::interpreter::Interpreter;
+use crate::resolver;
+use crate::stmt::Stmt;
+use crate::token::Token;
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+struct State {
+    globals: Vec<Global>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Global {
+    name: String,
+    value: Saved,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+enum Saved {
+    Value(Value),
+    Function {
+        name: Token,
+        params: Vec<Token>,
+        body: Vec<Stmt>,
+    },
+}
+
+pub(crate) fn save<P>(interpreter: &Interpreter, path: P) -> InterpreterResult<Vec<String>>
+where
+    P: AsRef<Path>,
+{
+    let (state, skipped) = capture(interpreter);
+    fs::write(path, serde_json::to_string_pretty(&state)?)?;
+    Ok(skipped)
+}
+
+pub(crate) fn load<P>(interpreter: &Interpreter, path: P) -> InterpreterResult<()>
+where
+    P: AsRef<Path>,
+{
+    let state: State = serde_json::from_str(&fs::read_to_string(path)?)?;
+    apply(interpreter, state);
+    Ok(())
+}
+
+fn capture(interpreter: &Interpreter) -> (State, Vec<String>) {
+    let globals = interpreter.globals();
+    let mut state = State::default();
+    let mut skipped = Vec::default();
+    let mut names = globals.values().keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        let value = match &globals.values()[name] {
+            Value::Callable(Callable::Lox(f)) if f.closure.is_none() => Saved::Function {
+                name: f.name.clone(),
+                params: f.params.clone(),
+                body: f.body.clone(),
+            },
+            Value::Callable(Callable::Lox(_)) => {
+                skipped.push(name.clone());
+                continue;
+            }
+            Value::Callable(Callable::Native(_)) => continue,
+            value => Saved::Value(value.clone()),
+        };
+        state.globals.push(Global {
+            name: name.clone(),
+            value,
+        });
+    }
+    (state, skipped)
+}
+
+fn apply(interpreter: &Interpreter, state: State) {
+    for Global { name, value } in state.globals {
+        let value = match value {
+            Saved::Value(value) => value,
+            Saved::Function { name, params, body } => {
+                let function = LoxFunction {
+                    locals: Rc::new(resolver::resolve_function(&params, &body)),
+                    this: None,
+                    name,
+                    params,
+                    body,
+                    closure: None,
+                };
+                Value::Callable(Callable::Lox(Rc::new(function)))
+            }
+        };
+        interpreter.define(&name, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+    use crate::scanner::scan;
+    fn run(interpreter: &Interpreter, src: &str) -> InterpreterResult<Option<Value>> {
+        let (tokens, _) = scan(String::from(src));
+        let (stmts, _) = parser::parse(tokens);
+        interpreter.interpret_all(&stmts)
+    }
+    #[test]
+    fn state_round_trip() -> InterpreterResult<()> {
+        let interpreter = Interpreter::default();
+        run(
+            &interpreter,
+            "var a = 1; var s = \"hi\"; fun add(x, y) { return x + y; }
+             fun make() { var n = 1; fun get() { return n; } return get; }
+             var g = make();",
+        )?;
+        let (state, skipped) = capture(&interpreter);
+        assert_eq!(skipped, vec![String::from("g")]);
+        let json = serde_json::to_string(&state)?;
+        let restored = Interpreter::default();
+        apply(&restored, serde_json::from_str(&json)?);
+        assert_eq!(restored.lookup("a"), Some(Value::Number(1.0)));
+        assert_eq!(restored.lookup("s"), Some(Value::r#String("hi".into())));
+        assert_eq!(restored.lookup("g"), None);
+        assert_eq!(run(&restored, "add(a, 2);")?, Some(Value::Number(3.0)));
+        Ok(())
+    }
+    #[test]
+    fn state_save_load() -> InterpreterResult<()> {
+        let path = std::env::temp_dir().join(format!("rlox-state-{}.json", std::process::id()));
+        let interpreter = Interpreter::default();
+        run(&interpreter, "var a = true;")?;
+        save(&interpreter, &path)?;
+        let restored = Interpreter::default();
+        load(&restored, &path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(restored.lookup("a"), Some(Value::Bool(true)));
+        Ok(())
+    }
+}
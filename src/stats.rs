@@ -0,0 +1,66 @@
+use std::cell::Cell;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub statements: usize,
+    pub calls: usize,
+    pub lookups: usize,
+    pub allocations: usize,
+    pub allocated_bytes: usize,
+    pub peak_call_depth: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "statements: {}", self.statements)?;
+        writeln!(f, "calls: {}", self.calls)?;
+        writeln!(f, "lookups: {}", self.lookups)?;
+        writeln!(
+            f,
+            "allocations: {} ({} bytes)",
+            self.allocations, self.allocated_bytes
+        )?;
+        write!(f, "peak call depth: {}", self.peak_call_depth)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    stats: Cell<Stats>,
+}
+
+impl Counters {
+    pub(crate) fn get(&self) -> Stats {
+        self.stats.get()
+    }
+    pub(crate) fn reset(&self) {
+        self.stats.set(Stats::default());
+    }
+    pub(crate) fn update(&self, f: impl FnOnce(&mut Stats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::Lox;
+    use crate::InterpreterResult;
+    #[test]
+    fn stats_counts() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.eval_str("fun down(n) { while (n > 0) { n = down(n - 1); } return n; }")?;
+        lox.eval_str("var r = down(3); print len(\"abc\");")?;
+        let stats = lox.stats();
+        assert_eq!(stats.calls, 5);
+        assert_eq!(stats.peak_call_depth, 4);
+        assert_eq!(stats.statements, 17);
+        assert_eq!(stats.lookups, 22);
+        lox.reset_stats();
+        assert_eq!(lox.stats(), Stats::default());
+        Ok(())
+    }
+}
@@ -1,26 +1,186 @@
+use crate::errors::InterpreterResult;
 use crate::expr::Expr;
+use crate::node::NodeId;
+use crate::stmt_printer::StmtPrinter;
 use crate::token::Token;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stmt {
     Block {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
         stmts: Vec<Stmt>,
     },
     Variable {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
         name: Token,
         initializer: Option<Box<Expr>>,
+        doc: Option<String>,
     },
     Print {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
+        keyword: Token,
         expr: Box<Expr>,
     },
     Expr {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
         expr: Box<Expr>,
     },
+    Function {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
+        name: Token,
+        params: Vec<Token>,
+        body: Vec<Stmt>,
+        doc: Option<String>,
+    },
+    Return {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
+        keyword: Token,
+        value: Option<Box<Expr>>,
+    },
+    While {
+        #[cfg_attr(feature = "serde", serde(skip, default = "NodeId::next"))]
+        id: NodeId,
+        condition: Box<Expr>,
+        body: Box<Stmt>,
+    },
+}
+
+pub trait StmtVisitor<T> {
+    fn visit_block(&mut self, stmts: &[Stmt]) -> T;
+    fn visit_variable(&mut self, name: &Token, initializer: Option<&Expr>) -> T;
+    fn visit_print(&mut self, keyword: &Token, expr: &Expr) -> T;
+    fn visit_expr(&mut self, expr: &Expr) -> T;
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &[Stmt]) -> T;
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) -> T;
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> T;
+}
+
+impl Stmt {
+    pub fn accept<T, V>(&self, visitor: &mut V) -> T
+    where
+        V: StmtVisitor<T> + ?Sized,
+    {
+        match self {
+            Self::Block { stmts, .. } => visitor.visit_block(stmts),
+            Self::Variable {
+                name, initializer, ..
+            } => visitor.visit_variable(name, initializer.as_deref()),
+            Self::Print { keyword, expr, .. } => visitor.visit_print(keyword, expr),
+            Self::Expr { expr, .. } => visitor.visit_expr(expr),
+            Self::Function {
+                name, params, body, ..
+            } => visitor.visit_function(name, params, body),
+            Self::Return { keyword, value, .. } => visitor.visit_return(keyword, value.as_deref()),
+            Self::While {
+                condition, body, ..
+            } => visitor.visit_while(condition, body),
+        }
+    }
+    pub fn id(&self) -> NodeId {
+        match self {
+            Self::Block { id, .. }
+            | Self::Variable { id, .. }
+            | Self::Print { id, .. }
+            | Self::Expr { id, .. }
+            | Self::Function { id, .. }
+            | Self::Return { id, .. }
+            | Self::While { id, .. } => *id,
+        }
+    }
+    pub(crate) fn line(&self) -> Option<usize> {
+        match self {
+            Self::Block { stmts, .. } => stmts.first().and_then(Stmt::line),
+            Self::Variable { name, .. } | Self::Function { name, .. } => name.get_line(),
+            Self::Print { keyword, .. } | Self::Return { keyword, .. } => keyword.get_line(),
+            Self::Expr { expr, .. } => expr.line(),
+            Self::While {
+                condition, body, ..
+            } => condition.line().or_else(|| body.line()),
+        }
+    }
+    pub fn print(&self) -> InterpreterResult<String> {
+        StmtPrinter::default().build(self)?.print()
+    }
+}
+
+impl PartialEq for Stmt {
+    // Ids identify a node, they aren't part of its structure.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Block { stmts, .. }, Self::Block { stmts: s, .. }) => stmts == s,
+            (
+                Self::Variable {
+                    name,
+                    initializer,
+                    doc,
+                    ..
+                },
+                Self::Variable {
+                    name: n,
+                    initializer: i,
+                    doc: d,
+                    ..
+                },
+            ) => name == n && initializer == i && doc == d,
+            (
+                Self::Print { keyword, expr, .. },
+                Self::Print {
+                    keyword: k,
+                    expr: e,
+                    ..
+                },
+            ) => keyword == k && expr == e,
+            (Self::Expr { expr, .. }, Self::Expr { expr: e, .. }) => expr == e,
+            (
+                Self::Function {
+                    name,
+                    params,
+                    body,
+                    doc,
+                    ..
+                },
+                Self::Function {
+                    name: n,
+                    params: p,
+                    body: b,
+                    doc: d,
+                    ..
+                },
+            ) => name == n && params == p && body == b && doc == d,
+            (
+                Self::Return { keyword, value, .. },
+                Self::Return {
+                    keyword: k,
+                    value: v,
+                    ..
+                },
+            ) => keyword == k && value == v,
+            (
+                Self::While {
+                    condition, body, ..
+                },
+                Self::While {
+                    condition: c,
+                    body: b,
+                    ..
+                },
+            ) => condition == c && body == b,
+            _ => false,
+        }
+    }
 }
 
 impl From<Expr> for Stmt {
     fn from(value: Expr) -> Stmt {
         Stmt::Expr {
+            id: NodeId::next(),
             expr: Box::new(value),
         }
     }
@@ -29,8 +189,67 @@ impl From<Expr> for Stmt {
 impl From<Stmt> for Expr {
     fn from(value: Stmt) -> Expr {
         match value {
-            Stmt::Expr { expr } => *expr.clone(),
+            Stmt::Expr { expr, .. } => *expr,
             _ => Expr::literal_nil(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast;
+    #[test]
+    fn stmt_print_expr() -> InterpreterResult<()> {
+        let s = Stmt::from(Expr::literal_num(1.0));
+        assert_eq!(s.print()?, String::from("(; 1)"));
+        Ok(())
+    }
+    #[test]
+    fn stmt_print_print() -> InterpreterResult<()> {
+        let s = ast::print(ast::string("hi"));
+        assert_eq!(s.print()?, String::from("(print hi)"));
+        Ok(())
+    }
+    #[test]
+    fn stmt_print_variable() -> InterpreterResult<()> {
+        let s = ast::var("foo", None);
+        assert_eq!(s.print()?, String::from("(var foo)"));
+        let s = ast::var("foo", Some(ast::num(3.0)));
+        assert_eq!(s.print()?, String::from("(var foo 3)"));
+        Ok(())
+    }
+    #[test]
+    fn stmt_print_block() -> InterpreterResult<()> {
+        let s = ast::block(vec![]);
+        assert_eq!(s.print()?, String::from("(block)"));
+        let s = ast::block(vec![
+            ast::var("foo", Some(ast::num(3.0))),
+            ast::print(ast::variable("foo")),
+        ]);
+        assert_eq!(s.print()?, String::from("(block (var foo 3) (print foo))"));
+        Ok(())
+    }
+    #[test]
+    fn stmt_print_function() -> InterpreterResult<()> {
+        let s = ast::function(
+            "add",
+            &["a", "b"],
+            vec![ast::ret(Some(ast::binary(
+                ast::variable("a"),
+                Token::Plus { line: 0 },
+                ast::variable("b"),
+            )))],
+        );
+        assert_eq!(s.print()?, String::from("(fun add (a b) (return (+ a b)))"));
+        let s = ast::function("nothing", &[], vec![ast::ret(None)]);
+        assert_eq!(s.print()?, String::from("(fun nothing () (return))"));
+        Ok(())
+    }
+    #[test]
+    fn stmt_print_while() -> InterpreterResult<()> {
+        let s = ast::while_loop(ast::boolean(true), ast::block(vec![]));
+        assert_eq!(s.print()?, String::from("(while true (block))"));
+        Ok(())
+    }
+}
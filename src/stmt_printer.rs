@@ -0,0 +1,91 @@
+use crate::errors::InterpreterResult;
+use crate::expr::Expr;
+use crate::expr_printer::ExprPrinter;
+use crate::stmt::{Stmt, StmtVisitor};
+use crate::token::Token;
+use std::fmt::Write;
+
+#[derive(Default)]
+pub struct StmtPrinter {
+    s: String,
+}
+
+impl StmtPrinter {
+    pub fn build(mut self, stmt: &Stmt) -> InterpreterResult<Self> {
+        stmt.accept(&mut self)?;
+        Ok(self)
+    }
+    pub fn print(self) -> InterpreterResult<String> {
+        Ok(self.s)
+    }
+    fn stmts(&mut self, stmts: &[Stmt]) -> InterpreterResult<()> {
+        for stmt in stmts.iter() {
+            self.s.write_str(" ")?;
+            stmt.accept(self)?;
+        }
+        Ok(())
+    }
+    fn expr(&mut self, expr: &Expr) -> InterpreterResult<()> {
+        let printed = ExprPrinter::default().build(expr)?.print()?;
+        self.s.write_str(&printed)?;
+        Ok(())
+    }
+    fn optional(&mut self, name: &str, expr: Option<&Expr>) -> InterpreterResult<()> {
+        write!(&mut self.s, "({}", name)?;
+        if let Some(expr) = expr {
+            self.s.write_str(" ")?;
+            self.expr(expr)?;
+        }
+        self.s.write_str(")")?;
+        Ok(())
+    }
+}
+
+impl StmtVisitor<InterpreterResult<()>> for StmtPrinter {
+    fn visit_block(&mut self, stmts: &[Stmt]) -> InterpreterResult<()> {
+        self.s.write_str("(block")?;
+        self.stmts(stmts)?;
+        self.s.write_str(")")?;
+        Ok(())
+    }
+    fn visit_variable(
+        &mut self,
+        name: &Token,
+        initializer: Option<&Expr>,
+    ) -> InterpreterResult<()> {
+        self.optional(&format!("var {}", name), initializer)
+    }
+    fn visit_print(&mut self, _keyword: &Token, expr: &Expr) -> InterpreterResult<()> {
+        self.optional("print", Some(expr))
+    }
+    fn visit_expr(&mut self, expr: &Expr) -> InterpreterResult<()> {
+        self.optional(";", Some(expr))
+    }
+    fn visit_function(
+        &mut self,
+        name: &Token,
+        params: &[Token],
+        body: &[Stmt],
+    ) -> InterpreterResult<()> {
+        let params = params
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(&mut self.s, "(fun {} ({})", name, params)?;
+        self.stmts(body)?;
+        self.s.write_str(")")?;
+        Ok(())
+    }
+    fn visit_return(&mut self, _keyword: &Token, value: Option<&Expr>) -> InterpreterResult<()> {
+        self.optional("return", value)
+    }
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> InterpreterResult<()> {
+        self.s.write_str("(while ")?;
+        self.expr(condition)?;
+        self.s.write_str(" ")?;
+        body.accept(self)?;
+        self.s.write_str(")")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+// A string is a view of the first `len` bytes of a buffer that only ever
+// grows. Concatenating onto the view that ends at the buffer's end appends in
+// place, so `s = s + piece;` in a loop is amortized O(n) instead of copying
+// `s` every time. Any other view copies into a fresh buffer.
+#[derive(Clone)]
+pub struct LoxString {
+    buf: Rc<RefCell<String>>,
+    len: usize,
+}
+
+impl LoxString {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub(crate) fn with_str<R>(&self, f: impl FnOnce(&str) -> R) -> R {
+        f(&self.buf.borrow()[..self.len])
+    }
+    pub(crate) fn concat(&self, other: &str) -> Self {
+        let mut buf = self.buf.borrow_mut();
+        if buf.len() == self.len {
+            buf.push_str(other);
+            Self {
+                buf: Rc::clone(&self.buf),
+                len: buf.len(),
+            }
+        } else {
+            let mut s = String::with_capacity(self.len + other.len());
+            s.push_str(&buf[..self.len]);
+            s.push_str(other);
+            Self::from(s)
+        }
+    }
+    #[cfg(test)]
+    pub(crate) fn shares_buffer(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.buf, &other.buf)
+    }
+}
+
+impl From<String> for LoxString {
+    fn from(s: String) -> Self {
+        Self {
+            len: s.len(),
+            buf: Rc::new(RefCell::new(s)),
+        }
+    }
+}
+
+impl From<&str> for LoxString {
+    fn from(s: &str) -> Self {
+        Self::from(String::from(s))
+    }
+}
+
+impl From<Cow<'_, str>> for LoxString {
+    fn from(s: Cow<'_, str>) -> Self {
+        Self::from(s.into_owned())
+    }
+}
+
+impl PartialEq for LoxString {
+    fn eq(&self, other: &Self) -> bool {
+        self.with_str(|s| other.with_str(|o| s == o))
+    }
+}
+
+impl PartialEq<str> for LoxString {
+    fn eq(&self, other: &str) -> bool {
+        self.with_str(|s| s == other)
+    }
+}
+
+impl fmt::Display for LoxString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.with_str(|s| f.write_str(s))
+    }
+}
+
+impl fmt::Debug for LoxString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.with_str(|s| write!(f, "{:?}", s))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for LoxString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.with_str(|s| serializer.serialize_str(s))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for LoxString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn string_concat_appends_in_place() {
+        let a = LoxString::from("ab");
+        let b = a.concat("cd");
+        let c = b.concat("ef");
+        assert!(a.shares_buffer(&c));
+        assert_eq!(a.to_string(), "ab");
+        assert_eq!(b.to_string(), "abcd");
+        assert_eq!(c.to_string(), "abcdef");
+        let d = b.concat("xy");
+        assert!(!d.shares_buffer(&b));
+        assert_eq!(d.to_string(), "abcdxy");
+        assert_eq!(c.to_string(), "abcdef");
+        assert_eq!(a.concat(&a.to_string()), LoxString::from("abab"));
+        assert_eq!(c.len(), 6);
+        assert!(LoxString::from("").is_empty());
+    }
+}
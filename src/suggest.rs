@@ -0,0 +1,57 @@
+// Picks the candidate closest to a misspelled name, if any is close enough to
+// be worth suggesting. Shared by keyword and variable hints.
+pub(crate) fn closest<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|&c| c != name)
+        .map(|c| (distance(name, c), c))
+        .filter(|&(d, _)| d <= limit)
+        .min()
+        .map(|(_, c)| c)
+}
+
+// Optimal string alignment distance: Levenshtein plus adjacent swaps, so
+// `pritn` is one edit from `print`.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut d = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = d;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn suggest_closest() {
+        let keywords = ["print", "while", "var", "return"];
+        assert_eq!(closest("pritn", keywords), Some("print"));
+        assert_eq!(closest("whle", keywords), Some("while"));
+        assert_eq!(closest("retrun", keywords), Some("return"));
+        assert_eq!(closest("vr", keywords), Some("var"));
+        assert_eq!(closest("count", keywords), None);
+        assert_eq!(closest("print", keywords), None);
+    }
+}
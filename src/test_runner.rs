@@ -0,0 +1,134 @@
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::interpreter::Interpreter;
+use crate::parser;
+use crate::prelude::Prelude;
+use crate::scanner::scan;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const EXPECT: &str = "// expect: ";
+const ERROR: &str = "// error: ";
+
+pub(crate) fn run_tests(path: &str) -> InterpreterResult<()> {
+    let mut files = Vec::default();
+    collect(Path::new(path), &mut files)?;
+    let mut failed = 0;
+    for file in files.iter() {
+        match run_test(&fs::read_to_string(file)?) {
+            Ok(()) => println!("PASS {}", file.display()),
+            Err(reason) => {
+                failed += 1;
+                println!("FAIL {}: {}", file.display(), reason);
+            }
+        }
+    }
+    println!("{} passed, {} failed", files.len() - failed, failed);
+    if failed > 0 {
+        Err(InterpreterError::Exit { code: 1 })
+    } else {
+        Ok(())
+    }
+}
+
+fn collect(path: &Path, files: &mut Vec<PathBuf>) -> InterpreterResult<()> {
+    if path.is_dir() {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries.iter() {
+            collect(entry, files)?;
+        }
+    } else if path.extension().is_some_and(|ext| ext == "lox") {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+fn run_test(source: &str) -> Result<(), String> {
+    let expected: Vec<&str> = annotations(source, EXPECT).collect();
+    let errors: Vec<&str> = annotations(source, ERROR).collect();
+    let mut interpreter = Interpreter::default();
+    Prelude::standard().load(&interpreter);
+    interpreter.set_input(Box::new(io::empty()));
+    interpreter.capture_output(true);
+    let res = execute(&interpreter, source);
+    let output = interpreter.take_output();
+    let actual: Vec<&str> = output.lines().collect();
+    for (i, expected) in expected.iter().enumerate() {
+        match actual.get(i) {
+            Some(actual) if actual == expected => {}
+            Some(actual) => return Err(format!("expected {:?}, got {:?}", expected, actual)),
+            None => return Err(format!("expected {:?}, got no output", expected)),
+        }
+    }
+    if let Some(extra) = actual.get(expected.len()) {
+        return Err(format!("unexpected output {:?}", extra));
+    }
+    match res {
+        Ok(()) if errors.is_empty() => Ok(()),
+        Ok(()) => Err(format!("expected error {:?}", errors[0])),
+        Err(err) if errors.iter().any(|e| err.to_string().contains(e)) => Ok(()),
+        Err(err) => Err(format!("unexpected error: {}", err)),
+    }
+}
+
+fn execute(interpreter: &Interpreter, source: &str) -> InterpreterResult<()> {
+    let (tokens, errs) = scan(String::from(source));
+    if let Some(err) = errs.into_iter().next() {
+        return Err(err);
+    }
+    let (stmts, errs) = parser::parse(tokens);
+    if let Some(err) = errs.into_iter().next() {
+        return Err(err);
+    }
+    interpreter.interpret_all(&stmts).map(|_| ())
+}
+
+fn annotations<'a>(source: &'a str, prefix: &'a str) -> impl Iterator<Item = &'a str> {
+    source.lines().filter_map(move |line| {
+        line.find(prefix)
+            .map(|i| line[i + prefix.len()..].trim_end())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_runner_expectations() {
+        assert_eq!(
+            run_test("print 1; // expect: 1\nprint \"a\"; // expect: a\n"),
+            Ok(())
+        );
+        assert_eq!(
+            run_test("print 2; // expect: 1\n"),
+            Err(String::from("expected \"1\", got \"2\""))
+        );
+        assert_eq!(
+            run_test("print 1;\n"),
+            Err(String::from("unexpected output \"1\""))
+        );
+        assert_eq!(
+            run_test("// expect: 1\n"),
+            Err(String::from("expected \"1\", got no output"))
+        );
+        assert_eq!(run_test("print max(1, 2); // expect: 2\n"), Ok(()));
+    }
+    #[test]
+    fn test_runner_errors() {
+        assert_eq!(
+            run_test("print 1; // expect: 1\nprint nope; // error: Undefined variable nope\n"),
+            Ok(())
+        );
+        assert!(run_test("print nope;\n")
+            .unwrap_err()
+            .starts_with("unexpected error"));
+        assert_eq!(
+            run_test("print 1; // expect: 1\n// error: boom\n"),
+            Err(String::from("expected error \"boom\""))
+        );
+        assert_eq!(run_test("var = 1; // error: Expected\n"), Ok(()));
+    }
+}
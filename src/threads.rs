@@ -0,0 +1,328 @@
+use crate::capabilities::Capability;
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::function::Callable;
+use crate::interpreter::Interpreter;
+use crate::lox::Lox;
+use crate::node::NodeId;
+use crate::prelude::Prelude;
+use crate::stmt::Stmt;
+use crate::to_source::ToSource;
+use crate::value::Value;
+use crate::worker::Sendable;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread::{self, JoinHandle};
+
+// Threads and channels are shared by every interpreter in the process so that
+// their handles, which are plain numbers, can be passed between threads. A
+// thread's entry is dropped when it is joined and a channel's when it is
+// closed.
+struct Channel {
+    // Taken on close, so that receivers see the channel disconnect.
+    tx: Mutex<Option<Sender<Sendable>>>,
+    rx: Mutex<Receiver<Sendable>>,
+}
+
+type Thread = JoinHandle<InterpreterResult<Sendable>>;
+
+static CHANNELS: OnceLock<Mutex<HashMap<usize, Arc<Channel>>>> = OnceLock::new();
+static THREADS: OnceLock<Mutex<HashMap<usize, Thread>>> = OnceLock::new();
+static NEXT_CHANNEL: AtomicUsize = AtomicUsize::new(0);
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+fn channels() -> MutexGuard<'static, HashMap<usize, Arc<Channel>>> {
+    let channels = CHANNELS.get_or_init(Mutex::default);
+    channels.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn threads() -> MutexGuard<'static, HashMap<usize, Thread>> {
+    let threads = THREADS.get_or_init(Mutex::default);
+    threads.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// The function runs in a fresh interpreter from its own source, so it sees its
+// arguments, the natives and the spawning interpreter's prelude but none of
+// the spawning script's variables.
+pub(crate) fn spawn(
+    interpreter: &Interpreter,
+    mut args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    interpreter.require(Capability::Threads, line)?;
+    let function = match args.remove(0) {
+        Value::Callable(Callable::Lox(function)) => function,
+        value => {
+            return Err(InterpreterError::type_error(
+                String::from("function"),
+                String::from(value.type_name()),
+            )
+            .add_line_to_type_error(line))
+        }
+    };
+    if args.len() != function.arity() {
+        return Err(InterpreterError::RuntimeError {
+            line,
+            message: format!(
+                "Expected {} arguments but got {}",
+                function.arity(),
+                args.len()
+            ),
+        });
+    }
+    let args = args
+        .into_iter()
+        .map(Sendable::try_from)
+        .collect::<InterpreterResult<Vec<Sendable>>>()
+        .map_err(|e| e.add_line_to_type_error(line))?;
+    let name = function.name();
+    let source = Stmt::Function {
+        id: NodeId::next(),
+        name: function.name.clone(),
+        params: function.params.clone(),
+        body: function.body.clone(),
+        doc: None,
+    }
+    .to_source();
+    let capabilities = interpreter.capabilities();
+    let limits = *interpreter.limits();
+    let prelude = interpreter.prelude();
+    let handle = thread::Builder::new()
+        .name(format!("lox {}", name))
+        .stack_size(8 << 20)
+        .spawn(move || {
+            let mut lox = match prelude {
+                Some(source) => Lox::from_prelude(&Prelude::from_source(&source)?),
+                None => Lox::new(),
+            };
+            lox.set_capabilities(capabilities);
+            lox.set_limits(limits);
+            lox.eval_str(&source)?;
+            let function =
+                lox.get_function(&name)
+                    .ok_or_else(|| InterpreterError::RuntimeError {
+                        line,
+                        message: format!("Couldn't define {} on the new thread", name),
+                    })?;
+            let args = args.into_iter().map(Value::from).collect();
+            function.call(&mut lox, args).and_then(Sendable::try_from)
+        })?;
+    let id = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+    threads().insert(id, handle);
+    Ok(Value::Number(id as f32))
+}
+
+pub(crate) fn join(
+    _interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    let id = handle_arg(&args, 0, line)?;
+    let handle = threads()
+        .remove(&id)
+        .ok_or_else(|| unknown("thread", &args[0], line))?;
+    match handle.join() {
+        Ok(result) => result.map(Value::from),
+        Err(_) => Err(InterpreterError::RuntimeError {
+            line,
+            message: format!("Thread {} panicked", id),
+        }),
+    }
+}
+
+pub(crate) fn chan_new(
+    _interpreter: &Interpreter,
+    _args: Vec<Value>,
+    _line: usize,
+) -> InterpreterResult<Value> {
+    let (tx, rx) = mpsc::channel();
+    let id = NEXT_CHANNEL.fetch_add(1, Ordering::Relaxed);
+    channels().insert(
+        id,
+        Arc::new(Channel {
+            tx: Mutex::new(Some(tx)),
+            rx: Mutex::new(rx),
+        }),
+    );
+    Ok(Value::Number(id as f32))
+}
+
+// Values not yet received are dropped, and receivers waiting on the channel
+// wake with an error.
+pub(crate) fn chan_close(
+    _interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    let id = handle_arg(&args, 0, line)?;
+    let channel = channels()
+        .remove(&id)
+        .ok_or_else(|| unknown("channel", &args[0], line))?;
+    channel.tx.lock().unwrap_or_else(|e| e.into_inner()).take();
+    Ok(Value::Nil)
+}
+
+pub(crate) fn send(
+    _interpreter: &Interpreter,
+    mut args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    let channel = channel_arg(&args, 0, line)?;
+    let value = Sendable::try_from(args.remove(1)).map_err(|e| e.add_line_to_type_error(line))?;
+    let tx = channel.tx.lock().unwrap_or_else(|e| e.into_inner());
+    // The channel holds its own receiver, so sending only fails once closed.
+    match tx.as_ref().map(|tx| tx.send(value)) {
+        Some(Ok(())) => Ok(Value::Nil),
+        _ => Err(closed(line)),
+    }
+}
+
+pub(crate) fn recv(
+    _interpreter: &Interpreter,
+    args: Vec<Value>,
+    line: usize,
+) -> InterpreterResult<Value> {
+    let channel = channel_arg(&args, 0, line)?;
+    let rx = channel.rx.lock().unwrap_or_else(|e| e.into_inner());
+    rx.recv().map(Value::from).map_err(|_| closed(line))
+}
+
+fn channel_arg(args: &[Value], i: usize, line: usize) -> InterpreterResult<Arc<Channel>> {
+    let id = handle_arg(args, i, line)?;
+    channels()
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| unknown("channel", &args[i], line))
+}
+
+fn handle_arg(args: &[Value], i: usize, line: usize) -> InterpreterResult<usize> {
+    match args[i] {
+        Value::Number(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+        Value::Number(_) => Err(unknown("handle", &args[i], line)),
+        ref value => Err(InterpreterError::type_error(
+            String::from("number"),
+            String::from(value.type_name()),
+        )
+        .add_line_to_type_error(line)),
+    }
+}
+
+fn closed(line: usize) -> InterpreterError {
+    InterpreterError::RuntimeError {
+        line,
+        message: String::from("Channel closed"),
+    }
+}
+
+fn unknown(kind: &str, handle: &Value, line: usize) -> InterpreterError {
+    InterpreterError::RuntimeError {
+        line,
+        message: format!("Unknown {} {}", kind, handle),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn threads_spawn_and_channels() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.eval_str(
+            "fun square(ch, n) { send(ch, n * n); return \"done \" + str(n); }
+             var ch = chanNew();
+             var t1 = spawn(square, ch, 3);
+             var t2 = spawn(square, ch, 4);",
+        )?;
+        assert_eq!(lox.eval_str("join(t1);")?, Value::r#String("done 3".into()));
+        assert_eq!(lox.eval_str("join(t2);")?, Value::r#String("done 4".into()));
+        assert_eq!(lox.eval_str("recv(ch) + recv(ch);")?, Value::Number(25.0));
+        assert!(matches!(
+            lox.eval_str("join(t1);"),
+            Err(InterpreterError::RuntimeError { .. })
+        ));
+        lox.eval_str("var hidden = 1; fun peek() { return hidden; }")?;
+        assert!(matches!(
+            lox.eval_str("join(spawn(peek));"),
+            Err(InterpreterError::UndefinedVariable { .. })
+        ));
+        assert!(matches!(
+            lox.eval_str("send(ch, peek);"),
+            Err(InterpreterError::Type { line: Some(1), .. })
+        ));
+        assert!(matches!(
+            lox.eval_str("spawn(square, ch);"),
+            Err(InterpreterError::RuntimeError { line: 1, .. })
+        ));
+        assert!(matches!(
+            lox.eval_str("recv(-1);"),
+            Err(InterpreterError::RuntimeError { line: 1, .. })
+        ));
+        Ok(())
+    }
+    #[test]
+    fn threads_close_wakes_receivers() -> InterpreterResult<()> {
+        let mut lox = Lox::new();
+        lox.eval_str(
+            "fun wait(ready, ch) { send(ready, true); return recv(ch); }
+             var ready = chanNew();
+             var ch = chanNew();
+             var t = spawn(wait, ready, ch);
+             recv(ready);",
+        )?;
+        let id = match lox.get_global("ch") {
+            Some(Value::Number(id)) => id as usize,
+            other => panic!("{:?}", other),
+        };
+        assert!(channels().contains_key(&id));
+        lox.eval_str("chanClose(ch);")?;
+        assert!(!channels().contains_key(&id));
+        // The thread is usually waiting by now, but may not have looked the
+        // channel up yet; either way it doesn't wait forever.
+        match lox.eval_str("join(t);") {
+            Err(InterpreterError::RuntimeError { message, .. }) => assert!(
+                message == "Channel closed" || message.starts_with("Unknown channel"),
+                "{}",
+                message
+            ),
+            other => panic!("{:?}", other),
+        }
+        for source in ["send(ch, 1);", "recv(ch);", "chanClose(ch);"] {
+            assert!(matches!(
+                lox.eval_str(source),
+                Err(InterpreterError::RuntimeError { line: 1, .. })
+            ));
+        }
+        Ok(())
+    }
+    #[test]
+    fn threads_prelude_and_capability() -> InterpreterResult<()> {
+        let mut lox = Lox::from_prelude(&Prelude::standard());
+        lox.eval_str("fun biggest(a, b) { return max(a, b); }")?;
+        assert_eq!(
+            lox.eval_str("join(spawn(biggest, 2, 5));")?,
+            Value::Number(5.0)
+        );
+        let mut lox = Lox::from_prelude(&Prelude::new("fun twice(n) { return n * 2; }")?);
+        lox.eval_str("fun f(n) { return twice(n); }")?;
+        assert_eq!(lox.eval_str("join(spawn(f, 4));")?, Value::Number(8.0));
+        let mut lox = Lox::new();
+        lox.eval_str("fun f(a, b) { return max(a, b); }")?;
+        assert!(matches!(
+            lox.eval_str("join(spawn(f, 1, 2));"),
+            Err(InterpreterError::UndefinedVariable { .. })
+        ));
+        lox.set_capabilities(crate::capabilities::Capabilities {
+            allow_threads: false,
+            ..crate::capabilities::Capabilities::all()
+        });
+        assert!(matches!(
+            lox.eval_str("spawn(f, 1, 2);"),
+            Err(InterpreterError::CapabilityDenied {
+                capability: "thread",
+                line: 1
+            })
+        ));
+        Ok(())
+    }
+}
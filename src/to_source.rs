@@ -0,0 +1,365 @@
+use crate::expr::{Expr, ExprVisitor};
+use crate::node::NodeId;
+use crate::parser::{infix_precedence, Precedence};
+use crate::stmt::{Stmt, StmtVisitor};
+use crate::token::Token;
+use crate::value::Value;
+
+const INDENT: &str = "    ";
+
+pub trait ToSource {
+    fn to_source(&self) -> String;
+}
+
+impl ToSource for Expr {
+    fn to_source(&self) -> String {
+        let mut printer = SourcePrinter::default();
+        self.accept(&mut printer);
+        printer.out
+    }
+}
+
+impl ToSource for Stmt {
+    fn to_source(&self) -> String {
+        let mut printer = SourcePrinter::default();
+        printer.stmt(self);
+        printer.out
+    }
+}
+
+impl ToSource for [Stmt] {
+    fn to_source(&self) -> String {
+        let mut printer = SourcePrinter::default();
+        for stmt in self.iter() {
+            printer.stmt(stmt);
+            printer.out.push('\n');
+        }
+        printer.out
+    }
+}
+
+fn precedence(expr: &Expr) -> Precedence {
+    match expr {
+        Expr::Assign { .. } => Precedence::Assignment,
+        Expr::Binary { operator, .. } | Expr::Logical { operator, .. } => {
+            infix_precedence(operator)
+        }
+        Expr::Unary { .. } => Precedence::Unary,
+        Expr::Call { .. }
+        | Expr::Index { .. }
+        | Expr::Grouping { .. }
+        | Expr::Literal { .. }
+        | Expr::Variable { .. } => Precedence::Call,
+    }
+}
+
+#[derive(Default)]
+struct SourcePrinter {
+    out: String,
+    indent: usize,
+}
+
+impl SourcePrinter {
+    fn stmt(&mut self, stmt: &Stmt) {
+        if let Stmt::Variable { doc: Some(doc), .. } | Stmt::Function { doc: Some(doc), .. } = stmt
+        {
+            for line in doc.split('\n') {
+                self.out.push_str("///");
+                if !line.is_empty() {
+                    self.out.push(' ');
+                    self.out.push_str(line);
+                }
+                self.newline();
+            }
+        }
+        stmt.accept(self)
+    }
+    fn operand(&mut self, expr: &Expr, min: Precedence) {
+        if precedence(expr) < min {
+            self.out.push('(');
+            expr.accept(self);
+            self.out.push(')');
+        } else {
+            expr.accept(self)
+        }
+    }
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+}
+
+impl StmtVisitor<()> for SourcePrinter {
+    fn visit_block(&mut self, stmts: &[Stmt]) {
+        self.out.push('{');
+        if stmts.is_empty() {
+            self.out.push('}');
+            return;
+        }
+        self.indent += 1;
+        for stmt in stmts.iter() {
+            self.newline();
+            self.stmt(stmt);
+        }
+        self.indent -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+    fn visit_variable(&mut self, name: &Token, initializer: Option<&Expr>) {
+        self.out.push_str(&format!("var {}", name));
+        if let Some(initializer) = initializer {
+            self.out.push_str(" = ");
+            initializer.accept(self);
+        }
+        self.out.push(';');
+    }
+    fn visit_print(&mut self, _keyword: &Token, expr: &Expr) {
+        self.out.push_str("print ");
+        expr.accept(self);
+        self.out.push(';');
+    }
+    fn visit_expr(&mut self, expr: &Expr) {
+        expr.accept(self);
+        self.out.push(';');
+    }
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &[Stmt]) {
+        let params = params
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.out.push_str(&format!("fun {}({}) ", name, params));
+        self.visit_block(body)
+    }
+    fn visit_return(&mut self, _keyword: &Token, value: Option<&Expr>) {
+        self.out.push_str("return");
+        if let Some(value) = value {
+            self.out.push(' ');
+            value.accept(self);
+        }
+        self.out.push(';');
+    }
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt) {
+        self.out.push_str("while (");
+        condition.accept(self);
+        self.out.push_str(") ");
+        body.accept(self)
+    }
+}
+
+impl ExprVisitor<()> for SourcePrinter {
+    fn visit_assign(&mut self, _id: NodeId, name: &Token, value: &Expr) {
+        self.out.push_str(&format!("{} = ", name));
+        value.accept(self)
+    }
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) {
+        let precedence = infix_precedence(operator);
+        self.operand(left, precedence);
+        self.out.push_str(&format!(" {} ", operator));
+        self.operand(right, precedence.next());
+    }
+    fn visit_call(&mut self, callee: &Expr, _paren: &Token, arguments: &[Expr]) {
+        self.operand(callee, Precedence::Call);
+        self.out.push('(');
+        for (i, argument) in arguments.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            argument.accept(self);
+        }
+        self.out.push(')');
+    }
+    fn visit_grouping(&mut self, expression: &Expr) {
+        self.out.push('(');
+        expression.accept(self);
+        self.out.push(')');
+    }
+    fn visit_index(&mut self, object: &Expr, _bracket: &Token, index: &Expr) {
+        self.operand(object, Precedence::Call);
+        self.out.push('[');
+        index.accept(self);
+        self.out.push(']');
+    }
+    fn visit_literal(&mut self, value: &Value) {
+        match value {
+            Value::r#String(s) => self.out.push_str(&format!("\"{}\"", s)),
+            Value::Number(n) => self.out.push_str(&n.to_string()),
+            Value::BigInt(n) => self.out.push_str(&format!("{}n", n)),
+            value => self.out.push_str(&value.to_string()),
+        }
+    }
+    fn visit_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) {
+        self.visit_binary(left, operator, right)
+    }
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) {
+        self.out.push_str(&operator.to_string());
+        self.operand(right, Precedence::Unary);
+    }
+    fn visit_variable(&mut self, _id: NodeId, name: &Token) {
+        self.out.push_str(&name.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast;
+    use crate::parser;
+    use crate::scanner::scan_with_trivia;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        parser::parse(scan_with_trivia(String::from(source)).0).0
+    }
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len() as u64) as usize]
+        }
+        fn name(&mut self) -> &'static str {
+            self.pick(&["a", "b", "count", "add"])
+        }
+        fn operator(&mut self) -> Token {
+            match self.below(10) {
+                0 => Token::Plus { line: 0 },
+                1 => Token::Minus { line: 0 },
+                2 => Token::Star { line: 0 },
+                3 => Token::Slash { line: 0 },
+                4 => Token::Less { line: 0 },
+                5 => Token::LessEqual { line: 0 },
+                6 => Token::Greater { line: 0 },
+                7 => Token::GreaterEqual { line: 0 },
+                8 => Token::EqualEqual { line: 0 },
+                _ => Token::BangEqual { line: 0 },
+            }
+        }
+        fn operand(&mut self, depth: usize, min: Precedence) -> Expr {
+            let expr = self.expr(depth);
+            if precedence(&expr) < min {
+                ast::grouping(expr)
+            } else {
+                expr
+            }
+        }
+        fn expr(&mut self, depth: usize) -> Expr {
+            let choice = if depth == 0 {
+                self.below(4)
+            } else {
+                self.below(9)
+            };
+            match choice {
+                0 => ast::num(self.below(1000) as f32 / 8.0),
+                1 => ast::string(self.pick(&["", "hi", "two words"])),
+                2 => match self.below(3) {
+                    0 => ast::boolean(true),
+                    1 => ast::boolean(false),
+                    _ => ast::nil(),
+                },
+                3 => ast::variable(self.name()),
+                4 => ast::assign(self.name(), self.expr(depth - 1)),
+                5 => {
+                    let operator = self.operator();
+                    let precedence = infix_precedence(&operator);
+                    let left = self.operand(depth - 1, precedence);
+                    let right = self.operand(depth - 1, precedence.next());
+                    ast::binary(left, operator, right)
+                }
+                6 => {
+                    let operator = if self.below(2) == 0 {
+                        Token::Minus { line: 0 }
+                    } else {
+                        Token::Bang { line: 0 }
+                    };
+                    ast::unary(operator, self.operand(depth - 1, Precedence::Unary))
+                }
+                7 => {
+                    let callee = self.operand(depth - 1, Precedence::Call);
+                    let arguments = (0..self.below(3)).map(|_| self.expr(depth - 1)).collect();
+                    ast::call(callee, arguments)
+                }
+                _ => ast::grouping(self.expr(depth - 1)),
+            }
+        }
+        fn stmts(&mut self, depth: usize, in_function: bool) -> Vec<Stmt> {
+            (0..self.below(4))
+                .map(|_| self.stmt(depth, in_function))
+                .collect()
+        }
+        fn stmt(&mut self, depth: usize, in_function: bool) -> Stmt {
+            let choice = if depth == 0 {
+                self.below(3)
+            } else {
+                self.below(7)
+            };
+            match choice {
+                0 => ast::print(self.expr(2)),
+                1 => ast::expression(self.expr(2)),
+                2 => {
+                    let initializer = (self.below(2) == 0).then(|| ast::num(1.0));
+                    let mut stmt = ast::var(self.name(), initializer);
+                    if let Stmt::Variable { doc, .. } = &mut stmt {
+                        *doc = (self.below(3) == 0).then(|| String::from("Docs.\n\nMore."));
+                    }
+                    stmt
+                }
+                3 => ast::block(self.stmts(depth - 1, in_function)),
+                4 => {
+                    let body = match self.stmt(depth - 1, in_function) {
+                        Stmt::Variable { .. } | Stmt::Function { .. } => ast::block(vec![]),
+                        body => body,
+                    };
+                    ast::while_loop(self.expr(2), body)
+                }
+                5 => {
+                    let params: Vec<&str> = (0..self.below(3)).map(|_| self.name()).collect();
+                    ast::function(self.name(), &params, self.stmts(depth - 1, true))
+                }
+                _ if in_function => ast::ret((self.below(2) == 0).then(|| self.expr(2))),
+                _ => ast::print(self.expr(1)),
+            }
+        }
+    }
+
+    #[test]
+    fn to_source_statements() {
+        let source = "/// Adds.\nfun add(a, b) {\n    return a + b;\n}\nvar n = -(1 + 2) * 3;\nwhile (n < 10) {\n    n = add(n, 1);\n    {}\n}\nprint !true == false;\nwhile (false) print \"never\";\nprint 123n;\nprint b\"\\x00a\"[0];\nprint (a ?? b) + (c ?? nil ?? 1);\n";
+        let stmts = parse(source);
+        assert_eq!(stmts.to_source(), source);
+        assert_eq!(stmts[1].to_source(), "var n = -(1 + 2) * 3;");
+    }
+    #[test]
+    fn to_source_adds_needed_parens() {
+        let e = ast::binary(
+            ast::binary(ast::num(1.0), Token::Plus { line: 0 }, ast::num(2.0)),
+            Token::Star { line: 0 },
+            ast::binary(ast::num(3.0), Token::Minus { line: 0 }, ast::num(4.0)),
+        );
+        assert_eq!(e.to_source(), "(1 + 2) * (3 - 4)");
+        let e = ast::binary(
+            ast::num(1.0),
+            Token::Minus { line: 0 },
+            ast::binary(ast::num(2.0), Token::Minus { line: 0 }, ast::num(3.0)),
+        );
+        assert_eq!(e.to_source(), "1 - (2 - 3)");
+    }
+    #[test]
+    fn to_source_round_trips() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let generated = rng.stmts(3, false);
+            let source = generated.to_source();
+            let parsed = parse(&source);
+            assert_eq!(parsed.to_source(), source);
+            assert_eq!(parse(&parsed.to_source()), parsed, "{}", source);
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use num_bigint::BigInt;
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Token {
     // single-character tokens
     LeftParen {
@@ -13,6 +16,12 @@ pub enum Token {
     RightBrace {
         line: usize,
     },
+    LeftBracket {
+        line: usize,
+    },
+    RightBracket {
+        line: usize,
+    },
     Comma {
         line: usize,
     },
@@ -34,6 +43,10 @@ pub enum Token {
     Star {
         line: usize,
     },
+    // two character tokens
+    QuestionQuestion {
+        line: usize,
+    },
     // one or two character tokens
     Bang {
         line: usize,
@@ -75,6 +88,16 @@ pub enum Token {
         literal: f32,
         line: usize,
     },
+    BigInt {
+        lexeme: String,
+        literal: BigInt,
+        line: usize,
+    },
+    Bytes {
+        lexeme: String,
+        literal: Vec<u8>,
+        line: usize,
+    },
     // keywords
     And {
         line: usize,
@@ -127,19 +150,99 @@ pub enum Token {
     Eof {
         line: usize,
     },
-    Comment,
-    Whitespace,
+    Comment {
+        text: String,
+        line: usize,
+    },
+    Whitespace {
+        text: String,
+        line: usize,
+    },
 }
 
 impl Token {
+    pub(crate) fn kind(&self) -> &'static str {
+        use Token::*;
+        match self {
+            LeftParen { .. } => "LEFT_PAREN",
+            RightParen { .. } => "RIGHT_PAREN",
+            LeftBrace { .. } => "LEFT_BRACE",
+            RightBrace { .. } => "RIGHT_BRACE",
+            LeftBracket { .. } => "LEFT_BRACKET",
+            RightBracket { .. } => "RIGHT_BRACKET",
+            Comma { .. } => "COMMA",
+            Dot { .. } => "DOT",
+            Minus { .. } => "MINUS",
+            Plus { .. } => "PLUS",
+            Semicolon { .. } => "SEMICOLON",
+            Slash { .. } => "SLASH",
+            Star { .. } => "STAR",
+            QuestionQuestion { .. } => "QUESTION_QUESTION",
+            Bang { .. } => "BANG",
+            BangEqual { .. } => "BANG_EQUAL",
+            Equal { .. } => "EQUAL",
+            EqualEqual { .. } => "EQUAL_EQUAL",
+            Greater { .. } => "GREATER",
+            GreaterEqual { .. } => "GREATER_EQUAL",
+            Less { .. } => "LESS",
+            LessEqual { .. } => "LESS_EQUAL",
+            Identifier { .. } => "IDENTIFIER",
+            r#String { .. } => "STRING",
+            Number { .. } => "NUMBER",
+            BigInt { .. } => "BIGINT",
+            Bytes { .. } => "BYTES",
+            And { .. } => "AND",
+            Class { .. } => "CLASS",
+            Else { .. } => "ELSE",
+            False { .. } => "FALSE",
+            Fun { .. } => "FUN",
+            For { .. } => "FOR",
+            If { .. } => "IF",
+            Nil { .. } => "NIL",
+            Or { .. } => "OR",
+            Print { .. } => "PRINT",
+            Return { .. } => "RETURN",
+            Super { .. } => "SUPER",
+            This { .. } => "THIS",
+            True { .. } => "TRUE",
+            Var { .. } => "VAR",
+            While { .. } => "WHILE",
+            Eof { .. } => "EOF",
+            Comment { .. } => "COMMENT",
+            Whitespace { .. } => "WHITESPACE",
+        }
+    }
+    pub(crate) fn lexeme(&self) -> String {
+        match self {
+            Token::Identifier { lexeme, .. }
+            | Token::r#String { lexeme, .. }
+            | Token::Number { lexeme, .. }
+            | Token::BigInt { lexeme, .. }
+            | Token::Bytes { lexeme, .. } => lexeme.clone(),
+            Token::Comment { text, .. } | Token::Whitespace { text, .. } => text.clone(),
+            t => t.to_string(),
+        }
+    }
+    pub(crate) fn is_trivia(&self) -> bool {
+        matches!(self, Token::Comment { .. } | Token::Whitespace { .. })
+    }
+    pub(crate) fn dump(&self) -> String {
+        let line = self.get_line().map_or(String::default(), |l| l.to_string());
+        format!("{} {} {}", line, self.kind(), self.lexeme())
+            .trim()
+            .to_string()
+    }
     pub(crate) fn get_line(&self) -> Option<usize> {
         use Token::*;
         match self {
-            Comment | Whitespace => None,
+            Whitespace { line, .. } => Some(*line),
+            Comment { line, .. } => Some(*line),
             LeftParen { line } => Some(*line),
             RightParen { line } => Some(*line),
             LeftBrace { line } => Some(*line),
             RightBrace { line } => Some(*line),
+            LeftBracket { line } => Some(*line),
+            RightBracket { line } => Some(*line),
             Comma { line } => Some(*line),
             Dot { line } => Some(*line),
             Minus { line } => Some(*line),
@@ -147,6 +250,7 @@ impl Token {
             Semicolon { line } => Some(*line),
             Slash { line } => Some(*line),
             Star { line } => Some(*line),
+            QuestionQuestion { line } => Some(*line),
             Bang { line } => Some(*line),
             BangEqual { line } => Some(*line),
             Equal { line } => Some(*line),
@@ -158,6 +262,8 @@ impl Token {
             Identifier { line, .. } => Some(*line),
             r#String { line, .. } => Some(*line),
             Number { line, .. } => Some(*line),
+            BigInt { line, .. } => Some(*line),
+            Bytes { line, .. } => Some(*line),
             And { line } => Some(*line),
             Class { line } => Some(*line),
             Else { line } => Some(*line),
@@ -187,6 +293,8 @@ impl std::fmt::Display for Token {
             RightParen { .. } => f.write_str(")"),
             LeftBrace { .. } => f.write_str("{"),
             RightBrace { .. } => f.write_str("}"),
+            LeftBracket { .. } => f.write_str("["),
+            RightBracket { .. } => f.write_str("]"),
             Comma { .. } => f.write_str(","),
             Dot { .. } => f.write_str("."),
             Minus { .. } => f.write_str("-"),
@@ -194,6 +302,7 @@ impl std::fmt::Display for Token {
             Semicolon { .. } => f.write_str(";"),
             Slash { .. } => f.write_str("/"),
             Star { .. } => f.write_str("*"),
+            QuestionQuestion { .. } => f.write_str("??"),
             Bang { .. } => f.write_str("!"),
             BangEqual { .. } => f.write_str("!="),
             Equal { .. } => f.write_str("="),
@@ -206,6 +315,8 @@ impl std::fmt::Display for Token {
                 write!(f, "{}", literal)
             }
             Number { literal, .. } => write!(f, "{}", literal),
+            BigInt { ref literal, .. } => write!(f, "{}n", literal),
+            Bytes { ref lexeme, .. } => f.write_str(lexeme),
             And { .. } => f.write_str("and"),
             Class { .. } => f.write_str("class"),
             Else { .. } => f.write_str("else"),
@@ -226,3 +337,99 @@ impl std::fmt::Display for Token {
         }
     }
 }
+
+pub const KEYWORDS: [&str; 16] = [
+    "and", "class", "else", "false", "for", "fun", "if", "nil", "or", "print", "return", "super",
+    "this", "true", "var", "while",
+];
+
+// A perfect hash over the keywords: length plus first and last byte picks a
+// distinct slot for each, so an identifier costs one comparison at most.
+// Building the table panics at compile time if a new keyword collides.
+const KEYWORD_SLOTS: [u8; 32] = keyword_slots();
+
+const fn keyword_hash(s: &[u8]) -> usize {
+    (s.len() + s[0] as usize + 5 * s[s.len() - 1] as usize) % 32
+}
+
+const fn keyword_slots() -> [u8; 32] {
+    let mut slots = [0; 32];
+    let mut i = 0;
+    while i < KEYWORDS.len() {
+        let slot = keyword_hash(KEYWORDS[i].as_bytes());
+        assert!(slots[slot] == 0, "keyword hash collision");
+        slots[slot] = i as u8 + 1;
+        i += 1;
+    }
+    slots
+}
+
+pub(crate) fn keyword(s: &str, line: usize) -> Option<Token> {
+    if s.is_empty() {
+        return None;
+    }
+    let i = usize::from(KEYWORD_SLOTS[keyword_hash(s.as_bytes())]).checked_sub(1)?;
+    if KEYWORDS[i] != s {
+        return None;
+    }
+    // In KEYWORDS order.
+    Some(match i {
+        0 => Token::And { line },
+        1 => Token::Class { line },
+        2 => Token::Else { line },
+        3 => Token::False { line },
+        4 => Token::For { line },
+        5 => Token::Fun { line },
+        6 => Token::If { line },
+        7 => Token::Nil { line },
+        8 => Token::Or { line },
+        9 => Token::Print { line },
+        10 => Token::Return { line },
+        11 => Token::Super { line },
+        12 => Token::This { line },
+        13 => Token::True { line },
+        14 => Token::Var { line },
+        _ => Token::While { line },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn token_dump() {
+        assert_eq!(Token::LeftParen { line: 1 }.dump(), "1 LEFT_PAREN (");
+        assert_eq!(Token::BangEqual { line: 2 }.dump(), "2 BANG_EQUAL !=");
+        assert_eq!(
+            Token::Number {
+                lexeme: String::from("3.0"),
+                literal: 3.0,
+                line: 3
+            }
+            .dump(),
+            "3 NUMBER 3.0"
+        );
+        assert_eq!(
+            Token::r#String {
+                lexeme: String::from("hi"),
+                literal: String::from("hi"),
+                line: 1
+            }
+            .dump(),
+            "1 STRING hi"
+        );
+        assert_eq!(Token::Eof { line: 4 }.dump(), "4 EOF");
+    }
+    #[test]
+    fn token_keywords() {
+        for kw in KEYWORDS {
+            assert_eq!(
+                keyword(kw, 1).map(|t| t.to_string()),
+                Some(String::from(kw))
+            );
+        }
+        for ident in ["a", "andy", "classes", "printf", "whilst", "x1", "Print"] {
+            assert_eq!(keyword(ident, 1), None);
+        }
+    }
+}
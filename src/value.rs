@@ -1,12 +1,31 @@
+use crate::class::{LoxClass, LoxInstance};
 use crate::errors::InterpreterError;
-use float_eq::float_eq;
+use crate::function::Callable;
+use crate::string::LoxString;
+use num_bigint::BigInt;
+use num_traits::FromPrimitive;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(untagged)
+)]
 pub enum Value {
-    r#String(String),
+    r#String(LoxString),
     Number(f32),
+    BigInt(Rc<BigInt>),
+    Bytes(Rc<[u8]>),
     Bool(bool),
     Nil,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Callable(Callable),
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Class(Rc<LoxClass>),
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Instance(Rc<RefCell<LoxInstance>>),
 }
 
 impl PartialEq for Value {
@@ -17,7 +36,17 @@ impl PartialEq for Value {
                 _ => false,
             },
             Self::Number(n) => match other {
-                Self::Number(o) => float_eq!(n, o, abs <= 0.000_1),
+                Self::Number(o) => n == o,
+                Self::BigInt(o) => whole_number(*n).is_some_and(|n| n == **o),
+                _ => false,
+            },
+            Self::BigInt(b) => match other {
+                Self::BigInt(o) => b == o,
+                Self::Number(o) => whole_number(*o).is_some_and(|o| o == **b),
+                _ => false,
+            },
+            Self::Bytes(b) => match other {
+                Self::Bytes(o) => b == o,
                 _ => false,
             },
             Self::Bool(b) => match other {
@@ -25,6 +54,34 @@ impl PartialEq for Value {
                 _ => false,
             },
             Self::Nil => matches!(other, Self::Nil),
+            Self::Callable(c) => match other {
+                Self::Callable(o) => c == o,
+                _ => false,
+            },
+            Self::Class(c) => match other {
+                Self::Class(o) => Rc::ptr_eq(c, o),
+                _ => false,
+            },
+            Self::Instance(i) => match other {
+                Self::Instance(o) => Rc::ptr_eq(i, o),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::r#String(_) => "string",
+            Self::Number(_) => "number",
+            Self::BigInt(_) => "bigint",
+            Self::Bytes(_) => "bytes",
+            Self::Bool(_) => "bool",
+            Self::Nil => "nil",
+            Self::Callable(_) => "function",
+            Self::Class(_) => "class",
+            Self::Instance(_) => "instance",
         }
     }
 }
@@ -33,13 +90,57 @@ impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::r#String(s) => write!(f, "{}", s),
-            Self::Number(n) => write!(f, "{}", n),
+            Self::Number(n) => f.write_str(&format_number(*n)),
+            Self::BigInt(b) => write!(f, "{}", b),
+            Self::Bytes(b) => write!(f, "b\"{}\"", escape_bytes(b)),
             Self::Bool(b) => write!(f, "{}", b),
             Self::Nil => f.write_str("nil"),
+            Self::Callable(c) => write!(f, "{}", c),
+            Self::Class(c) => write!(f, "{}", c),
+            Self::Instance(i) => write!(f, "{}", i.borrow()),
         }
     }
 }
 
+fn escape_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            b'\n' => String::from("\\n"),
+            b'\r' => String::from("\\r"),
+            b'\t' => String::from("\\t"),
+            b'\\' => String::from("\\\\"),
+            b'"' => String::from("\\\""),
+            b' '..=b'~' => char::from(b).to_string(),
+            b => format!("\\x{:02x}", b),
+        })
+        .collect()
+}
+
+pub(crate) fn whole_number(n: f32) -> Option<BigInt> {
+    (n.fract() == 0.0).then(|| BigInt::from_f32(n)).flatten()
+}
+
+fn format_number(n: f32) -> String {
+    if n.is_nan() {
+        return String::from("NaN");
+    }
+    if n.is_infinite() {
+        return String::from(if n > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    let abs = n.abs();
+    if abs == 0.0 || (1e-3..1e7).contains(&abs) {
+        return n.to_string();
+    }
+    let s = format!("{:e}", n);
+    let (mantissa, exponent) = s.split_once('e').unwrap_or((&s, "0"));
+    if mantissa.contains('.') {
+        format!("{}E{}", mantissa, exponent)
+    } else {
+        format!("{}.0E{}", mantissa, exponent)
+    }
+}
+
 impl TryFrom<f32> for Value {
     type Error = InterpreterError;
 
@@ -48,11 +149,27 @@ impl TryFrom<f32> for Value {
     }
 }
 
+impl TryFrom<f64> for Value {
+    type Error = InterpreterError;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        Ok(Value::Number(value as f32))
+    }
+}
+
 impl TryFrom<String> for Value {
     type Error = InterpreterError;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        Ok(Value::r#String(value))
+        Ok(Value::r#String(value.into()))
+    }
+}
+
+impl TryFrom<&str> for Value {
+    type Error = InterpreterError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(Value::r#String(value.into()))
     }
 }
 
@@ -78,6 +195,16 @@ impl TryFrom<&Value> for f32 {
                 String::from("number"),
                 String::from("string"),
             )),
+            Value::Callable(_) => Err(InterpreterError::type_error(
+                String::from("number"),
+                String::from("function"),
+            )),
+            Value::BigInt(_) | Value::Bytes(_) | Value::Class(_) | Value::Instance(_) => {
+                Err(InterpreterError::type_error(
+                    String::from("number"),
+                    String::from(value.type_name()),
+                ))
+            }
             Value::Nil => Err(InterpreterError::type_error(
                 String::from("number"),
                 String::from("nil"),
@@ -86,6 +213,14 @@ impl TryFrom<&Value> for f32 {
     }
 }
 
+impl TryFrom<&Value> for f64 {
+    type Error = InterpreterError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        f32::try_from(value).map(f64::from)
+    }
+}
+
 impl TryFrom<&Value> for String {
     type Error = InterpreterError;
 
@@ -99,7 +234,17 @@ impl TryFrom<&Value> for String {
                 String::from("string"),
                 String::from("boolean"),
             )),
-            Value::r#String(s) => Ok(s.clone()),
+            Value::r#String(s) => Ok(s.to_string()),
+            Value::Callable(_) => Err(InterpreterError::type_error(
+                String::from("string"),
+                String::from("function"),
+            )),
+            Value::BigInt(_) | Value::Bytes(_) | Value::Class(_) | Value::Instance(_) => {
+                Err(InterpreterError::type_error(
+                    String::from("string"),
+                    String::from(value.type_name()),
+                ))
+            }
             Value::Nil => Err(InterpreterError::type_error(
                 String::from("string"),
                 String::from("nil"),
@@ -122,7 +267,166 @@ impl TryFrom<&Value> for bool {
                 String::from("boolean"),
                 String::from("string"),
             )),
+            Value::Callable(_) => Err(InterpreterError::type_error(
+                String::from("boolean"),
+                String::from("function"),
+            )),
+            Value::BigInt(_) | Value::Bytes(_) | Value::Class(_) | Value::Instance(_) => {
+                Err(InterpreterError::type_error(
+                    String::from("boolean"),
+                    String::from(value.type_name()),
+                ))
+            }
             Value::Nil => Ok(false),
         }
     }
 }
+
+#[cfg(feature = "serde")]
+impl TryFrom<Value> for serde_json::Value {
+    type Error = InterpreterError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::r#String(s) => Ok(serde_json::Value::String(s.to_string())),
+            Value::Number(n) => Ok(serde_json::Number::from_f64(f64::from(n))
+                .map_or(serde_json::Value::Null, serde_json::Value::Number)),
+            Value::Bool(b) => Ok(serde_json::Value::Bool(b)),
+            Value::Nil => Ok(serde_json::Value::Null),
+            Value::Callable(_) => Err(InterpreterError::type_error(
+                String::from("string, number, boolean or nil"),
+                String::from("function"),
+            )),
+            Value::BigInt(_) | Value::Bytes(_) | Value::Class(_) | Value::Instance(_) => {
+                Err(InterpreterError::type_error(
+                    String::from("string, number, boolean or nil"),
+                    String::from(value.type_name()),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<serde_json::Value> for Value {
+    type Error = InterpreterError;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        let expected = || String::from("string, number, boolean or null");
+        match value {
+            serde_json::Value::Null => Ok(Value::Nil),
+            serde_json::Value::Bool(b) => Ok(Value::Bool(b)),
+            serde_json::Value::Number(n) => {
+                n.as_f64()
+                    .map(|n| Value::Number(n as f32))
+                    .ok_or(InterpreterError::type_error(
+                        expected(),
+                        String::from("number"),
+                    ))
+            }
+            serde_json::Value::String(s) => Ok(Value::r#String(s.into())),
+            serde_json::Value::Array(_) => Err(InterpreterError::type_error(
+                expected(),
+                String::from("array"),
+            )),
+            serde_json::Value::Object(_) => Err(InterpreterError::type_error(
+                expected(),
+                String::from("object"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn value_number_display() {
+        let show = |n: f32| Value::Number(n).to_string();
+        assert_eq!(show(3.0), "3");
+        assert_eq!(show(-0.0), "-0");
+        assert_eq!(show(2.5), "2.5");
+        assert_eq!(show(0.1), "0.1");
+        assert_eq!(show(1.0 / 3.0), "0.33333334");
+        assert_eq!(show(1234567.0), "1234567");
+        assert_eq!(show(1e7), "1.0E7");
+        assert_eq!(show(1.5e-5), "1.5E-5");
+        assert_eq!(show(-2.5e10), "-2.5E10");
+        assert_eq!(show(f32::INFINITY), "Infinity");
+        assert_eq!(show(f32::NEG_INFINITY), "-Infinity");
+        assert_eq!(show(f32::NAN), "NaN");
+    }
+    #[test]
+    fn value_ieee_equality() {
+        assert_ne!(Value::Number(0.000_05), Value::Number(0.000_09));
+        assert_ne!(Value::Number(f32::NAN), Value::Number(f32::NAN));
+        assert_eq!(Value::Number(0.0), Value::Number(-0.0));
+        assert_eq!(Value::Number(0.5 + 0.25), Value::Number(0.75));
+    }
+    #[test]
+    fn value_from_rust() -> Result<(), InterpreterError> {
+        assert_eq!(Value::try_from(1.5_f64)?, Value::Number(1.5));
+        assert_eq!(Value::try_from("hi")?, Value::r#String("hi".into()));
+        assert_eq!(f64::try_from(&Value::Number(2.0))?, 2.0);
+        assert!(f64::try_from(&Value::Nil).is_err());
+        Ok(())
+    }
+    #[test]
+    fn value_string_clone_is_shared() {
+        let s = Value::r#String("shared".into());
+        match (&s, &s.clone()) {
+            (Value::r#String(a), Value::r#String(b)) => assert!(a.shares_buffer(b)),
+            other => panic!("expected strings, got {:?}", other),
+        }
+    }
+    #[test]
+    fn value_callable() -> Result<(), InterpreterError> {
+        let globals = crate::natives::globals();
+        let clock = globals.get("clock")?;
+        let Value::Callable(callable) = &clock else {
+            panic!("expected a callable, got {:?}", clock);
+        };
+        assert_eq!(callable.name(), "clock");
+        assert_eq!(callable.arity(), 0);
+        assert_eq!(clock.to_string(), "<native fn clock>");
+        assert_eq!(clock.type_name(), "function");
+        assert_eq!(clock, globals.get("clock")?);
+        assert_ne!(clock, globals.get("now")?);
+        assert!(matches!(
+            f32::try_from(&clock),
+            Err(InterpreterError::Type { .. })
+        ));
+        assert!(bool::try_from(&clock).is_err());
+        Ok(())
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn value_serde_json() -> Result<(), InterpreterError> {
+        use serde_json::json;
+        assert_eq!(serde_json::Value::try_from(Value::Number(2.0))?, json!(2.0));
+        assert_eq!(serde_json::Value::try_from(Value::Nil)?, json!(null));
+        assert_eq!(
+            serde_json::Value::try_from(Value::r#String("hi".into()))?,
+            json!("hi")
+        );
+        assert_eq!(Value::try_from(json!(true))?, Value::Bool(true));
+        assert_eq!(Value::try_from(json!(3))?, Value::Number(3.0));
+        assert!(Value::try_from(json!([1, 2])).is_err());
+        assert!(Value::try_from(json!({"a": 1})).is_err());
+        Ok(())
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn value_serde_round_trip() {
+        let values = vec![
+            Value::Number(1.5),
+            Value::r#String("hi".into()),
+            Value::Bool(false),
+            Value::Nil,
+        ];
+        let s = serde_json::to_string(&values).unwrap();
+        assert_eq!(s, r#"[1.5,"hi",false,null]"#);
+        let back: Vec<Value> = serde_json::from_str(&s).unwrap();
+        assert_eq!(back, values);
+    }
+}
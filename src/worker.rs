@@ -0,0 +1,195 @@
+use crate::cancel::CancelToken;
+use crate::errors::{InterpreterError, InterpreterResult};
+use crate::lox::Lox;
+use crate::value::Value;
+use num_bigint::BigInt;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+pub struct LoxWorker {
+    jobs: Sender<Job>,
+    cancel: CancelToken,
+}
+
+enum Job {
+    Eval {
+        source: String,
+        reply: Sender<InterpreterResult<Sendable>>,
+    },
+    Define {
+        name: String,
+        value: Sendable,
+    },
+    Get {
+        name: String,
+        reply: Sender<Option<InterpreterResult<Sendable>>>,
+    },
+}
+
+pub(crate) enum Sendable {
+    r#String(String),
+    Number(f32),
+    BigInt(BigInt),
+    Bytes(Vec<u8>),
+    Bool(bool),
+    Nil,
+}
+
+impl LoxWorker {
+    pub fn new() -> InterpreterResult<Self> {
+        let (jobs, rx) = mpsc::channel();
+        let (token, cancel) = mpsc::channel();
+        thread::Builder::new()
+            .name(String::from("lox"))
+            .spawn(move || run(rx, token))?;
+        let cancel = cancel.recv().map_err(|_| stopped())?;
+        Ok(Self { jobs, cancel })
+    }
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+    pub fn eval_str(&self, src: &str) -> InterpreterResult<Value> {
+        let (reply, rx) = mpsc::channel();
+        self.send(Job::Eval {
+            source: String::from(src),
+            reply,
+        })?;
+        rx.recv().map_err(|_| stopped())?.map(Value::from)
+    }
+    pub fn define_global<T>(&self, name: T, value: Value) -> InterpreterResult<()>
+    where
+        T: Into<String>,
+    {
+        self.send(Job::Define {
+            name: name.into(),
+            value: Sendable::try_from(value)?,
+        })
+    }
+    pub fn get_global(&self, name: &str) -> InterpreterResult<Option<Value>> {
+        let (reply, rx) = mpsc::channel();
+        self.send(Job::Get {
+            name: String::from(name),
+            reply,
+        })?;
+        rx.recv()
+            .map_err(|_| stopped())?
+            .map(|v| v.map(Value::from))
+            .transpose()
+    }
+    fn send(&self, job: Job) -> InterpreterResult<()> {
+        self.jobs.send(job).map_err(|_| stopped())
+    }
+}
+
+fn run(jobs: Receiver<Job>, token: Sender<CancelToken>) {
+    let mut lox = Lox::new();
+    _ = token.send(lox.cancel_token());
+    for job in jobs {
+        match job {
+            Job::Eval { source, reply } => {
+                _ = reply.send(lox.eval_str(&source).and_then(Sendable::try_from));
+            }
+            Job::Define { name, value } => lox.define_global(name, Value::from(value)),
+            Job::Get { name, reply } => {
+                _ = reply.send(lox.get_global(&name).map(Sendable::try_from));
+            }
+        }
+    }
+}
+
+fn stopped() -> InterpreterError {
+    InterpreterError::RuntimeError {
+        line: 0,
+        message: "Interpreter thread has stopped".into(),
+    }
+}
+
+impl TryFrom<Value> for Sendable {
+    type Error = InterpreterError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::r#String(s) => Ok(Sendable::r#String(s.to_string())),
+            Value::Number(n) => Ok(Sendable::Number(n)),
+            Value::BigInt(n) => Ok(Sendable::BigInt(BigInt::clone(&n))),
+            Value::Bytes(b) => Ok(Sendable::Bytes(b.to_vec())),
+            Value::Bool(b) => Ok(Sendable::Bool(b)),
+            Value::Nil => Ok(Sendable::Nil),
+            Value::Callable(_) | Value::Class(_) | Value::Instance(_) => {
+                Err(InterpreterError::type_error(
+                    String::from("string, number, boolean or nil"),
+                    String::from(value.type_name()),
+                ))
+            }
+        }
+    }
+}
+
+impl From<Sendable> for Value {
+    fn from(value: Sendable) -> Self {
+        match value {
+            Sendable::r#String(s) => Value::r#String(s.into()),
+            Sendable::Number(n) => Value::Number(n),
+            Sendable::BigInt(n) => Value::BigInt(Rc::new(n)),
+            Sendable::Bytes(b) => Value::Bytes(Rc::from(b)),
+            Sendable::Bool(b) => Value::Bool(b),
+            Sendable::Nil => Value::Nil,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    fn assert_send_sync<T: Send + Sync>() {}
+    #[test]
+    fn worker_is_send_sync() {
+        assert_send_sync::<LoxWorker>();
+        assert_send_sync::<InterpreterError>();
+    }
+    #[test]
+    fn worker_eval_str() -> InterpreterResult<()> {
+        let worker = LoxWorker::new()?;
+        worker.define_global("a", Value::Number(1.0))?;
+        assert_eq!(worker.eval_str("a + 2;")?, Value::Number(3.0));
+        assert_eq!(worker.get_global("a")?, Some(Value::Number(1.0)));
+        assert_eq!(worker.get_global("nope")?, None);
+        assert!(worker.eval_str("fun f() {} f;").is_err());
+        Ok(())
+    }
+    #[test]
+    fn worker_across_threads() -> InterpreterResult<()> {
+        let worker = Arc::new(LoxWorker::new()?);
+        worker.eval_str("var count = 0; fun bump() { count = count + 1; }")?;
+        let handles = (0..4)
+            .map(|_| {
+                let worker = Arc::clone(&worker);
+                std::thread::spawn(move || worker.eval_str("bump();").map(|_| ()))
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+        assert_eq!(worker.get_global("count")?, Some(Value::Number(4.0)));
+        Ok(())
+    }
+    #[test]
+    fn worker_cancel() -> InterpreterResult<()> {
+        let worker = Arc::new(LoxWorker::new()?);
+        let canceller = {
+            let worker = Arc::clone(&worker);
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                worker.cancel();
+            })
+        };
+        assert!(matches!(
+            worker.eval_str("while (true) {}"),
+            Err(InterpreterError::Interrupted)
+        ));
+        canceller.join().unwrap();
+        Ok(())
+    }
+}